@@ -0,0 +1,285 @@
+//! Simple task scheduler
+//!
+//! Cooperative and hardware-independent: a `Task<C>` calls a plain
+//! `fn(&mut C)`, where `C` is whatever context the firmware hands its tasks
+//! (lesson 03 passes its I2C bus). Time is a `u64` of milliseconds from the
+//! caller, so `cargo test` drives the same code with a simulated clock.
+
+#![no_std]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use log::warn;
+
+/// Missed deadlines across all tasks, for a monitoring task to print
+pub static MISSED_DEADLINES: AtomicU32 = AtomicU32::new(0);
+
+/// A task that runs at a fixed period, or once after a delay
+pub struct Task<C> {
+    pub run: fn(&mut C),
+    pub period_ms: u64,
+    pub last_run: u64,
+    /// Higher runs first when several tasks are due in the same tick
+    pub priority: u8,
+    /// Times `execute` has run this task
+    pub run_count: u32,
+    /// false for a one-shot task: it disables itself after running
+    pub repeat: bool,
+    /// Cleared once a one-shot task has fired; disabled tasks never run
+    pub enabled: bool,
+    /// Runs that came due more than two periods after the previous one
+    pub missed_deadlines: u32,
+    /// After falling behind by several periods: true runs the missed ones
+    /// back-to-back, false (the default) skips them and waits for the next
+    pub catch_up: bool,
+}
+
+impl<C> Task<C> {
+    /// Create a task that first runs one period after start
+    pub const fn new(run: fn(&mut C), period_ms: u64, priority: u8) -> Self {
+        Self {
+            run,
+            period_ms,
+            last_run: 0,
+            priority,
+            run_count: 0,
+            repeat: true,
+            enabled: true,
+            missed_deadlines: 0,
+            catch_up: false,
+        }
+    }
+
+    /// Create a one-shot task that runs once, `delay_ms` after start
+    ///
+    /// To run it relative to some later event instead (e.g. turn an LED off
+    /// 2 s after a button press), create it disabled and call `arm`.
+    pub const fn once(run: fn(&mut C), delay_ms: u64, priority: u8) -> Self {
+        let mut task = Self::new(run, delay_ms, priority);
+        task.repeat = false;
+        task
+    }
+
+    /// (Re)start the task so it next runs one period after `now`
+    pub fn arm(&mut self, now: u64) {
+        self.last_run = now;
+        self.enabled = true;
+    }
+
+    /// Check if this task should run based on current time
+    ///
+    /// A `last_run` ahead of `now` (a task scheduled to start later, or a
+    /// time source that stepped back) counts as not due yet.
+    ///
+    /// A due task more than two periods past its last run missed a deadline,
+    /// usually because a task before it overran: that is counted (here and
+    /// in `MISSED_DEADLINES`) and logged as a warning.
+    pub fn should_run(&mut self, now: u64) -> bool {
+        let elapsed = now.saturating_sub(self.last_run);
+        if !self.enabled || elapsed < self.period_ms {
+            return false;
+        }
+        if elapsed > 2 * self.period_ms {
+            self.missed_deadlines = self.missed_deadlines.wrapping_add(1);
+            MISSED_DEADLINES.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Task missed its deadline: {} ms since last run (period {} ms)",
+                elapsed, self.period_ms
+            );
+        }
+        true
+    }
+
+    /// Execute the task and schedule the next run
+    ///
+    /// Runs stay on a fixed grid: `last_run` advances by `period_ms` from
+    /// the previous scheduled time, not from `now`, so lateness (the tick
+    /// granularity, a slow task before this one) doesn't accumulate and the
+    /// average rate matches `period_ms` exactly. See `catch_up` for what
+    /// happens when the task fell more than a period behind.
+    pub fn execute(&mut self, now: u64, ctx: &mut C) {
+        (self.run)(ctx);
+        self.advance(now);
+    }
+
+    /// Move `last_run` to the next grid point and do the run bookkeeping
+    fn advance(&mut self, now: u64) {
+        let period = self.period_ms.max(1);
+        let steps = if self.catch_up {
+            1
+        } else {
+            // Latest grid point not after `now`: skip the missed runs
+            (now.saturating_sub(self.last_run) / period).max(1)
+        };
+        self.finish(self.last_run + steps * period);
+    }
+
+    /// Bookkeeping after a run: record it and retire one-shot tasks
+    fn finish(&mut self, last_run: u64) {
+        self.last_run = last_run;
+        self.run_count = self.run_count.wrapping_add(1);
+        if !self.repeat {
+            self.enabled = false;
+        }
+    }
+}
+
+/// Order tasks by descending priority, keeping array order for equal ones
+///
+/// Call once after building the task array; the main loop then runs due
+/// tasks in array order, i.e. highest priority first within a tick.
+pub fn sort_by_priority<C>(tasks: &mut [Task<C>]) {
+    // Insertion sort: stable, no allocation, and task lists are tiny
+    for i in 1..tasks.len() {
+        let mut j = i;
+        while j > 0 && tasks[j - 1].priority < tasks[j].priority {
+            tasks.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop(_ctx: &mut ()) {}
+
+    #[test]
+    fn test_future_last_run_waits_for_time_to_catch_up() {
+        let mut task: Task<()> = Task::new(noop, 100, 0);
+        task.last_run = 1000;
+
+        // Before last_run: no underflow, not due
+        assert!(!task.should_run(0));
+        assert!(!task.should_run(999));
+        // At last_run the period still has to elapse
+        assert!(!task.should_run(1000));
+        assert!(!task.should_run(1099));
+        assert!(task.should_run(1100));
+    }
+
+    /// Context for the tests: the times the task body ran at
+    struct Log {
+        now: u64,
+        runs: [u64; 4],
+        len: usize,
+    }
+
+    fn log_run(log: &mut Log) {
+        log.runs[log.len] = log.now;
+        log.len += 1;
+    }
+
+    #[test]
+    fn test_one_shot_fires_exactly_once() {
+        let mut task: Task<Log> = Task::once(log_run, 2000, 0);
+        let mut log = Log { now: 0, runs: [0; 4], len: 0 };
+
+        // Same loop as main
+        for now in (0..10_000).step_by(10) {
+            log.now = now;
+            if task.should_run(now) {
+                task.execute(now, &mut log);
+            }
+        }
+        assert_eq!(log.runs[..log.len], [2000]);
+        assert_eq!(task.run_count, 1);
+        assert!(!task.enabled);
+
+        // Re-arming schedules it once more, relative to the new start
+        task.arm(10_000);
+        for now in (10_000..20_000).step_by(10) {
+            log.now = now;
+            if task.should_run(now) {
+                task.execute(now, &mut log);
+            }
+        }
+        assert_eq!(log.runs[..log.len], [2000, 12_000]);
+        assert_eq!(task.run_count, 2);
+    }
+
+    /// Context for the overrun test: the simulated system timer
+    struct Clock {
+        now: u64,
+    }
+
+    fn quick(_clock: &mut Clock) {}
+
+    fn overrun(clock: &mut Clock) {
+        clock.now += 350;
+    }
+
+    #[test]
+    fn test_overrunning_task_makes_the_next_one_miss_its_deadline() {
+        let mut tasks: [Task<Clock>; 2] = [Task::new(quick, 100, 0), Task::new(overrun, 1000, 1)];
+        sort_by_priority(&mut tasks);
+        let mut clock = Clock { now: 0 };
+
+        // Same loop as main, but each run of the slow task takes 350 ms
+        // and main reads the time once per tick, before running anything
+        while clock.now < 3500 {
+            clock.now += 10;
+            let now = clock.now;
+            for task in &mut tasks {
+                if task.should_run(now) {
+                    task.execute(now, &mut clock);
+                }
+            }
+        }
+        let [slow, fast] = &tasks;
+        // Slow ran at 1000, 2000 and 3000; fast was checked 350 ms late after each
+        assert_eq!(slow.run_count, 3);
+        assert_eq!(slow.missed_deadlines, 0);
+        assert_eq!(fast.missed_deadlines, 3);
+        assert!(MISSED_DEADLINES.load(Ordering::Relaxed) >= 3);
+    }
+
+    fn count_run(runs: &mut u32) {
+        *runs += 1;
+    }
+
+    /// Main's loop with 1 ms of work per 10 ms tick, optionally stalling
+    /// 350 ms at `stall_at`; returns (runs, latest run past its grid point, last run)
+    fn simulate(task: &mut Task<u32>, until: u64, stall_at: Option<u64>) -> (u32, u64, u64) {
+        let (mut runs, mut max_lag, mut last) = (0, 0, 0);
+        let mut now = 0;
+        while now < until {
+            now += 11;
+            if stall_at.is_some_and(|t| now >= t && now < t + 11) {
+                now += 350;
+            }
+            if task.should_run(now) {
+                task.execute(now, &mut runs);
+                max_lag = max_lag.max(now % task.period_ms);
+                last = now;
+            }
+        }
+        (runs, max_lag, last)
+    }
+
+    #[test]
+    fn test_fixed_rate_does_not_drift() {
+        // Measuring each period from the (late) run time would give ~90 runs
+        let mut task: Task<u32> = Task::new(count_run, 100, 0);
+        let (runs, max_lag, _) = simulate(&mut task, 10_000, None);
+        assert!((99..=100).contains(&runs), "{} runs", runs);
+        // Never more than a tick behind its 100 ms grid point
+        assert!(max_lag < 11, "{} ms late", max_lag);
+    }
+
+    #[test]
+    fn test_catch_up_runs_missed_periods_back_to_back() {
+        let mut skip: Task<u32> = Task::new(count_run, 100, 0);
+        let (skipped, _, skip_last) = simulate(&mut skip, 10_000, Some(5000));
+        let mut catch_up: Task<u32> = Task::new(count_run, 100, 0);
+        catch_up.catch_up = true;
+        let (caught_up, _, catch_up_last) = simulate(&mut catch_up, 10_000, Some(5000));
+
+        // Catching up keeps every run; skipping drops the three missed ones
+        assert!((99..=100).contains(&caught_up), "{} runs", caught_up);
+        assert_eq!(skipped + 3, caught_up);
+        // Either way the task is back on the 100 ms grid afterwards
+        assert!(skip_last % 100 < 11);
+        assert!(catch_up_last % 100 < 11);
+    }
+}
@@ -31,7 +31,9 @@ use esp_hal::{
 };
 use esp_hal_smartled::{buffer_size, color_order, SmartLedsAdapter, Ws2812Timing};
 use lesson_02_task_scheduler::{
-    button, neopixel, scheduler::Scheduler, BUTTON_GPIO, NEOPIXEL_GPIO, RMT_CLOCK_MHZ,
+    button::{self, ButtonEvent},
+    neopixel, scheduler::Scheduler, set_led_enabled, toggle_led_enabled, BUTTON_GPIO,
+    NEOPIXEL_GPIO, RMT_CLOCK_MHZ,
 };
 use log::info;
 
@@ -80,7 +82,7 @@ fn main() -> ! {
     let mut scheduler = Scheduler::new();
 
     info!("✓ Scheduler initialized\n");
-    info!("Press button to toggle LED!\n");
+    info!("Press button to toggle LED, double-click for on, hold to turn off\n");
 
     // ========================================================================
     // MAIN SCHEDULER LOOP
@@ -89,7 +91,21 @@ fn main() -> ! {
     loop {
         scheduler.tick(
             &delay,
-            || button::button_task(&button),
+            || match button::button_task(&button) {
+                ButtonEvent::None => {}
+                ButtonEvent::SinglePress => {
+                    info!("📍 [button_task] Press - LED toggled");
+                    toggle_led_enabled();
+                }
+                ButtonEvent::DoublePress => {
+                    info!("📍 [button_task] Double-click - LED on");
+                    set_led_enabled(true);
+                }
+                ButtonEvent::LongPress => {
+                    info!("📍 [button_task] Long press - LED off");
+                    set_led_enabled(false);
+                }
+            },
             || neopixel::led_task(&mut led),
         );
     }
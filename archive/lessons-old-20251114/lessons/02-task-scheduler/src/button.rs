@@ -1,11 +1,24 @@
 //! Button input handling with edge detection and debouncing.
 //!
-//! This module reads button state and detects press events (LOW → HIGH transition).
-//! When a press is detected, it toggles the shared LED_ENABLED atomic.
+//! This module reads button state and turns it into events: a single press,
+//! a double-click (two presses within DOUBLE_CLICK_MS) or a long press (held
+//! for LONG_PRESS_MS). The caller decides what each event does.
+//!
+//! A single press is only reported once the double-click window has passed
+//! without a second press, so it arrives DOUBLE_CLICK_MS after the button
+//! went down.
+
+use crate::{BUTTON_PERIOD_MS, DEBOUNCE_MS, DOUBLE_CLICK_MS, LONG_PRESS_MS};
+use esp_hal::{gpio::Input, time::Instant};
 
-use crate::{toggle_led_enabled, BUTTON_PERIOD_MS, DEBOUNCE_MS};
-use esp_hal::gpio::Input;
-use log::info;
+/// What `button_task` saw on this call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    None,
+    SinglePress,
+    DoublePress,
+    LongPress,
+}
 
 /// Button state for edge detection
 static mut BUTTON_WAS_PRESSED: bool = false;
@@ -13,39 +26,73 @@ static mut BUTTON_WAS_PRESSED: bool = false;
 /// Debounce counter - tracks calls since last press
 static mut DEBOUNCE_COUNTER: u32 = 0;
 
+/// Time of the press that may still become a double-click (ms since boot)
+static mut PENDING_PRESS_MS: Option<u64> = None;
+
+/// Time the button went down, while it is held and may become a long press
+static mut HELD_SINCE_MS: Option<u64> = None;
+
 /// Calculate how many task calls equal the debounce period
 const DEBOUNCE_CALLS: u32 = (DEBOUNCE_MS as u64 / BUTTON_PERIOD_MS) as u32;
 
-/// Button task: Read button state and update shared LED state
+/// Button task: Read button state and report press events
 ///
 /// This function should be called periodically by the scheduler (every 10ms).
-/// It detects button press events (transition from released to pressed)
-/// and toggles the LED state atomically.
+/// It detects button press events (transition from released to pressed) and
+/// classifies them as single, double or long presses.
 ///
 /// Uses time-based debouncing that doesn't block the scheduler.
 ///
 /// # Arguments
 /// * `button` - Reference to the GPIO input pin
-pub fn button_task(button: &Input) {
+pub fn button_task(button: &Input) -> ButtonEvent {
     let button_pressed = button.is_low();
+    let now_ms = Instant::now().duration_since_epoch().as_millis();
 
     unsafe {
+        // Long press: still held LONG_PRESS_MS after going down. Releases
+        // are ignored while debouncing, the contacts may still be bouncing.
+        if let Some(since) = HELD_SINCE_MS {
+            if !button_pressed && DEBOUNCE_COUNTER == 0 {
+                HELD_SINCE_MS = None;
+            } else if now_ms.saturating_sub(since) >= LONG_PRESS_MS as u64 {
+                HELD_SINCE_MS = None;
+                PENDING_PRESS_MS = None;
+                return ButtonEvent::LongPress;
+            }
+        }
+
+        // Single press: the double-click window ran out and it wasn't held
+        if let Some(first) = PENDING_PRESS_MS {
+            let held = HELD_SINCE_MS;
+            if held.is_none() && now_ms.saturating_sub(first) >= DOUBLE_CLICK_MS as u64 {
+                PENDING_PRESS_MS = None;
+                return ButtonEvent::SinglePress;
+            }
+        }
+
         // Decrement debounce counter if active
         if DEBOUNCE_COUNTER > 0 {
             DEBOUNCE_COUNTER -= 1;
             // Update button state but don't process press
             BUTTON_WAS_PRESSED = button_pressed;
-            return;
+            return ButtonEvent::None;
         }
 
+        let mut event = ButtonEvent::None;
+
         // Detect button press (transition to LOW, since button is active LOW)
         if button_pressed && !BUTTON_WAS_PRESSED {
-            info!("📍 [button_task] Button press detected!");
-
-            // Toggle LED state using atomic operation
-            toggle_led_enabled();
-
-            info!("📍 [button_task] LED toggled");
+            let pending = PENDING_PRESS_MS;
+            if pending.is_some() {
+                // Second press inside the window; holding it on doesn't
+                // also make it a long press
+                PENDING_PRESS_MS = None;
+                event = ButtonEvent::DoublePress;
+            } else {
+                PENDING_PRESS_MS = Some(now_ms);
+                HELD_SINCE_MS = Some(now_ms);
+            }
 
             // Start debounce period (non-blocking)
             DEBOUNCE_COUNTER = DEBOUNCE_CALLS;
@@ -53,5 +100,6 @@ pub fn button_task(button: &Input) {
 
         // Update previous state for next edge detection
         BUTTON_WAS_PRESSED = button_pressed;
+        event
     }
 }
@@ -39,6 +39,12 @@ pub const TICK_MS: u64 = 10;
 /// Debounce delay after button press detection (200ms)
 pub const DEBOUNCE_MS: u32 = 200;
 
+/// Second press within this long of the first one is a double-click (400ms)
+pub const DOUBLE_CLICK_MS: u32 = 400;
+
+/// Holding the button this long is a long press (1000ms)
+pub const LONG_PRESS_MS: u32 = 1000;
+
 // ============================================================================
 // LED COLOR CONFIGURATION
 // ============================================================================
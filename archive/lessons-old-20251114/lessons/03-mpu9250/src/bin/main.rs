@@ -23,13 +23,14 @@ use esp_hal::{
     delay::Delay,
     i2c::master::{I2c, Config as I2cConfig},
     main,
+    time::Instant,
     Blocking,
 };
 use log::info;
 
 use lesson_03_mpu9250::mpu9250;
-use lesson_03_mpu9250::scheduler::{Context, Task};
-use lesson_03_mpu9250::tasks::imu_task;
+use lesson_03_mpu9250::scheduler::{sort_by_priority, Task};
+use lesson_03_mpu9250::tasks::{imu_task, stats_task, Context};
 
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
@@ -81,25 +82,28 @@ fn main() -> ! {
 
     info!("✓ Task scheduler ready\n");
 
-    // Create task list
-    let mut tasks: [Task<Blocking>; 1] = [Task {
-        run: imu_task,
-        period_ms: 500,
-        last_run: 0,
-    }];
+    // Create task list: Task::new(run, period_ms, priority)
+    let mut tasks: [Task<Context<Blocking>>; 2] = [
+        Task::new(imu_task, 500, 1),
+        Task::new(stats_task, 5000, 0),
+    ];
+    sort_by_priority(&mut tasks);
 
     let mut ctx = Context { i2c: &mut i2c };
 
     info!("🔄 Starting sensor readings...\n");
 
-    // Simple cooperative scheduler
-    let mut current_time_ms: u64 = 0;
-    const TICK_MS: u64 = 10;
+    // Simple cooperative scheduler. Time comes from the system timer, so
+    // a slow task makes the ones after it late (see missed_deadlines).
+    const TICK_MS: u32 = 10;
 
     loop {
-        delay.delay_millis(TICK_MS as u32);
-        current_time_ms += TICK_MS;
+        delay.delay_millis(TICK_MS);
+        let current_time_ms = Instant::now().duration_since_epoch().as_millis();
 
+        // Sorted by priority, so due tasks run highest priority first.
+        // Finished one-shot tasks stay in the array but are disabled, and
+        // should_run skips them.
         for task in &mut tasks {
             if task.should_run(current_time_ms) {
                 task.execute(current_time_ms, &mut ctx);
@@ -5,5 +5,5 @@
 #![no_std]
 
 pub mod mpu9250;
-pub mod scheduler;
+pub use scheduler;
 pub mod tasks;
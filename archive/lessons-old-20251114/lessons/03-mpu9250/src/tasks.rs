@@ -1,10 +1,17 @@
 //! Task implementations
 
 use crate::mpu9250;
-use crate::scheduler::Context;
+use crate::scheduler::MISSED_DEADLINES;
+use core::sync::atomic::Ordering;
+use esp_hal::i2c::master::I2c;
 use esp_hal::DriverMode;
 use log::info;
 
+/// Context passed to all tasks - holds hardware references
+pub struct Context<'a, Dm: DriverMode> {
+    pub i2c: &'a mut I2c<'a, Dm>,
+}
+
 /// Task: Read IMU sensor data and print to console
 pub fn imu_task<Dm: DriverMode>(ctx: &mut Context<Dm>) {
     // Read accelerometer
@@ -29,3 +36,11 @@ pub fn imu_task<Dm: DriverMode>(ctx: &mut Context<Dm>) {
 
     info!("");
 }
+
+/// Task: report scheduler overload (deadlines missed since boot)
+pub fn stats_task<Dm: DriverMode>(_ctx: &mut Context<Dm>) {
+    info!(
+        "[Sched] Missed deadlines: {}",
+        MISSED_DEADLINES.load(Ordering::Relaxed)
+    );
+}
@@ -0,0 +1,142 @@
+//! CLI command history for up/down-arrow recall
+//!
+//! Keeps the last `HISTORY_LEN` entered lines. Up (`ESC [ A`) walks back
+//! from the newest entry, down (`ESC [ B`) walks forward and past the newest
+//! one returns to an empty line. Empty lines and repeats of the previous
+//! entry are not stored.
+
+use heapless::{Deque, String};
+
+pub const HISTORY_LEN: usize = 8;
+
+/// Where the CLI is in an ANSI escape sequence (`ESC [ <final>`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Escape {
+    Idle,
+    /// Got ESC, expecting `[`
+    Esc,
+    /// Got `ESC [`, the next byte is the final one
+    Csi,
+}
+
+/// Last entered lines, oldest first; `N` is the CLI buffer size
+pub struct History<const N: usize> {
+    entries: Deque<String<N>, HISTORY_LEN>,
+    /// Entry currently shown (index into `entries`), None while editing a new line
+    cursor: Option<usize>,
+}
+
+impl<const N: usize> History<N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Deque::new(),
+            cursor: None,
+        }
+    }
+
+    /// Store an entered line (dropping the oldest when full) and stop browsing
+    pub fn push(&mut self, line: &str) {
+        self.cursor = None;
+        let line = line.trim();
+        if line.is_empty() || self.entries.back().is_some_and(|last| last == line) {
+            return;
+        }
+        let Ok(entry) = String::try_from(line) else {
+            return;
+        };
+        if self.entries.is_full() {
+            self.entries.pop_front();
+        }
+        let _ = self.entries.push_back(entry);
+    }
+
+    /// Up arrow: the next older entry, or None if there is no history.
+    /// Stays on the oldest entry once reached.
+    pub fn older(&mut self) -> Option<&str> {
+        let index = match self.cursor {
+            None => self.entries.len().checked_sub(1)?,
+            Some(i) => i.saturating_sub(1),
+        };
+        self.cursor = Some(index);
+        self.entries.iter().nth(index).map(|e| e.as_str())
+    }
+
+    /// Down arrow: the next newer entry; `Some("")` when moving past the
+    /// newest one, None when not browsing
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.cursor? + 1;
+        if index >= self.entries.len() {
+            self.cursor = None;
+            return Some("");
+        }
+        self.cursor = Some(index);
+        self.entries.iter().nth(index).map(|e| e.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<const N: usize> Default for History<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse() {
+        let mut history: History<32> = History::new();
+        assert_eq!(history.older(), None);
+        assert_eq!(history.newer(), None);
+
+        history.push("imu.read");
+        history.push("state.get");
+        assert_eq!(history.older(), Some("state.get"));
+        assert_eq!(history.older(), Some("imu.read"));
+        assert_eq!(history.older(), Some("imu.read")); // stays on the oldest
+        assert_eq!(history.newer(), Some("state.get"));
+        assert_eq!(history.newer(), Some("")); // back to a blank line
+        assert_eq!(history.newer(), None);
+
+        // Entering a line ends browsing: up starts from the newest again
+        history.older();
+        history.push("ping");
+        assert_eq!(history.older(), Some("ping"));
+    }
+
+    #[test]
+    fn test_skips_empty_and_repeats() {
+        let mut history: History<32> = History::new();
+        history.push("");
+        history.push("   ");
+        history.push("imu.read");
+        history.push("imu.read");
+        history.push("state.get");
+        history.push("imu.read");
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn test_drops_oldest_when_full() {
+        let mut history: History<32> = History::new();
+        for i in 0..HISTORY_LEN + 2 {
+            let mut line: String<32> = String::new();
+            core::fmt::write(&mut line, format_args!("cmd{}", i)).unwrap();
+            history.push(&line);
+        }
+        assert_eq!(history.len(), HISTORY_LEN);
+        for _ in 0..HISTORY_LEN {
+            history.older();
+        }
+        assert_eq!(history.older(), Some("cmd2"));
+    }
+}
@@ -0,0 +1,245 @@
+//! Table-driven command dispatch for the UART CLIs
+//!
+//! Each lesson describes its commands as a `CommandTable` and hands every
+//! received line to `dispatch`. The table is also the source of the `help`
+//! text, so a command can't be accepted without being listed (or vice versa).
+//! `LineReader` (`line.rs`) turns the received bytes into those lines.
+//!
+//! Handlers don't write to the UART: they return the reply text
+//! (`CommandOutput`) or the reason they failed (`CmdError`), and the caller
+//! prints it with `write_result`. That keeps them testable without hardware.
+
+#![no_std]
+
+pub mod history;
+pub mod line;
+
+pub use line::{LineReader, PROMPT};
+
+use core::fmt::{self, Write};
+use heapless::String;
+
+/// Most arguments a command takes after its name
+pub const MAX_ARGS: usize = 8;
+
+/// Column the help text starts at (`  <usage padded>- <help>`)
+pub const HELP_USAGE_WIDTH: usize = 20;
+
+/// Longest reply a command can return (`help` is the longest by far)
+pub const OUTPUT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmdError {
+    /// No command with that name
+    Unknown,
+    /// More than `MAX_ARGS` arguments
+    TooManyArgs,
+    /// Missing or malformed arguments; reported with the command's usage
+    Usage,
+    /// Arguments parsed but were rejected, with the reason
+    Invalid(&'static str),
+}
+
+/// Reply text of a successful command, lines ending in `\r\n`
+///
+/// Built with `write!` like the `String`s it replaces; text past
+/// `OUTPUT_CAPACITY` is dropped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput(String<OUTPUT_CAPACITY>);
+
+impl CommandOutput {
+    pub const fn new() -> Self {
+        Self(String::new())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for CommandOutput {
+    fn from(text: &str) -> Self {
+        let mut output = Self::new();
+        let _ = output.write_str(text);
+        output
+    }
+}
+
+impl Write for CommandOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+pub type CmdResult = Result<CommandOutput, CmdError>;
+
+pub struct Command<C> {
+    pub name: &'static str,
+    /// Name plus arguments, as shown by `help` and usage errors
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub handler: fn(&[&str], &mut C) -> CmdResult,
+}
+
+pub struct CommandTable<C: 'static> {
+    commands: &'static [Command<C>],
+}
+
+impl<C> CommandTable<C> {
+    pub const fn new(commands: &'static [Command<C>]) -> Self {
+        Self { commands }
+    }
+
+    pub fn commands(&self) -> &'static [Command<C>] {
+        self.commands
+    }
+
+    pub fn find(&self, name: &str) -> Option<&'static Command<C>> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    /// Run the command on `line`. Blank lines are a no-op with no output.
+    pub fn dispatch(&self, line: &str, ctx: &mut C) -> CmdResult {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(CommandOutput::new());
+        };
+        let command = self.find(name).ok_or(CmdError::Unknown)?;
+
+        let mut args = [""; MAX_ARGS];
+        let mut count = 0;
+        for word in words {
+            *args.get_mut(count).ok_or(CmdError::TooManyArgs)? = word;
+            count += 1;
+        }
+        (command.handler)(&args[..count], ctx)
+    }
+
+    /// One line per command, in table order
+    pub fn write_help<W: Write>(&self, w: &mut W) -> fmt::Result {
+        for c in self.commands {
+            write!(w, "  {:<width$}- {}\r\n", c.usage, c.help, width = HELP_USAGE_WIDTH)?;
+        }
+        Ok(())
+    }
+
+    /// The `ERROR: ...` line for a failed `dispatch` of `line`
+    pub fn write_error<W: Write>(&self, w: &mut W, line: &str, err: CmdError) -> fmt::Result {
+        match err {
+            CmdError::Unknown => w.write_str("ERROR: Unknown command. Type 'help' for commands.\r\n"),
+            CmdError::TooManyArgs => w.write_str("ERROR: Too many arguments\r\n"),
+            CmdError::Usage => {
+                let name = line.split_whitespace().next().unwrap_or("");
+                let usage = self.find(name).map_or(name, |c| c.usage);
+                write!(w, "ERROR: Usage: {}\r\n", usage)
+            }
+            CmdError::Invalid(reason) => write!(w, "ERROR: {}\r\n", reason),
+        }
+    }
+
+    /// Print what `dispatch(line, ..)` returned: the reply or the error line
+    pub fn write_result<W: Write>(&self, w: &mut W, line: &str, result: &CmdResult) -> fmt::Result {
+        match result {
+            Ok(output) => w.write_str(output.as_str()),
+            Err(err) => self.write_error(w, line, *err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ctx {
+        led: bool,
+        last_args: usize,
+    }
+
+    fn led_on(_args: &[&str], ctx: &mut Ctx) -> CmdResult {
+        ctx.led = true;
+        Ok(CommandOutput::from("OK [LED on]\r\n"))
+    }
+
+    fn count(args: &[&str], ctx: &mut Ctx) -> CmdResult {
+        if args.is_empty() {
+            return Err(CmdError::Usage);
+        }
+        ctx.last_args = args.len();
+        let mut output = CommandOutput::new();
+        write!(output, "{} args\r\n", args.len()).ok();
+        Ok(output)
+    }
+
+    static TABLE: CommandTable<Ctx> = CommandTable::new(&[
+        Command { name: "led.on", usage: "led.on", help: "LED on", handler: led_on },
+        Command { name: "count", usage: "count <args...>", help: "Count args", handler: count },
+    ]);
+
+    struct Out<const N: usize>([u8; N], usize);
+
+    impl<const N: usize> Write for Out<N> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.1 + s.len();
+            self.0.get_mut(self.1..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+            self.1 = end;
+            Ok(())
+        }
+    }
+
+    impl<const N: usize> Out<N> {
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.0[..self.1]).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_dispatch_finds_command_and_passes_args() {
+        let mut ctx = Ctx { led: false, last_args: 0 };
+        assert_eq!(TABLE.dispatch("  led.on \r\n", &mut ctx), Ok(CommandOutput::from("OK [LED on]\r\n")));
+        assert!(ctx.led);
+        assert_eq!(TABLE.dispatch("count a  b c", &mut ctx).unwrap().as_str(), "3 args\r\n");
+        assert_eq!(ctx.last_args, 3);
+        assert_eq!(TABLE.dispatch("   ", &mut ctx), Ok(CommandOutput::new()));
+    }
+
+    #[test]
+    fn test_dispatch_errors() {
+        let mut ctx = Ctx { led: false, last_args: 0 };
+        assert_eq!(TABLE.dispatch("led.blink", &mut ctx), Err(CmdError::Unknown));
+        assert_eq!(TABLE.dispatch("count", &mut ctx), Err(CmdError::Usage));
+        assert_eq!(TABLE.dispatch("count 1 2 3 4 5 6 7 8 9", &mut ctx), Err(CmdError::TooManyArgs));
+        assert!(!ctx.led);
+    }
+
+    #[test]
+    fn test_help_and_error_text() {
+        let mut out = Out([0; 128], 0);
+        TABLE.write_help(&mut out).unwrap();
+        assert_eq!(out.as_str(), "  led.on              - LED on\r\n  count <args...>     - Count args\r\n");
+
+        let mut out = Out([0; 64], 0);
+        TABLE.write_error(&mut out, "count", CmdError::Usage).unwrap();
+        assert_eq!(out.as_str(), "ERROR: Usage: count <args...>\r\n");
+    }
+
+    #[test]
+    fn test_write_result_renders_reply_or_error() {
+        let mut ctx = Ctx { led: false, last_args: 0 };
+        let mut out = Out([0; 128], 0);
+        let result = TABLE.dispatch("count x y", &mut ctx);
+        TABLE.write_result(&mut out, "count x y", &result).unwrap();
+        let result = TABLE.dispatch("led.blink", &mut ctx);
+        TABLE.write_result(&mut out, "led.blink", &result).unwrap();
+        assert_eq!(out.as_str(), "2 args\r\nERROR: Unknown command. Type 'help' for commands.\r\n");
+    }
+
+    #[test]
+    fn test_output_drops_text_past_capacity() {
+        let mut output = CommandOutput::new();
+        for _ in 0..OUTPUT_CAPACITY / 8 {
+            output.write_str("12345678").unwrap();
+        }
+        assert!(output.write_str("x").is_err());
+        assert_eq!(output.as_str().len(), OUTPUT_CAPACITY);
+    }
+}
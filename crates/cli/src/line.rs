@@ -0,0 +1,188 @@
+//! Line editing for the UART CLIs
+//!
+//! `LineReader` takes the received bytes one at a time and hands back a
+//! whole line when Enter is pressed. It does what every lesson's main loop
+//! used to do by hand: echo, backspace, dropping control characters, arrow
+//! key history (`ESC [ A` / `ESC [ B`) and rejecting lines longer than the
+//! buffer. The caller runs the line and prints the next prompt.
+
+use crate::history::{Escape, History};
+use core::fmt::{self, Write};
+use heapless::String;
+
+/// Prompt the CLIs print before each line; redrawn on history recall and
+/// after an overlong line
+pub const PROMPT: &str = "> ";
+
+pub struct LineReader<const N: usize> {
+    buf: String<N>,
+    history: History<N>,
+    escape: Escape,
+    /// The line outgrew `buf`; the rest of it is ignored until Enter
+    too_long: bool,
+    /// `buf` holds the line returned by the last call; cleared on the next one
+    complete: bool,
+    echo: bool,
+}
+
+impl<const N: usize> LineReader<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: String::new(),
+            history: History::new(),
+            escape: Escape::Idle,
+            too_long: false,
+            complete: false,
+            echo: true,
+        }
+    }
+
+    /// Echo typed characters back (turn off for terminals with local echo)
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Read one byte from `uart` if one has arrived and handle it
+    ///
+    /// Returns the entered line when the byte was Enter, otherwise None.
+    /// Never blocks: without pending input it returns None straight away.
+    pub fn poll<U>(&mut self, uart: &mut U) -> Option<&str>
+    where
+        U: embedded_io::Read + embedded_io::ReadReady + Write,
+    {
+        if !uart.read_ready().unwrap_or(false) {
+            return None;
+        }
+        let mut byte = [0u8; 1];
+        match uart.read(&mut byte) {
+            Ok(1) => self.feed(byte[0], uart),
+            _ => None,
+        }
+    }
+
+    /// Handle one received byte, echoing to `out`; same result as `poll`
+    pub fn feed<W: Write>(&mut self, byte: u8, out: &mut W) -> Option<&str> {
+        if self.complete {
+            self.complete = false;
+            self.buf.clear();
+        }
+        let ch = byte as char;
+
+        if self.escape == Escape::Esc {
+            self.escape = if ch == '[' { Escape::Csi } else { Escape::Idle };
+        } else if self.escape == Escape::Csi {
+            self.escape = Escape::Idle;
+            let entry = match ch {
+                'A' => self.history.older(),
+                'B' => self.history.newer(),
+                _ => None,
+            };
+            if let Some(entry) = entry {
+                self.buf.clear();
+                let _ = self.buf.push_str(entry);
+                let _ = out.write_str("\r\x1b[K");
+                let _ = out.write_str(PROMPT);
+                let _ = out.write_str(&self.buf);
+            }
+        } else if ch == '\x1b' {
+            self.escape = Escape::Esc;
+        } else if ch == '\r' || ch == '\n' {
+            self.too_long = false;
+            if !self.buf.is_empty() {
+                let _ = out.write_str("\r\n");
+                self.history.push(&self.buf);
+                self.complete = true;
+                return Some(&self.buf);
+            }
+        } else if ch == '\x08' || ch == '\x7f' {
+            if self.buf.pop().is_some() && self.echo {
+                let _ = out.write_str("\x08 \x08");
+            }
+        } else if self.too_long {
+            // Rest of an overlong line, already reported
+        } else if ch.is_ascii_graphic() || ch == ' ' {
+            if self.buf.push(ch).is_err() {
+                self.too_long = true;
+                self.buf.clear();
+                let _ = report_too_long(N, out);
+            } else if self.echo {
+                let _ = out.write_char(ch);
+            }
+        }
+        None
+    }
+
+    /// The line being edited (or the one just returned)
+    pub fn as_str(&self) -> &str {
+        &self.buf
+    }
+}
+
+impl<const N: usize> Default for LineReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ring the bell, say why the line was dropped and redraw the prompt
+fn report_too_long<W: Write>(max: usize, out: &mut W) -> fmt::Result {
+    write!(out, "\x07\r\nERROR: command too long (max {} characters)\r\n{}", max, PROMPT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echo sink
+    struct Out(String<256>);
+
+    impl Write for Out {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0.push_str(s).map_err(|_| fmt::Error)
+        }
+    }
+
+    fn feed_all<const N: usize>(reader: &mut LineReader<N>, bytes: &[u8], out: &mut Out) -> Option<String<N>> {
+        let mut line = None;
+        for &b in bytes {
+            if let Some(l) = reader.feed(b, out) {
+                line = Some(String::try_from(l).unwrap());
+            }
+        }
+        line
+    }
+
+    #[test]
+    fn test_line_with_backspace_and_echo() {
+        let mut reader: LineReader<16> = LineReader::new();
+        let mut out = Out(String::new());
+        assert_eq!(feed_all(&mut reader, b"pinx\x7fg\x01", &mut out), None);
+        assert_eq!(reader.as_str(), "ping");
+        assert_eq!(feed_all(&mut reader, b"\r", &mut out).as_deref(), Some("ping"));
+        assert_eq!(out.0.as_str(), "pinx\x08 \x08g\r\n");
+        // Blank Enter: no line, and the next byte starts a fresh one
+        assert_eq!(feed_all(&mut reader, b"\r\ne", &mut out), None);
+        assert_eq!(reader.as_str(), "e");
+    }
+
+    #[test]
+    fn test_history_recall() {
+        let mut reader: LineReader<16> = LineReader::new();
+        let mut out = Out(String::new());
+        feed_all(&mut reader, b"imu.read\r", &mut out);
+        out.0.clear();
+        assert_eq!(feed_all(&mut reader, b"\x1b[A", &mut out), None);
+        assert_eq!(reader.as_str(), "imu.read");
+        assert_eq!(out.0.as_str(), "\r\x1b[K> imu.read");
+        assert_eq!(feed_all(&mut reader, b"\r", &mut out).as_deref(), Some("imu.read"));
+    }
+
+    #[test]
+    fn test_overlong_line_dropped_until_enter() {
+        let mut reader: LineReader<4> = LineReader::new();
+        let mut out = Out(String::new());
+        assert_eq!(feed_all(&mut reader, b"abcdef\r", &mut out), None);
+        assert!(out.0.ends_with("ERROR: command too long (max 4 characters)\r\n> "));
+        assert_eq!(feed_all(&mut reader, b"ok\r", &mut out).as_deref(), Some("ok"));
+    }
+}
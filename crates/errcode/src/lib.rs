@@ -0,0 +1,97 @@
+//! Fatal error codes blinked on the plain LED
+//!
+//! The LED blinks once per count, pauses, and repeats, so a board without a
+//! UART attached still shows what went wrong. Lessons 04 and 05 use the same
+//! codes; the UART/log message says the same thing in words.
+
+#![no_std]
+
+use mpu6050::Mpu6050Error;
+
+pub const BLINK_ON_MS: u32 = 200;
+pub const BLINK_OFF_MS: u32 = 300;
+/// Dark gap between repeats, long enough to tell where a count starts
+pub const BLINK_PAUSE_MS: u32 = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// IMU didn't acknowledge, or reported an unknown WHO_AM_I
+    Sensor = 2,
+    /// I2C bus fault other than a missing ACK (timeout, lost arbitration:
+    /// usually SDA or SCL held low)
+    Bus = 3,
+    /// Flash read or write failed
+    Flash = 4,
+}
+
+impl ErrorCode {
+    pub fn blinks(self) -> u32 {
+        self as u32
+    }
+
+    /// Message for the UART/log report
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::Sensor => "IMU not found",
+            ErrorCode::Bus => "I2C bus error (SDA/SCL stuck?)",
+            ErrorCode::Flash => "Flash read/write failed",
+        }
+    }
+
+    /// Length of one cycle: the blinks plus the pause
+    pub fn period_ms(self) -> u32 {
+        self.blinks() * (BLINK_ON_MS + BLINK_OFF_MS) + BLINK_PAUSE_MS
+    }
+
+    /// Whether the LED is lit `t_ms` into the pattern (repeats every `period_ms`)
+    pub fn led_on(self, t_ms: u32) -> bool {
+        let t = t_ms % self.period_ms();
+        t < self.blinks() * (BLINK_ON_MS + BLINK_OFF_MS) && t % (BLINK_ON_MS + BLINK_OFF_MS) < BLINK_ON_MS
+    }
+}
+
+/// Sensor when nothing (or the wrong chip) answers, Bus for any other I2C fault
+impl<E: embedded_hal::i2c::Error> From<Mpu6050Error<E>> for ErrorCode {
+    fn from(err: Mpu6050Error<E>) -> Self {
+        if err.is_missing_device() {
+            ErrorCode::Sensor
+        } else {
+            ErrorCode::Bus
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Off→on transitions in one period, sampled every 10 ms like the main loop
+    fn count_blinks(code: ErrorCode) -> u32 {
+        let mut prev = false;
+        let mut count = 0;
+        for t in (0..code.period_ms()).step_by(10) {
+            let on = code.led_on(t);
+            if on && !prev {
+                count += 1;
+            }
+            prev = on;
+        }
+        count
+    }
+
+    #[test]
+    fn test_blink_counts() {
+        for code in [ErrorCode::Sensor, ErrorCode::Bus, ErrorCode::Flash] {
+            assert_eq!(count_blinks(code), code.blinks(), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn test_pattern_repeats_after_pause() {
+        let code = ErrorCode::Bus;
+        assert!(code.led_on(0));
+        assert!(!code.led_on(code.period_ms() - 1));
+        assert!(code.led_on(code.period_ms()));
+    }
+}
@@ -0,0 +1,134 @@
+//! `Mpu6050` wrapper: owns the bus and remembers the address and range
+//!
+//! The lessons keep calling the free functions with `&mut i2c`; this type is
+//! for code that wants one value to pass around. With the `accelerometer`
+//! feature it implements the `accelerometer` crate's traits, so generic
+//! tilt/orientation code can use the sensor directly.
+
+use crate::{accel_sensitivity, read_accel, set_accel_range, Accel, AccelRange, Mpu6050Error};
+use embedded_hal::i2c::I2c;
+
+pub struct Mpu6050<I> {
+    i2c: I,
+    addr: u8,
+    range: AccelRange,
+}
+
+impl<I: I2c> Mpu6050<I> {
+    /// Wrap a sensor at `addr`, assumed at its power-on ±2 g range
+    pub fn new(i2c: I, addr: u8) -> Self {
+        Self {
+            i2c,
+            addr,
+            range: AccelRange::G2,
+        }
+    }
+
+    pub fn addr(&self) -> u8 {
+        self.addr
+    }
+
+    pub fn accel_range(&self) -> AccelRange {
+        self.range
+    }
+
+    /// Program the range; `accel_norm` scales by it from then on
+    pub fn set_accel_range(&mut self, range: AccelRange) -> Result<(), Mpu6050Error<I::Error>> {
+        set_accel_range(&mut self.i2c, self.addr, range)?;
+        self.range = range;
+        Ok(())
+    }
+
+    pub fn read_accel(&mut self) -> Result<Accel, Mpu6050Error<I::Error>> {
+        read_accel(&mut self.i2c, self.addr)
+    }
+
+    /// Accelerometer in g at the configured range
+    pub fn read_accel_g(&mut self) -> Result<[f32; 3], Mpu6050Error<I::Error>> {
+        let a = self.read_accel()?;
+        let scale = accel_sensitivity(self.range);
+        Ok([a.x as f32 / scale, a.y as f32 / scale, a.z as f32 / scale])
+    }
+
+    /// Give the bus back, e.g. to go back to the free functions
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    pub fn i2c(&mut self) -> &mut I {
+        &mut self.i2c
+    }
+}
+
+#[cfg(feature = "accelerometer")]
+mod traits {
+    use super::Mpu6050;
+    use crate::{Mpu6050Error, CONFIG, SMPLRT_DIV};
+    use accelerometer::vector::{F32x3, I16x3};
+    use accelerometer::{Accelerometer, Error, ErrorKind, RawAccelerometer};
+    use embedded_hal::i2c::I2c;
+
+    fn to_error<E: core::fmt::Debug>(err: Mpu6050Error<E>) -> Error<E> {
+        match err {
+            Mpu6050Error::I2c(cause) => Error::new_with_cause(ErrorKind::Bus, cause),
+            Mpu6050Error::UnknownDevice(_) => Error::new(ErrorKind::Device),
+        }
+    }
+
+    impl<I: I2c> RawAccelerometer<I16x3> for Mpu6050<I> {
+        type Error = I::Error;
+
+        fn accel_raw(&mut self) -> Result<I16x3, Error<I::Error>> {
+            let a = self.read_accel().map_err(to_error)?;
+            Ok(I16x3::new(a.x, a.y, a.z))
+        }
+    }
+
+    impl<I: I2c> Accelerometer for Mpu6050<I> {
+        type Error = I::Error;
+
+        fn accel_norm(&mut self) -> Result<F32x3, Error<I::Error>> {
+            let [x, y, z] = self.read_accel_g().map_err(to_error)?;
+            Ok(F32x3::new(x, y, z))
+        }
+
+        /// Output rate from SMPLRT_DIV: the gyro clock (8 kHz with the
+        /// DLPF off, 1 kHz with it on) divided by `1 + SMPLRT_DIV`
+        fn sample_rate(&mut self) -> Result<f32, Error<I::Error>> {
+            let mut div = [0u8; 1];
+            let mut config = [0u8; 1];
+            let addr = self.addr();
+            let bus = |e| to_error(Mpu6050Error::I2c(e));
+            self.i2c().write_read(addr, &[SMPLRT_DIV], &mut div).map_err(bus)?;
+            self.i2c().write_read(addr, &[CONFIG], &mut config).map_err(bus)?;
+            let dlpf_off = matches!(config[0] & 0x07, 0 | 7);
+            let gyro_rate = if dlpf_off { 8000.0 } else { 1000.0 };
+            Ok(gyro_rate / (1.0 + div[0] as f32))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        extern crate std;
+
+        use super::*;
+        use crate::{AccelRange, ACCEL_CONFIG, ACCEL_XOUT_H, MPU_ADDR};
+        use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+        use std::vec;
+
+        #[test]
+        fn test_accel_traits_scale_by_range() {
+            let mut mock = Mock::new(&[
+                Transaction::write_read(MPU_ADDR, vec![ACCEL_XOUT_H], vec![0x40, 0x00, 0xC0, 0x00, 0x00, 0x00]),
+                Transaction::write(MPU_ADDR, vec![ACCEL_CONFIG, 0x08]),
+                Transaction::write_read(MPU_ADDR, vec![ACCEL_XOUT_H], vec![0x40, 0x00, 0xC0, 0x00, 0x00, 0x00]),
+            ]);
+            let mut imu = Mpu6050::new(mock.clone(), MPU_ADDR);
+
+            assert_eq!(imu.accel_raw().unwrap(), I16x3::new(16384, -16384, 0));
+            imu.set_accel_range(AccelRange::G4).unwrap();
+            assert_eq!(imu.accel_norm().unwrap(), F32x3::new(2.0, -2.0, 0.0));
+            mock.done();
+        }
+    }
+}
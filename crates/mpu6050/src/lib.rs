@@ -0,0 +1,540 @@
+//! MPU6050/MPU9250 sensor helper functions
+//!
+//! Compatible with both MPU6050 and MPU9250 (same I2C interface for accel/gyro).
+//! Generic over `embedded_hal::i2c::I2c`, so the lessons pass esp-hal's `I2c`
+//! and the tests a mock bus.
+
+#![no_std]
+
+use embedded_hal::i2c::{Error, ErrorKind, I2c};
+
+mod device;
+
+pub use device::Mpu6050;
+
+/// Default address (AD0 low); `MPU_ADDR_ALT` with AD0 strapped high.
+/// Every helper takes the address so two sensors can share the bus.
+pub const MPU_ADDR: u8 = 0x68;
+pub const MPU_ADDR_ALT: u8 = 0x69;
+pub const WHO_AM_I_REG: u8 = 0x75;
+pub const PWR_MGMT_1: u8 = 0x6B;
+pub const SMPLRT_DIV: u8 = 0x19;
+pub const CONFIG: u8 = 0x1A;
+pub const GYRO_CONFIG: u8 = 0x1B;
+pub const ACCEL_CONFIG: u8 = 0x1C;
+pub const ACCEL_XOUT_H: u8 = 0x3B;
+pub const TEMP_OUT_H: u8 = 0x41;
+pub const GYRO_XOUT_H: u8 = 0x43;
+pub const INT_PIN_CFG: u8 = 0x37;
+pub const INT_ENABLE: u8 = 0x38;
+pub const INT_STATUS: u8 = 0x3A;
+pub const FIFO_EN: u8 = 0x23;
+pub const USER_CTRL: u8 = 0x6A;
+/// FIFO_COUNT_H; FIFO_COUNT_L follows
+pub const FIFO_COUNT_H: u8 = 0x72;
+pub const FIFO_R_W: u8 = 0x74;
+/// XA_OFFS_H; YA_OFFS and ZA_OFFS follow (0x06..=0x0B, MPU6050 only)
+pub const XA_OFFS_H: u8 = 0x06;
+
+/// Accel offset register LSBs per g (registers are ±16 g scale at any range)
+pub const ACCEL_OFFS_LSB_PER_G: f32 = 2048.0;
+
+/// DATA_RDY_EN / DATA_RDY_INT bit in INT_ENABLE / INT_STATUS
+pub const DATA_RDY_BIT: u8 = 0x01;
+
+/// INT_PIN_CFG bits: INT_LEVEL (active low), LATCH_INT_EN, INT_RD_CLEAR
+pub const INT_LEVEL_ACTIVE_LOW: u8 = 0x80;
+pub const INT_LATCH: u8 = 0x20;
+pub const INT_RD_CLEAR: u8 = 0x10;
+
+/// FIFO_OFLOW_EN / FIFO_OFLOW_INT bit in INT_ENABLE / INT_STATUS
+pub const FIFO_OFLOW_BIT: u8 = 0x10;
+
+/// FIFO_EN bits for XG, YG, ZG and ACCEL: accel and gyro, no temperature
+pub const FIFO_EN_ACCEL_GYRO: u8 = 0x78;
+
+/// USER_CTRL bits: FIFO_EN (bit 6) and FIFO_RESET (bit 2, self-clearing)
+pub const USER_CTRL_FIFO_EN: u8 = 0x40;
+pub const USER_CTRL_FIFO_RESET: u8 = 0x04;
+
+/// FIFO size in bytes
+pub const FIFO_DEPTH: usize = 1024;
+
+/// Bytes per sample in the FIFO with `FIFO_EN_ACCEL_GYRO`: accel X, Y, Z
+/// then gyro X, Y, Z, big-endian, in register order
+pub const FIFO_FRAME_LEN: usize = 12;
+
+/// WHO_AM_I values accepted by `probe`: MPU6050, MPU6500, MPU9250, MPU9255
+pub const KNOWN_IDS: [u8; 4] = [0x68, 0x70, 0x71, 0x73];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mpu6050Error<E> {
+    /// The I2C transaction failed
+    I2c(E),
+    /// WHO_AM_I read fine but isn't one of `KNOWN_IDS`
+    UnknownDevice(u8),
+}
+
+impl<E> From<E> for Mpu6050Error<E> {
+    fn from(err: E) -> Self {
+        Mpu6050Error::I2c(err)
+    }
+}
+
+impl<E: Error> Mpu6050Error<E> {
+    /// No sensor at the address (no ACK, or something else answered),
+    /// as opposed to a bus fault such as a timeout or lost arbitration
+    pub fn is_missing_device(&self) -> bool {
+        match self {
+            Mpu6050Error::UnknownDevice(_) => true,
+            Mpu6050Error::I2c(err) => matches!(err.kind(), ErrorKind::NoAcknowledge(_)),
+        }
+    }
+}
+
+/// Level of the INT pin while an interrupt is pending (INT_PIN_CFG.INT_LEVEL)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntPolarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Accelerometer full-scale range (ACCEL_CONFIG.AFS_SEL)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum AccelRange {
+    G2 = 0,
+    G4 = 1,
+    G8 = 2,
+    G16 = 3,
+}
+
+/// On-chip digital low-pass filter (CONFIG.DLPF_CFG), named by accel
+/// bandwidth; the gyro bandwidth is about the same (256, 188, 98, 42, 20,
+/// 10, 5 Hz). Lower bandwidth means less noise but more delay (0 ms at
+/// 260 Hz, ~5 ms at 44 Hz, ~19 ms at 5 Hz), and any setting but `Hz260`
+/// drops the gyro output rate from 8 kHz to 1 kHz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum DlpfConfig {
+    Hz260 = 0,
+    Hz184 = 1,
+    Hz94 = 2,
+    Hz44 = 3,
+    Hz21 = 4,
+    Hz10 = 5,
+    Hz5 = 6,
+}
+
+impl DlpfConfig {
+    /// Applied by `wake_sensor`: cuts most vibration noise, still responsive
+    pub const DEFAULT: DlpfConfig = DlpfConfig::Hz44;
+
+    /// From the DLPF_CFG register value 0-6
+    pub fn from_index(index: u8) -> Option<Self> {
+        Some(match index {
+            0 => DlpfConfig::Hz260,
+            1 => DlpfConfig::Hz184,
+            2 => DlpfConfig::Hz94,
+            3 => DlpfConfig::Hz44,
+            4 => DlpfConfig::Hz21,
+            5 => DlpfConfig::Hz10,
+            6 => DlpfConfig::Hz5,
+            _ => return None,
+        })
+    }
+
+    /// Accelerometer bandwidth in Hz
+    pub fn bandwidth_hz(self) -> u16 {
+        [260, 184, 94, 44, 21, 10, 5][self as usize]
+    }
+}
+
+/// Accelerometer counts per g at `range` (16384 at the power-on ±2 g)
+pub fn accel_sensitivity(range: AccelRange) -> f32 {
+    16384.0 / (1u32 << range as u32) as f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accel {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gyro {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+/// Read one register
+pub fn read_reg<I: I2c>(i2c: &mut I, addr: u8, reg: u8) -> Result<u8, Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 1];
+    read_regs(i2c, addr, reg, &mut buf)?;
+    Ok(buf[0])
+}
+
+/// Read `buf.len()` consecutive registers starting at `reg`
+///
+/// The register address auto-increments during a burst read, so one
+/// transaction fetches the whole block (FIFO_R_W is the exception: it stays
+/// put and returns successive FIFO bytes).
+pub fn read_regs<I: I2c>(i2c: &mut I, addr: u8, reg: u8, buf: &mut [u8]) -> Result<(), Mpu6050Error<I::Error>> {
+    i2c.write_read(addr, &[reg], buf)?;
+    Ok(())
+}
+
+/// Write one register
+///
+/// For settings without a dedicated helper; the helpers below know which
+/// bits of a register they own, this writes all eight.
+pub fn write_reg<I: I2c>(i2c: &mut I, addr: u8, reg: u8, value: u8) -> Result<(), Mpu6050Error<I::Error>> {
+    i2c.write(addr, &[reg, value])?;
+    Ok(())
+}
+
+/// Three big-endian i16 words starting at `buf[0]`
+fn xyz(buf: &[u8]) -> [i16; 3] {
+    [
+        i16::from_be_bytes([buf[0], buf[1]]),
+        i16::from_be_bytes([buf[2], buf[3]]),
+        i16::from_be_bytes([buf[4], buf[5]]),
+    ]
+}
+
+/// Wake up the MPU6050/MPU9250 from sleep mode, with the DLPF at
+/// `DlpfConfig::DEFAULT`
+pub fn wake_sensor<I: I2c>(i2c: &mut I, addr: u8) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, PWR_MGMT_1, 0x00)?;
+    set_dlpf(i2c, addr, DlpfConfig::DEFAULT)
+}
+
+/// Set the digital low-pass filter
+///
+/// DLPF_CFG is bits 2:0 of CONFIG; EXT_SYNC_SET (bits 5:3) is left at 0, disabled.
+pub fn set_dlpf<I: I2c>(i2c: &mut I, addr: u8, cfg: DlpfConfig) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, CONFIG, cfg as u8)
+}
+
+/// Read WHO_AM_I register
+pub fn read_who_am_i<I: I2c>(i2c: &mut I, addr: u8) -> Result<u8, Mpu6050Error<I::Error>> {
+    read_reg(i2c, addr, WHO_AM_I_REG)
+}
+
+/// Check that an IMU answers at `addr`; returns its WHO_AM_I
+///
+/// See `Mpu6050Error::is_missing_device` to tell a missing sensor apart from
+/// a bus fault.
+pub fn probe<I: I2c>(i2c: &mut I, addr: u8) -> Result<u8, Mpu6050Error<I::Error>> {
+    let id = read_who_am_i(i2c, addr)?;
+    if KNOWN_IDS.contains(&id) {
+        Ok(id)
+    } else {
+        Err(Mpu6050Error::UnknownDevice(id))
+    }
+}
+
+/// Set the accelerometer full-scale range
+///
+/// AFS_SEL is bits 4:3 of ACCEL_CONFIG; the self-test bits are left cleared.
+/// Raw readings scale with the range, so offsets taken at another range are stale.
+pub fn set_accel_range<I: I2c>(i2c: &mut I, addr: u8, range: AccelRange) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, ACCEL_CONFIG, (range as u8) << 3)
+}
+
+/// Read accelerometer data
+pub fn read_accel<I: I2c>(i2c: &mut I, addr: u8) -> Result<Accel, Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 6];
+    read_regs(i2c, addr, ACCEL_XOUT_H, &mut buf)?;
+    let [x, y, z] = xyz(&buf);
+    Ok(Accel { x, y, z })
+}
+
+/// Read gyroscope data
+pub fn read_gyro<I: I2c>(i2c: &mut I, addr: u8) -> Result<Gyro, Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 6];
+    read_regs(i2c, addr, GYRO_XOUT_H, &mut buf)?;
+    let [x, y, z] = xyz(&buf);
+    Ok(Gyro { x, y, z })
+}
+
+/// Read accelerometer and gyroscope in one burst
+///
+/// ACCEL_XOUT_H..GYRO_ZOUT_L are contiguous (temperature sits in between),
+/// so this is a single 14-byte transaction instead of two.
+pub fn read_motion<I: I2c>(i2c: &mut I, addr: u8) -> Result<(Accel, Gyro), Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 14];
+    read_regs(i2c, addr, ACCEL_XOUT_H, &mut buf)?;
+    let [ax, ay, az] = xyz(&buf[0..6]);
+    let [gx, gy, gz] = xyz(&buf[8..14]);
+    Ok((Accel { x: ax, y: ay, z: az }, Gyro { x: gx, y: gy, z: gz }))
+}
+
+/// Read die temperature in °C
+///
+/// TEMP_OUT is signed: below 36.53 °C the raw value is negative. Uses the
+/// MPU6050 conversion (raw / 340 + 36.53); an MPU9250 reads a few degrees off.
+pub fn read_temp<I: I2c>(i2c: &mut I, addr: u8) -> Result<f32, Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 2];
+    read_regs(i2c, addr, TEMP_OUT_H, &mut buf)?;
+    Ok(i16::from_be_bytes(buf) as f32 / 340.0 + 36.53)
+}
+
+/// Read the accelerometer offset registers (X, Y, Z)
+///
+/// At power-up these hold the factory trim, not zero; keep the values so the
+/// trim can be restored and calibration added on top of it.
+pub fn read_hardware_offsets<I: I2c>(i2c: &mut I, addr: u8) -> Result<[i16; 3], Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 6];
+    read_regs(i2c, addr, XA_OFFS_H, &mut buf)?;
+    Ok(xyz(&buf))
+}
+
+/// Program the accelerometer offset registers so reads come pre-corrected
+///
+/// Register quirks (MPU6050; the MPU9250 keeps these at 0x77..0x7E instead):
+/// - one LSB is ~0.98 mg regardless of the configured range
+///   (`ACCEL_OFFS_LSB_PER_G`), i.e. 8 raw counts at ±2 g
+/// - the value is added to the factory trim already in the register, so
+///   write `trim - bias / 8`, not `-bias / 8`
+/// - bit 0 of each low byte is reserved (temperature compensation); it is
+///   read back and preserved, so the effective resolution is 2 LSB
+pub fn write_hardware_offsets<I: I2c>(i2c: &mut I, addr: u8, offsets: [i16; 3]) -> Result<(), Mpu6050Error<I::Error>> {
+    let current = read_hardware_offsets(i2c, addr)?;
+    let mut buf = [XA_OFFS_H, 0, 0, 0, 0, 0, 0];
+    for (i, (&offset, &reg)) in offsets.iter().zip(current.iter()).enumerate() {
+        let value = (offset & !1) | (reg & 1);
+        buf[1 + 2 * i..3 + 2 * i].copy_from_slice(&value.to_be_bytes());
+    }
+    i2c.write(addr, &buf)?;
+    Ok(())
+}
+
+/// Enable the data-ready interrupt source so INT_STATUS reports fresh samples
+pub fn enable_data_ready<I: I2c>(i2c: &mut I, addr: u8) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, INT_ENABLE, DATA_RDY_BIT)
+}
+
+/// Drive the INT pin (push-pull) with `polarity` while an interrupt is pending
+///
+/// The pin is latched until the next register read of any kind, e.g. the
+/// `read_accel` that fetches the new sample, instead of the default 50 µs
+/// pulse, so a GPIO that is only polled can't miss it. With
+/// `enable_data_ready` the pin then means "a sample is waiting".
+pub fn configure_int_pin<I: I2c>(i2c: &mut I, addr: u8, polarity: IntPolarity) -> Result<(), Mpu6050Error<I::Error>> {
+    let level = match polarity {
+        IntPolarity::ActiveHigh => 0,
+        IntPolarity::ActiveLow => INT_LEVEL_ACTIVE_LOW,
+    };
+    write_reg(i2c, addr, INT_PIN_CFG, level | INT_LATCH | INT_RD_CLEAR)
+}
+
+/// Check whether a new accel/gyro sample is available
+///
+/// Reading INT_STATUS clears the flag, so each sample is reported once.
+pub fn data_ready<I: I2c>(i2c: &mut I, addr: u8) -> Result<bool, Mpu6050Error<I::Error>> {
+    Ok(read_reg(i2c, addr, INT_STATUS)? & DATA_RDY_BIT != 0)
+}
+
+/// Start capturing every accel+gyro sample into the FIFO
+///
+/// Empties the FIFO first, so the first byte read is the start of a frame
+/// (`FIFO_FRAME_LEN` bytes each). Samples go in at the sample rate
+/// (1 kHz / (1 + SMPLRT_DIV) with the DLPF on) whether or not anyone reads
+/// them. Also enables the overflow interrupt source next to data-ready, so
+/// `fifo_overflowed` can report it.
+pub fn enable_fifo<I: I2c>(i2c: &mut I, addr: u8) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, USER_CTRL, USER_CTRL_FIFO_RESET)?;
+    write_reg(i2c, addr, FIFO_EN, FIFO_EN_ACCEL_GYRO)?;
+    write_reg(i2c, addr, INT_ENABLE, DATA_RDY_BIT | FIFO_OFLOW_BIT)?;
+    write_reg(i2c, addr, USER_CTRL, USER_CTRL_FIFO_EN)
+}
+
+/// Stop filling the FIFO and empty it
+pub fn disable_fifo<I: I2c>(i2c: &mut I, addr: u8) -> Result<(), Mpu6050Error<I::Error>> {
+    write_reg(i2c, addr, USER_CTRL, USER_CTRL_FIFO_RESET)?;
+    write_reg(i2c, addr, FIFO_EN, 0)?;
+    write_reg(i2c, addr, INT_ENABLE, DATA_RDY_BIT)
+}
+
+/// Bytes waiting in the FIFO (0..=`FIFO_DEPTH`)
+pub fn fifo_count<I: I2c>(i2c: &mut I, addr: u8) -> Result<u16, Mpu6050Error<I::Error>> {
+    let mut buf = [0u8; 2];
+    read_regs(i2c, addr, FIFO_COUNT_H, &mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+/// Drain whole frames from the FIFO into `buf`; returns the bytes read
+///
+/// Reads as many complete `FIFO_FRAME_LEN` frames as are waiting and fit in
+/// `buf`, leaving a partly written frame for the next call. Reading FIFO_R_W
+/// repeatedly returns the next byte each time, so this is one burst.
+///
+/// When the FIFO is full (`FIFO_DEPTH` bytes, 85 frames plus 4 bytes) new
+/// samples overwrite the oldest and FIFO_OFLOW_INT is set. 1024 isn't a
+/// multiple of the frame size, so after an overflow the bytes no longer
+/// start on a frame boundary: check `fifo_overflowed` and call `enable_fifo`
+/// again to restart from an empty FIFO.
+pub fn read_fifo<I: I2c>(i2c: &mut I, addr: u8, buf: &mut [u8]) -> Result<usize, Mpu6050Error<I::Error>> {
+    let count = (fifo_count(i2c, addr)? as usize).min(buf.len());
+    let len = count - count % FIFO_FRAME_LEN;
+    if len > 0 {
+        read_regs(i2c, addr, FIFO_R_W, &mut buf[..len])?;
+    }
+    Ok(len)
+}
+
+/// Decode one `FIFO_FRAME_LEN`-byte frame from `read_fifo`
+pub fn fifo_sample(frame: &[u8]) -> (Accel, Gyro) {
+    let [ax, ay, az] = xyz(&frame[0..6]);
+    let [gx, gy, gz] = xyz(&frame[6..12]);
+    (Accel { x: ax, y: ay, z: az }, Gyro { x: gx, y: gy, z: gz })
+}
+
+/// Check (and clear) the FIFO overflow flag
+///
+/// Like `data_ready`, this reads INT_STATUS, which clears every flag in it:
+/// a data-ready flag pending at the same time is lost.
+pub fn fifo_overflowed<I: I2c>(i2c: &mut I, addr: u8) -> Result<bool, Mpu6050Error<I::Error>> {
+    Ok(read_reg(i2c, addr, INT_STATUS)? & FIFO_OFLOW_BIT != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+    use std::vec;
+
+    #[test]
+    fn test_read_motion_skips_temperature() {
+        let mut data = vec![0x40, 0x00, 0xFF, 0xFE, 0x00, 0x10];
+        data.extend_from_slice(&[0xAA, 0xAA]); // TEMP_OUT, ignored
+        data.extend_from_slice(&[0x00, 0x83, 0x80, 0x00, 0x7F, 0xFF]);
+        let mut i2c = Mock::new(&[Transaction::write_read(MPU_ADDR, vec![ACCEL_XOUT_H], data)]);
+
+        let (accel, gyro) = read_motion(&mut i2c, MPU_ADDR).unwrap();
+        assert_eq!(accel, Accel { x: 16384, y: -2, z: 16 });
+        assert_eq!(gyro, Gyro { x: 131, y: i16::MIN, z: i16::MAX });
+        i2c.done();
+    }
+
+    #[test]
+    fn test_raw_register_access() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(MPU_ADDR, vec![SMPLRT_DIV], vec![0x07, 0x03, 0x00, 0x18]),
+            Transaction::write_read(MPU_ADDR, vec![PWR_MGMT_1], vec![0x40]),
+            Transaction::write(MPU_ADDR, vec![GYRO_CONFIG, 0x08]),
+        ]);
+
+        let mut block = [0u8; 4];
+        read_regs(&mut i2c, MPU_ADDR, SMPLRT_DIV, &mut block).unwrap();
+        assert_eq!(block, [0x07, 0x03, 0x00, 0x18]);
+        assert_eq!(read_reg(&mut i2c, MPU_ADDR, PWR_MGMT_1), Ok(0x40));
+        write_reg(&mut i2c, MPU_ADDR, GYRO_CONFIG, 0x08).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_configure_int_pin_latches_with_polarity() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(MPU_ADDR, vec![INT_PIN_CFG, 0x30]),
+            Transaction::write(MPU_ADDR, vec![INT_PIN_CFG, 0xB0]),
+        ]);
+        configure_int_pin(&mut i2c, MPU_ADDR, IntPolarity::ActiveHigh).unwrap();
+        configure_int_pin(&mut i2c, MPU_ADDR, IntPolarity::ActiveLow).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_enable_fifo_resets_then_enables() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(MPU_ADDR, vec![USER_CTRL, 0x04]),
+            Transaction::write(MPU_ADDR, vec![FIFO_EN, 0x78]),
+            Transaction::write(MPU_ADDR, vec![INT_ENABLE, 0x11]),
+            Transaction::write(MPU_ADDR, vec![USER_CTRL, 0x40]),
+        ]);
+        enable_fifo(&mut i2c, MPU_ADDR).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_read_fifo_drains_whole_frames() {
+        let mut frames = vec![0x40, 0x00, 0x00, 0x01, 0xFF, 0xFF, 0x00, 0x83, 0x00, 0x00, 0x80, 0x00];
+        frames.extend_from_slice(&[0; 12]);
+        let mut i2c = Mock::new(&[
+            // 29 bytes waiting: two frames and a partial one
+            Transaction::write_read(MPU_ADDR, vec![FIFO_COUNT_H], vec![0x00, 29]),
+            Transaction::write_read(MPU_ADDR, vec![FIFO_R_W], frames),
+            // Buffer smaller than one frame: nothing read
+            Transaction::write_read(MPU_ADDR, vec![FIFO_COUNT_H], vec![0x04, 0x00]),
+        ]);
+
+        let mut buf = [0u8; 64];
+        assert_eq!(read_fifo(&mut i2c, MPU_ADDR, &mut buf), Ok(24));
+        let (accel, gyro) = fifo_sample(&buf[..FIFO_FRAME_LEN]);
+        assert_eq!(accel, Accel { x: 16384, y: 1, z: -1 });
+        assert_eq!(gyro, Gyro { x: 131, y: 0, z: i16::MIN });
+        assert_eq!(read_fifo(&mut i2c, MPU_ADDR, &mut buf[..8]), Ok(0));
+        i2c.done();
+    }
+
+    #[test]
+    fn test_probe_classifies_failures() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(MPU_ADDR, vec![WHO_AM_I_REG], vec![0x68]),
+            Transaction::write_read(MPU_ADDR, vec![WHO_AM_I_REG], vec![0x12]),
+            Transaction::write_read(MPU_ADDR_ALT, vec![WHO_AM_I_REG], vec![0])
+                .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+            Transaction::write_read(MPU_ADDR, vec![WHO_AM_I_REG], vec![0]).with_error(ErrorKind::ArbitrationLoss),
+        ]);
+
+        assert_eq!(probe(&mut i2c, MPU_ADDR), Ok(0x68));
+        let wrong_id = probe(&mut i2c, MPU_ADDR).unwrap_err();
+        assert_eq!(wrong_id, Mpu6050Error::UnknownDevice(0x12));
+        assert!(wrong_id.is_missing_device());
+        assert!(probe(&mut i2c, MPU_ADDR_ALT).unwrap_err().is_missing_device());
+        assert!(!probe(&mut i2c, MPU_ADDR).unwrap_err().is_missing_device());
+        i2c.done();
+    }
+
+    #[test]
+    fn test_write_hardware_offsets_keeps_reserved_bit() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(MPU_ADDR, vec![XA_OFFS_H], vec![0x00, 0x01, 0x00, 0x00, 0xFF, 0xFF]),
+            Transaction::write(MPU_ADDR, vec![XA_OFFS_H, 0x01, 0x01, 0x01, 0x02, 0xFF, 0xFF]),
+        ]);
+
+        write_hardware_offsets(&mut i2c, MPU_ADDR, [0x0100, 0x0103, -1]).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_accel_range() {
+        let mut i2c = Mock::new(&[Transaction::write(MPU_ADDR, vec![ACCEL_CONFIG, 0x18])]);
+        set_accel_range(&mut i2c, MPU_ADDR, AccelRange::G16).unwrap();
+        i2c.done();
+
+        assert_eq!(accel_sensitivity(AccelRange::G2), 16384.0);
+        assert_eq!(accel_sensitivity(AccelRange::G16), 2048.0);
+    }
+
+    #[test]
+    fn test_wake_sets_default_dlpf() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(MPU_ADDR, vec![PWR_MGMT_1, 0x00]),
+            Transaction::write(MPU_ADDR, vec![CONFIG, 0x03]),
+            Transaction::write(MPU_ADDR, vec![CONFIG, 0x06]),
+        ]);
+        wake_sensor(&mut i2c, MPU_ADDR).unwrap();
+        set_dlpf(&mut i2c, MPU_ADDR, DlpfConfig::from_index(6).unwrap()).unwrap();
+        i2c.done();
+
+        assert_eq!(DlpfConfig::from_index(7), None);
+        assert_eq!(DlpfConfig::Hz44.bandwidth_hz(), 44);
+    }
+}
@@ -0,0 +1,70 @@
+//! Buzzer beep patterns
+//!
+//! A pattern is `count` beeps of `on_ms`, `gap_ms` apart, repeated every
+//! `period_ms`. The main loop asks `is_on(now)` every tick and switches the
+//! tone on or off, so nothing blocks while a pattern plays.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeepPattern {
+    pub count: u8,
+    pub on_ms: u16,
+    pub gap_ms: u16,
+    pub period_ms: u16,
+}
+
+impl BeepPattern {
+    pub const SILENT: BeepPattern = BeepPattern::new(0, 0, 0, 0);
+    /// One short beep every 2 s
+    pub const WARNING: BeepPattern = BeepPattern::new(1, 100, 0, 2000);
+    /// Rapid double beep
+    pub const ALERT: BeepPattern = BeepPattern::new(2, 80, 80, 600);
+
+    pub const fn new(count: u8, on_ms: u16, gap_ms: u16, period_ms: u16) -> Self {
+        Self { count, on_ms, gap_ms, period_ms }
+    }
+
+    /// Whether the beeps fit in the period (a silent pattern always does)
+    pub fn is_valid(&self) -> bool {
+        if self.count == 0 {
+            return true;
+        }
+        let beeps = self.count as u32 * (self.on_ms as u32 + self.gap_ms as u32) - self.gap_ms as u32;
+        self.on_ms > 0 && beeps <= self.period_ms as u32
+    }
+
+    /// Whether the tone is sounding at `t_ms`
+    pub fn is_on(&self, t_ms: u32) -> bool {
+        if self.count == 0 || self.on_ms == 0 || self.period_ms == 0 {
+            return false;
+        }
+        let phase = t_ms % self.period_ms as u32;
+        let slot = self.on_ms as u32 + self.gap_ms as u32;
+        phase / slot < self.count as u32 && phase % slot < self.on_ms as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_beep_timing() {
+        let p = BeepPattern::ALERT;
+        let on: [bool; 8] = core::array::from_fn(|i| p.is_on(i as u32 * 40));
+        // 0-80 on, 80-160 off, 160-240 on, then quiet until 600
+        assert_eq!(on, [true, true, false, false, true, true, false, false]);
+        assert!(!p.is_on(500));
+        assert!(p.is_on(600));
+        assert!(!BeepPattern::SILENT.is_on(0));
+    }
+
+    #[test]
+    fn test_pattern_must_fit_its_period() {
+        assert!(BeepPattern::WARNING.is_valid());
+        assert!(BeepPattern::ALERT.is_valid());
+        assert!(BeepPattern::SILENT.is_valid());
+        assert!(BeepPattern::new(3, 100, 100, 500).is_valid());
+        assert!(!BeepPattern::new(3, 100, 100, 499).is_valid());
+        assert!(!BeepPattern::new(1, 0, 0, 500).is_valid());
+    }
+}
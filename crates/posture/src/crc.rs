@@ -0,0 +1,37 @@
+//! CRC-8 for streamed text lines (`stream.crc`)
+//!
+//! Polynomial 0x07, initial value 0, no reflection (CRC-8/SMBUS), computed
+//! bit by bit: no 256-byte table in flash, and a line is only ~100 bytes.
+
+pub const POLY: u8 = 0x07;
+
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ POLY } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc8_check_value() {
+        // Standard check input for CRC-8/SMBUS
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc8(b""), 0x00);
+    }
+
+    #[test]
+    fn test_crc8_detects_single_char_change() {
+        let line = b"[dev=PostureMonitor state=Monitoring tilt=12.4]";
+        let mut corrupted = *line;
+        corrupted[30] ^= 0x01;
+        assert_ne!(crc8(line), crc8(&corrupted));
+    }
+}
@@ -0,0 +1,90 @@
+//! Quadrature decoding for a mechanical rotary encoder
+//!
+//! Feed the A/B pin levels on every poll; the decoder follows the Gray-code
+//! sequence and reports one step per detent. Invalid jumps (both pins
+//! changing between polls, i.e. a missed state) are ignored rather than
+//! guessed, so turning faster than the poll rate loses steps instead of
+//! reversing them.
+
+/// Count change for each (previous state, new state) pair, indexed by
+/// `prev << 2 | new` with `state = a << 1 | b`
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Gray-code transitions per mechanical detent
+const STEPS_PER_DETENT: i8 = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quadrature {
+    state: u8,
+    count: i8,
+}
+
+impl Quadrature {
+    /// Start from the current pin levels
+    pub fn new(a: bool, b: bool) -> Self {
+        Self {
+            state: (a as u8) << 1 | b as u8,
+            count: 0,
+        }
+    }
+
+    /// Feed the current pin levels; returns +1 (clockwise) or -1 once per
+    /// detent, 0 otherwise
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let new = (a as u8) << 1 | b as u8;
+        self.count += TRANSITIONS[(self.state << 2 | new) as usize];
+        self.state = new;
+
+        if self.count >= STEPS_PER_DETENT {
+            self.count = 0;
+            1
+        } else if self.count <= -STEPS_PER_DETENT {
+            self.count = 0;
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A leads B when turning clockwise
+    const CW: [(bool, bool); 4] = [(true, false), (true, true), (false, true), (false, false)];
+
+    fn feed(q: &mut Quadrature, seq: impl IntoIterator<Item = (bool, bool)>) -> i32 {
+        seq.into_iter().map(|(a, b)| q.update(a, b) as i32).sum()
+    }
+
+    #[test]
+    fn test_one_detent_each_way() {
+        let mut q = Quadrature::new(false, false);
+        assert_eq!(feed(&mut q, CW), 1);
+        assert_eq!(feed(&mut q, CW.iter().rev().skip(1).copied().chain([(false, false)])), -1);
+    }
+
+    #[test]
+    fn test_contact_bounce_cancels_out() {
+        let mut q = Quadrature::new(false, false);
+        let bounce = [(true, false), (false, false), (true, false), (false, false)];
+        assert_eq!(feed(&mut q, bounce), 0);
+        // A full detent afterwards still counts once
+        assert_eq!(feed(&mut q, CW), 1);
+    }
+
+    #[test]
+    fn test_skipped_state_is_ignored() {
+        let mut q = Quadrature::new(false, false);
+        // 00 -> 11 skips a state: direction unknown, no step
+        assert_eq!(feed(&mut q, [(true, true), (false, false)]), 0);
+    }
+
+    #[test]
+    fn test_many_detents() {
+        let mut q = Quadrature::new(false, false);
+        let total: i32 = (0..10).map(|_| feed(&mut q, CW)).sum();
+        assert_eq!(total, 10);
+    }
+}
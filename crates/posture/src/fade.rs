@@ -0,0 +1,103 @@
+//! Linear color fade for the Neopixel
+//!
+//! `ColorFade` crosses from the color currently shown to a target over a
+//! fixed duration. The caller polls `tick(now_ms)` from its loop and writes
+//! the result; times are the wrapping millisecond uptime (`UPTIME_MS`).
+
+use smart_leds::RGB8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ColorFade {
+    from: RGB8,
+    to: RGB8,
+    start_ms: u32,
+    duration_ms: u32,
+}
+
+impl ColorFade {
+    /// Showing `color`, no fade in progress
+    pub const fn new(color: RGB8, duration_ms: u32) -> Self {
+        Self {
+            from: color,
+            to: color,
+            start_ms: 0,
+            duration_ms,
+        }
+    }
+
+    /// Start fading towards `target`. Mid-fade, the new fade starts from
+    /// the color shown right now, so the output never jumps.
+    pub fn set_target(&mut self, target: RGB8, now_ms: u32) {
+        self.from = self.tick(now_ms);
+        self.to = target;
+        self.start_ms = now_ms;
+    }
+
+    /// Color to show at `now_ms`
+    pub fn tick(&self, now_ms: u32) -> RGB8 {
+        let t = now_ms.wrapping_sub(self.start_ms);
+        if t >= self.duration_ms {
+            return self.to;
+        }
+        let lerp = |a: u8, b: u8| (a as i32 + (b as i32 - a as i32) * t as i32 / self.duration_ms as i32) as u8;
+        RGB8::new(
+            lerp(self.from.r, self.to.r),
+            lerp(self.from.g, self.to.g),
+            lerp(self.from.b, self.to.b),
+        )
+    }
+
+    /// Still moving at `now_ms` (the caller only needs to repaint while true)
+    pub fn is_active(&self, now_ms: u32) -> bool {
+        self.from != self.to && now_ms.wrapping_sub(self.start_ms) < self.duration_ms
+    }
+
+    pub fn target(&self) -> RGB8 {
+        self.to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: RGB8 = RGB8::new(0, 30, 0);
+    const RED: RGB8 = RGB8::new(30, 0, 0);
+
+    #[test]
+    fn test_interpolates_to_target() {
+        let mut fade = ColorFade::new(GREEN, 300);
+        assert!(!fade.is_active(0));
+
+        fade.set_target(RED, 1000);
+        assert!(fade.is_active(1000));
+        assert_eq!(fade.tick(1000), GREEN);
+        assert_eq!(fade.tick(1100), RGB8::new(10, 20, 0));
+        assert_eq!(fade.tick(1150), RGB8::new(15, 15, 0));
+        assert_eq!(fade.tick(1300), RED);
+        assert_eq!(fade.tick(5000), RED);
+        assert!(!fade.is_active(1300));
+    }
+
+    #[test]
+    fn test_retarget_mid_fade_starts_from_current_color() {
+        let mut fade = ColorFade::new(GREEN, 300);
+        fade.set_target(RED, 0);
+        let shown = fade.tick(150);
+        assert_eq!(shown, RGB8::new(15, 15, 0));
+
+        // Back to green halfway: continues from the blend, no jump to red
+        fade.set_target(GREEN, 150);
+        assert_eq!(fade.tick(150), shown);
+        assert_eq!(fade.tick(300), RGB8::new(8, 22, 0));
+        assert_eq!(fade.tick(450), GREEN);
+    }
+
+    #[test]
+    fn test_survives_uptime_wrap() {
+        let mut fade = ColorFade::new(GREEN, 300);
+        fade.set_target(RED, u32::MAX - 99);
+        assert_eq!(fade.tick(50), RGB8::new(15, 15, 0));
+        assert_eq!(fade.tick(200), RED);
+    }
+}
@@ -0,0 +1,93 @@
+//! Free-fall and impact detection from the accelerometer magnitude
+//!
+//! At rest the accelerometer reads 1 g. In free fall every axis drops
+//! towards 0 g, and hitting something shows up as a short spike well above
+//! 1 g. `FallDetector` is fed one magnitude (in g) per sample and reports
+//! each event once: free fall after `freefall_samples` consecutive low
+//! samples, impact on the first sample over the threshold. Either re-arms
+//! once the magnitude is back on the other side of its threshold.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallEvent {
+    FreeFall,
+    Impact,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FallDetector {
+    freefall_g: f32,
+    freefall_samples: u32,
+    impact_g: f32,
+    low_count: u32,
+    in_impact: bool,
+}
+
+impl FallDetector {
+    pub const fn new(freefall_g: f32, freefall_samples: u32, impact_g: f32) -> Self {
+        Self {
+            freefall_g,
+            freefall_samples,
+            impact_g,
+            low_count: 0,
+            in_impact: false,
+        }
+    }
+
+    /// Feed one accel magnitude in g; returns the event it completes, if any
+    pub fn update(&mut self, magnitude_g: f32) -> Option<FallEvent> {
+        if magnitude_g < self.freefall_g {
+            self.low_count = self.low_count.saturating_add(1);
+        } else {
+            self.low_count = 0;
+        }
+
+        if magnitude_g > self.impact_g {
+            if !self.in_impact {
+                self.in_impact = true;
+                return Some(FallEvent::Impact);
+            }
+        } else {
+            self.in_impact = false;
+        }
+
+        if self.low_count == self.freefall_samples {
+            return Some(FallEvent::FreeFall);
+        }
+        None
+    }
+
+    /// Forget partial runs (e.g. when sampling pauses)
+    pub fn reset(&mut self) {
+        self.low_count = 0;
+        self.in_impact = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_fall_needs_consecutive_samples() {
+        let mut det = FallDetector::new(0.3, 3, 2.5);
+        assert_eq!(det.update(0.1), None);
+        assert_eq!(det.update(0.1), None);
+        assert_eq!(det.update(1.0), None); // run broken
+        assert_eq!(det.update(0.1), None);
+        assert_eq!(det.update(0.2), None);
+        assert_eq!(det.update(0.1), Some(FallEvent::FreeFall));
+        // Still falling: reported once
+        assert_eq!(det.update(0.1), None);
+        assert_eq!(det.update(0.1), None);
+    }
+
+    #[test]
+    fn test_impact_reported_once_per_spike() {
+        let mut det = FallDetector::new(0.3, 3, 2.5);
+        assert_eq!(det.update(1.0), None);
+        assert_eq!(det.update(3.2), Some(FallEvent::Impact));
+        assert_eq!(det.update(2.8), None);
+        assert_eq!(det.update(1.0), None);
+        assert_eq!(det.update(2.6), Some(FallEvent::Impact));
+    }
+}
@@ -0,0 +1,454 @@
+//! Tilt estimators fusing accelerometer angle with gyro rate
+//!
+//! - `Kalman1D`: two-state (angle, gyro bias) Kalman filter
+//! - `ComplementaryFilter`: fixed blend of integrated gyro and accel angle
+//! - `YawIntegrator`: heading from the Z gyro alone (no absolute reference)
+//! - `GyroBias`: gyro zero-rate offset learned while the device is at rest
+//! - `Ema` / `Ema3`: exponential moving average to smooth raw samples
+
+/// 1-D Kalman filter for a single angle
+///
+/// State is the angle and the gyro bias. The gyro rate drives the predict
+/// step (control input) and the accelerometer angle is the measurement.
+/// Angles in degrees, rates in degrees per second, `dt` in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Kalman1D {
+    /// Process noise of the angle
+    pub q_angle: f32,
+    /// Process noise of the gyro bias
+    pub q_bias: f32,
+    /// Measurement noise of the accelerometer angle
+    pub r_measure: f32,
+    angle: f32,
+    bias: f32,
+    p: [[f32; 2]; 2],
+}
+
+impl Kalman1D {
+    pub const DEFAULT_Q_ANGLE: f32 = 0.001;
+    pub const DEFAULT_Q_BIAS: f32 = 0.003;
+    pub const DEFAULT_R_MEASURE: f32 = 0.03;
+
+    pub const fn new(q_angle: f32, q_bias: f32, r_measure: f32) -> Self {
+        Self {
+            q_angle,
+            q_bias,
+            r_measure,
+            angle: 0.0,
+            bias: 0.0,
+            p: [[0.0; 2]; 2],
+        }
+    }
+
+    /// Restart from a known angle (e.g. the current accelerometer reading)
+    pub fn reset(&mut self, angle: f32) {
+        self.angle = angle;
+        self.bias = 0.0;
+        self.p = [[0.0; 2]; 2];
+    }
+
+    /// Predict with the gyro rate, correct with the measured angle;
+    /// returns the filtered angle
+    pub fn update(&mut self, measured_angle: f32, rate: f32, dt: f32) -> f32 {
+        // Predict
+        self.angle += dt * (rate - self.bias);
+        self.p[0][0] += dt * (dt * self.p[1][1] - self.p[0][1] - self.p[1][0] + self.q_angle);
+        self.p[0][1] -= dt * self.p[1][1];
+        self.p[1][0] -= dt * self.p[1][1];
+        self.p[1][1] += self.q_bias * dt;
+
+        // Correct
+        let s = self.p[0][0] + self.r_measure;
+        let k = [self.p[0][0] / s, self.p[1][0] / s];
+        let innovation = measured_angle - self.angle;
+        self.angle += k[0] * innovation;
+        self.bias += k[1] * innovation;
+
+        let (p00, p01) = (self.p[0][0], self.p[0][1]);
+        self.p[0][0] -= k[0] * p00;
+        self.p[0][1] -= k[0] * p01;
+        self.p[1][0] -= k[1] * p00;
+        self.p[1][1] -= k[1] * p01;
+
+        self.angle
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Current gyro bias estimate (degrees per second)
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+}
+
+impl Default for Kalman1D {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_Q_ANGLE, Self::DEFAULT_Q_BIAS, Self::DEFAULT_R_MEASURE)
+    }
+}
+
+/// Complementary filter for a single angle
+///
+/// High-passes the integrated gyro and low-passes the accelerometer angle:
+/// `alpha * (prev + rate * dt) + (1 - alpha) * measured`. Cheaper than
+/// `Kalman1D` but can't learn the gyro bias, so a biased gyro leaves a
+/// constant offset of `bias * dt * alpha / (1 - alpha)`.
+#[derive(Debug, Clone, Copy)]
+pub struct ComplementaryFilter {
+    /// Weight of the gyro path (0..1)
+    pub alpha: f32,
+    angle: f32,
+}
+
+impl ComplementaryFilter {
+    pub const DEFAULT_ALPHA: f32 = 0.98;
+
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, angle: 0.0 }
+    }
+
+    /// Restart from a known angle (e.g. the current accelerometer reading)
+    pub fn reset(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    /// Blend the gyro-propagated angle with the measured one; returns the
+    /// filtered angle
+    pub fn update(&mut self, accel_angle: f32, gyro_rate_dps: f32, dt_s: f32) -> f32 {
+        self.angle = self.alpha * (self.angle + gyro_rate_dps * dt_s) + (1.0 - self.alpha) * accel_angle;
+        self.angle
+    }
+
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+}
+
+impl Default for ComplementaryFilter {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ALPHA)
+    }
+}
+
+/// Heading (yaw) by integrating the Z-axis gyro
+///
+/// Gravity says nothing about rotation around the vertical axis, so there is
+/// no measurement to correct against: any gyro bias accumulates and the
+/// heading drifts (a 0.5 °/s bias is 30° per minute). Good for relative turns
+/// over a few seconds, not for an absolute compass heading.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YawIntegrator {
+    yaw: f32,
+}
+
+impl YawIntegrator {
+    /// Gyro sensitivity at the default ±250 °/s range
+    pub const LSB_PER_DPS: f32 = 131.0;
+
+    pub const fn new() -> Self {
+        Self { yaw: 0.0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.yaw = 0.0;
+    }
+
+    /// Accumulate one raw Z gyro sample; returns the heading in [0, 360)
+    pub fn update(&mut self, gyro_z_raw: i16, dt_s: f32) -> f32 {
+        let rate_dps = gyro_z_raw as f32 / Self::LSB_PER_DPS;
+        self.yaw = (self.yaw + rate_dps * dt_s) % 360.0;
+        if self.yaw < 0.0 {
+            self.yaw += 360.0;
+        }
+        // A tiny negative value plus 360 rounds to exactly 360.0 in f32
+        if self.yaw >= 360.0 {
+            self.yaw = 0.0;
+        }
+        self.yaw
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+}
+
+/// Gyro zero-rate offset, averaged from samples taken at rest
+///
+/// Only feed it samples taken while the device is known to be still: the
+/// true rate is zero then, so whatever the gyro reads is its bias. Each
+/// sample moves the estimate by `RATE` of the difference, so a movement the
+/// stillness check misses barely shifts it. All values in raw counts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GyroBias {
+    bias: [f32; 3],
+    samples: u32,
+}
+
+impl GyroBias {
+    /// Weight of each new sample (time constant of 100 samples, 5 s at 50 ms)
+    pub const RATE: f32 = 0.01;
+
+    pub const fn new() -> Self {
+        Self { bias: [0.0; 3], samples: 0 }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Fold in one raw sample taken while still
+    pub fn update(&mut self, raw: [i16; 3]) {
+        for (bias, &v) in self.bias.iter_mut().zip(raw.iter()) {
+            *bias += Self::RATE * (v as f32 - *bias);
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// `raw` with the bias subtracted, rounded to the nearest count
+    pub fn correct(&self, raw: [i16; 3]) -> [i16; 3] {
+        let mut out = [0i16; 3];
+        for ((out, &v), &bias) in out.iter_mut().zip(raw.iter()).zip(self.bias.iter()) {
+            let corrected = v as f32 - bias;
+            // `as` saturates at the i16 limits
+            *out = (if corrected >= 0.0 { corrected + 0.5 } else { corrected - 0.5 }) as i16;
+        }
+        out
+    }
+
+    pub fn bias(&self) -> [f32; 3] {
+        self.bias
+    }
+
+    /// Samples averaged since the last reset
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+}
+
+/// Exponential moving average: `alpha * x + (1 - alpha) * prev`
+///
+/// `alpha` is the weight of the new sample: 1.0 passes samples through
+/// unchanged, smaller values smooth more (and lag more). The first sample
+/// after `new`/`reset` is taken as is, so the output doesn't ramp up from 0.
+#[derive(Debug, Clone, Copy)]
+pub struct Ema {
+    pub alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, value: None }
+    }
+
+    /// Forget the history; the next sample is taken as is
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+
+    pub fn update(&mut self, x: f32) -> f32 {
+        let value = match self.value {
+            Some(prev) => self.alpha * x + (1.0 - self.alpha) * prev,
+            None => x,
+        };
+        self.value = Some(value);
+        value
+    }
+}
+
+/// `Ema` over the three axes of a vector (e.g. the accelerometer)
+#[derive(Debug, Clone, Copy)]
+pub struct Ema3 {
+    axes: [Ema; 3],
+}
+
+impl Ema3 {
+    pub const fn new(alpha: f32) -> Self {
+        Self { axes: [Ema::new(alpha); 3] }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.axes[0].alpha
+    }
+
+    /// Change the smoothing, keeping the current value
+    pub fn set_alpha(&mut self, alpha: f32) {
+        for axis in &mut self.axes {
+            axis.alpha = alpha;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for axis in &mut self.axes {
+            axis.reset();
+        }
+    }
+
+    pub fn update(&mut self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        [self.axes[0].update(x), self.axes[1].update(y), self.axes[2].update(z)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise in [-amplitude, amplitude] (LCG, no std RNG needed)
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self, amplitude: f32) -> f32 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            ((self.0 >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0) * amplitude
+        }
+    }
+
+    const DT: f32 = 0.05;
+
+    #[test]
+    fn test_converges_on_constant_angle() {
+        let mut kalman = Kalman1D::default();
+        let mut noise = Noise(1);
+        for _ in 0..400 {
+            kalman.update(30.0 + noise.next(3.0), 0.0, DT);
+        }
+        assert!((kalman.angle() - 30.0).abs() < 1.0, "angle={}", kalman.angle());
+    }
+
+    #[test]
+    fn test_smooths_measurement_noise() {
+        let mut kalman = Kalman1D::default();
+        kalman.reset(10.0);
+        let mut noise = Noise(7);
+        let (mut raw_err, mut filtered_err) = (0.0, 0.0);
+        for _ in 0..400 {
+            let measured = 10.0 + noise.next(5.0);
+            let filtered = kalman.update(measured, 0.0, DT);
+            raw_err += (measured - 10.0f32).abs();
+            filtered_err += (filtered - 10.0f32).abs();
+        }
+        assert!(filtered_err < raw_err / 3.0, "raw={raw_err} filtered={filtered_err}");
+    }
+
+    #[test]
+    fn test_tracks_ramp_and_learns_gyro_bias() {
+        // True angle ramps at 10 °/s; gyro reads 12 °/s (2 °/s bias)
+        let mut kalman = Kalman1D::default();
+        let mut noise = Noise(42);
+        let mut truth = 0.0;
+        for _ in 0..1200 {
+            truth += 10.0 * DT;
+            kalman.update(truth + noise.next(2.0), 12.0, DT);
+        }
+        assert!((kalman.angle() - truth).abs() < 1.0, "angle={} truth={truth}", kalman.angle());
+        assert!((kalman.bias() - 2.0).abs() < 0.5, "bias={}", kalman.bias());
+    }
+
+    #[test]
+    fn test_complementary_converges_and_smooths() {
+        let mut comp = ComplementaryFilter::default();
+        let mut noise = Noise(3);
+        for _ in 0..400 {
+            comp.update(20.0 + noise.next(3.0), 0.0, DT);
+        }
+        assert!((comp.angle() - 20.0).abs() < 1.0, "angle={}", comp.angle());
+    }
+
+    #[test]
+    fn test_complementary_follows_gyro_on_ramp() {
+        // Gyro matches the true rate, so the accel angle only corrects noise
+        let mut comp = ComplementaryFilter::default();
+        let mut noise = Noise(9);
+        let mut truth = 0.0;
+        for _ in 0..400 {
+            truth += 10.0 * DT;
+            comp.update(truth + noise.next(2.0), 10.0, DT);
+        }
+        assert!((comp.angle() - truth).abs() < 0.5, "angle={} truth={truth}", comp.angle());
+    }
+
+    #[test]
+    fn test_yaw_integrates_and_wraps() {
+        let mut yaw = YawIntegrator::new();
+        // 131 LSB = 1 °/s; 90 °/s for 1 s
+        for _ in 0..20 {
+            yaw.update(90 * 131, DT);
+        }
+        assert!((yaw.yaw() - 90.0).abs() < 0.01, "yaw={}", yaw.yaw());
+
+        // Turning back past zero wraps to the top of the range
+        for _ in 0..40 {
+            yaw.update(-90 * 131, DT);
+        }
+        assert!((yaw.yaw() - 270.0).abs() < 0.01, "yaw={}", yaw.yaw());
+
+        // Past 360 wraps to the bottom
+        for _ in 0..40 {
+            yaw.update(90 * 131, DT);
+        }
+        assert!(yaw.yaw() < 360.0 && (yaw.yaw() - 90.0).abs() < 0.01, "yaw={}", yaw.yaw());
+
+        yaw.reset();
+        assert_eq!(yaw.yaw(), 0.0);
+    }
+
+    #[test]
+    fn test_gyro_bias_converges_and_corrects() {
+        let mut bias = GyroBias::new();
+        assert_eq!(bias.correct([100, -50, 0]), [100, -50, 0]);
+
+        // At rest with a constant offset (plus noise): 10 s at 50 ms
+        let mut noise = Noise(7);
+        for _ in 0..200 {
+            let n = noise.next(20.0) as i16;
+            bias.update([65 + n, -131 + n, 13 - n]);
+        }
+        let [bx, by, bz] = bias.bias();
+        assert!((bx - 65.0).abs() < 10.0 && (by + 131.0).abs() < 20.0 && (bz - 13.0).abs() < 10.0, "bias={:?}", bias.bias());
+        assert_eq!(bias.samples(), 200);
+
+        // Integrating the corrected Z rate barely drifts; the raw one does 1° per 10 s
+        let mut yaw = YawIntegrator::new();
+        for _ in 0..200 {
+            yaw.update(bias.correct([65, -131, 13])[2], DT);
+        }
+        let drift = yaw.yaw().min(360.0 - yaw.yaw());
+        assert!(drift < 0.2, "yaw={}", yaw.yaw());
+
+        bias.reset();
+        assert_eq!(bias.samples(), 0);
+        assert_eq!(bias.correct([1, 2, 3]), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_ema_smooths_and_passes_through() {
+        // alpha 1.0: output is the input
+        let mut pass = Ema::new(1.0);
+        for x in [3.0, -7.0, 12.5] {
+            assert_eq!(pass.update(x), x);
+        }
+
+        // First sample is taken as is, then a step moves by alpha per sample
+        let mut ema = Ema::new(0.25);
+        assert_eq!(ema.update(8.0), 8.0);
+        assert_eq!(ema.update(0.0), 6.0);
+        assert_eq!(ema.update(0.0), 4.5);
+
+        // Noise shrinks, the mean is kept
+        let mut ema3 = Ema3::new(0.1);
+        let mut noise = Noise(5);
+        let mut out = [0.0; 3];
+        let (mut raw_err, mut smooth_err) = (0.0, 0.0);
+        for _ in 0..400 {
+            let x = 100.0 + noise.next(10.0);
+            out = ema3.update([x, -x, 0.0]);
+            raw_err += (x - 100.0f32).abs();
+            smooth_err += (out[0] - 100.0f32).abs();
+        }
+        assert!(smooth_err < raw_err / 3.0, "raw={raw_err} smooth={smooth_err}");
+        assert_eq!(out[1], -out[0]);
+        assert_eq!(out[2], 0.0);
+    }
+}
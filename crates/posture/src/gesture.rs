@@ -0,0 +1,136 @@
+//! Shake gesture detection from the accelerometer
+//!
+//! A shake is a rapid back-and-forth: the acceleration swings one way, then
+//! the other, several times in a row. `ShakeDetector` high-passes each axis
+//! (the sample minus a slow moving average, which removes gravity and the
+//! posture itself) and follows the axis with the largest swing. Each time
+//! that swing exceeds the threshold with the opposite sign to the previous
+//! one counts as a reversal; enough reversals within the window make a
+//! shake. It is reported once, then counting starts over.
+
+/// Last gesture seen, as stored in the `GESTURE` event static
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Gesture {
+    None = 0,
+    Shake = 1,
+}
+
+/// Weight of each sample in the high-pass baseline (~10 samples, 0.5 s at 50 ms)
+const BASELINE_ALPHA: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShakeDetector {
+    threshold_g: f32,
+    sign_changes: u32,
+    window_ms: u32,
+    baseline: Option<[f32; 3]>,
+    /// Sign of the last swing over the threshold (0 = none yet)
+    last_sign: i8,
+    reversals: u32,
+    first_reversal_ms: u32,
+}
+
+impl ShakeDetector {
+    pub const fn new(threshold_g: f32, sign_changes: u32, window_ms: u32) -> Self {
+        Self {
+            threshold_g,
+            sign_changes,
+            window_ms,
+            baseline: None,
+            last_sign: 0,
+            reversals: 0,
+            first_reversal_ms: 0,
+        }
+    }
+
+    /// Feed one accel sample (each axis in g) taken at `t_ms`; true when it completes a shake
+    pub fn update(&mut self, t_ms: u32, accel_g: [f32; 3]) -> bool {
+        let Some(baseline) = self.baseline.as_mut() else {
+            self.baseline = Some(accel_g);
+            return false;
+        };
+        let mut swing = 0.0f32;
+        for (base, &a) in baseline.iter_mut().zip(accel_g.iter()) {
+            let hp = a - *base;
+            *base += BASELINE_ALPHA * hp;
+            if hp.abs() > swing.abs() {
+                swing = hp;
+            }
+        }
+
+        if self.reversals > 0 && t_ms.wrapping_sub(self.first_reversal_ms) > self.window_ms {
+            self.reversals = 0;
+        }
+        if swing.abs() < self.threshold_g {
+            return false;
+        }
+        let sign = if swing > 0.0 { 1 } else { -1 };
+        if self.last_sign != 0 && sign != self.last_sign {
+            if self.reversals == 0 {
+                self.first_reversal_ms = t_ms;
+            }
+            self.reversals += 1;
+        }
+        self.last_sign = sign;
+
+        if self.reversals >= self.sign_changes {
+            self.reversals = 0;
+            self.last_sign = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Forget the baseline and partial shakes (e.g. when sampling pauses)
+    pub fn reset(&mut self) {
+        self.baseline = None;
+        self.last_sign = 0;
+        self.reversals = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_MS: u32 = 50;
+
+    /// Lying flat, then +/- `swing_g` on X, flipping every `half_period` samples
+    fn run(det: &mut ShakeDetector, swing_g: f32, half_period: u32, samples: u32) -> u32 {
+        let mut shakes = 0;
+        for i in 0..samples {
+            let x = if (i / half_period) & 1 == 0 { swing_g } else { -swing_g };
+            if det.update(100 * PERIOD_MS + i * PERIOD_MS, [x, 0.0, 1.0]) {
+                shakes += 1;
+            }
+        }
+        shakes
+    }
+
+    #[test]
+    fn test_shake_detected_once_per_burst() {
+        let mut det = ShakeDetector::new(1.0, 4, 1000);
+        for i in 0..100 {
+            assert!(!det.update(i * PERIOD_MS, [0.0, 0.0, 1.0]));
+        }
+        // 1.5 g swings reversing every 100 ms: four reversals in 0.35 s
+        assert_eq!(run(&mut det, 1.5, 2, 10), 1);
+    }
+
+    #[test]
+    fn test_gentle_or_slow_motion_is_not_a_shake() {
+        let mut det = ShakeDetector::new(1.0, 4, 1000);
+        // Below the threshold
+        assert_eq!(run(&mut det, 0.5, 2, 40), 0);
+        // Strong, but one reversal per second: never four inside the window
+        det.reset();
+        assert_eq!(run(&mut det, 1.5, 20, 100), 0);
+        // A tilt is a step, not a swing back and forth
+        det.reset();
+        for i in 0..40 {
+            let tilted = if i < 10 { [0.0, 0.0, 1.0] } else { [1.0, 0.0, 0.0] };
+            assert!(!det.update(i * PERIOD_MS, tilted));
+        }
+    }
+}
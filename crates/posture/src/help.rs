@@ -0,0 +1,621 @@
+//! Command help: the one-line summaries `help` lists and the longer text
+//! `help <command>` prints, kept in one table so the two can't drift apart
+//!
+//! Entries are in `help` order. A summary is the usage padded to the
+//! description column (20 characters), without the leading indent or line
+//! ending; a detail is one or more lines separated by `\r\n`, ending with an
+//! example. Adding a command to `process_command` means adding it here.
+
+pub struct HelpEntry {
+    /// Commands this entry covers; `help <name>` accepts any of them
+    pub names: &'static [&'static str],
+    /// Line shown in the `help` list
+    pub summary: &'static str,
+    /// Usage details, argument ranges and an example for `help <name>`
+    pub detail: &'static str,
+}
+
+pub const COMMANDS: &[HelpEntry] = &[
+    HelpEntry {
+        names: &["help"],
+        summary: "help [command]      - List commands, or details for one",
+        detail: "Without an argument lists every command with a one-line summary.\r\n\
+                 With a command name prints its arguments, ranges and an example.\r\n\
+                 Example: help device.cal_zero",
+    },
+    HelpEntry {
+        names: &["device.start"],
+        summary: "device.start        - Start monitoring",
+        detail: "Switches to the Monitoring state: the IMU is sampled every\r\n\
+                 posture.sample_period ms and the Neopixel shows the alert level.\r\n\
+                 Uncalibrated, tilt is measured from vertical (see device.cal_zero).\r\n\
+                 Example: device.start",
+    },
+    HelpEntry {
+        names: &["device.cal_zero"],
+        summary: "device.cal_zero     - Calibrate zero orientation",
+        detail: "Averages 100 accel samples and makes the current orientation 0° tilt.\r\n\
+                 Keep the board still: any gyro axis above 5 °/s aborts the run\r\n\
+                 (red flash, previous offsets kept). A fresh read must then be within\r\n\
+                 3° of zero, retried up to device.cal_retries times; if it still fails\r\n\
+                 the device stops in Sleep with the Neopixel red. On success the\r\n\
+                 offsets are saved to flash, any cal.mount reference is cleared and\r\n\
+                 monitoring starts. A short button press while monitoring does the\r\n\
+                 same.\r\n\
+                 Example: device.cal_zero",
+    },
+    HelpEntry {
+        names: &["device.cal_check"],
+        summary: "device.cal_check    - Check board is still in calibrated orientation",
+        detail: "Compares the current gravity vector with the one captured at\r\n\
+                 calibration and suggests recalibrating if they are more than 15°\r\n\
+                 apart. Reads the IMU once; nothing is changed. The same check runs\r\n\
+                 at boot when a calibration is loaded from flash.\r\n\
+                 Example: device.cal_check",
+    },
+    HelpEntry {
+        names: &["cal.verify"],
+        summary: "cal.verify          - PASS/FAIL: mean tilt of 20 reads within 3° of zero",
+        detail: "Averages 20 corrected reads 5 ms apart and prints PASS if the mean\r\n\
+                 tilt is within 3° of zero, FAIL otherwise. Hold the board in its\r\n\
+                 calibrated (or cal.mount) position first. Nothing is changed.\r\n\
+                 Example: cal.verify",
+    },
+    HelpEntry {
+        names: &["cal.save"],
+        summary: "cal.save            - Save calibration offsets to flash",
+        detail: "Writes the current offsets to flash, for the current accel range.\r\n\
+                 device.cal_zero already saves them on success.\r\n\
+                 Example: cal.save",
+    },
+    HelpEntry {
+        names: &["cal.load"],
+        summary: "cal.load            - Restore calibration offsets from flash",
+        detail: "Reloads the offsets saved for the current accel range and marks the\r\n\
+                 device calibrated. Fails if nothing was saved for that range.\r\n\
+                 Example: cal.load",
+    },
+    HelpEntry {
+        names: &["cal.mount"],
+        summary: "cal.mount [clear]   - Measure tilt from the current resting position",
+        detail: "Averages 32 reads and uses that resting vector as the reference, so\r\n\
+                 tilt is measured from how the device is mounted instead of from\r\n\
+                 vertical. Fails if the device moves meanwhile. Saved to flash.\r\n\
+                 `clear` goes back to tilt from vertical. device.cal_zero clears it.\r\n\
+                 Example: cal.mount",
+    },
+    HelpEntry {
+        names: &["device.cal_retries"],
+        summary: "device.cal_retries <n> - Calibration retries on verify failure (0-10)",
+        detail: "How many times device.cal_zero collects again when the post-\r\n\
+                 calibration check fails (0-10). 0 gives up after the first attempt.\r\n\
+                 Example: device.cal_retries 3",
+    },
+    HelpEntry {
+        names: &["device.cal_hw"],
+        summary: "device.cal_hw [on|off] - Correct offsets in MPU registers vs software",
+        detail: "Without an argument shows where offsets are applied and the factory\r\n\
+                 trim. `on` writes them into the MPU6050 offset registers (readings\r\n\
+                 come out corrected); `off` restores the factory trim and subtracts\r\n\
+                 them in software. `on` needs an MPU6050 (WHO_AM_I 0x68).\r\n\
+                 Example: device.cal_hw on",
+    },
+    HelpEntry {
+        names: &["device.sleep"],
+        summary: "device.sleep        - Enter sleep mode",
+        detail: "Stops monitoring and turns the Neopixel off. A 3 s button press or\r\n\
+                 device.start resumes. See device.light_sleep to also sleep the chip.\r\n\
+                 Example: device.sleep",
+    },
+    HelpEntry {
+        names: &["device.light_sleep"],
+        summary: "device.light_sleep <on|off> - Light-sleep the chip in Sleep (wake: button)",
+        detail: "`on` (default) light-sleeps the chip in the Sleep state until the\r\n\
+                 button or a 1 s timer wakes it. It stays awake for 10 s after each\r\n\
+                 received character so the CLI stays usable. `off` keeps the CPU\r\n\
+                 running in Sleep.\r\n\
+                 Example: device.light_sleep on",
+    },
+    HelpEntry {
+        names: &["device.appdesc"],
+        summary: "device.appdesc      - Show the app descriptor (version, project, IDF)",
+        detail: "Prints the fields of the app descriptor embedded in the image, as\r\n\
+                 the bootloader sees them.\r\n\
+                 Example: device.appdesc",
+    },
+    HelpEntry {
+        names: &["device.boots"],
+        summary: "device.boots        - Boot count (persisted in flash)",
+        detail: "Number of boots since the count was first written to flash.\r\n\
+                 Example: device.boots",
+    },
+    HelpEntry {
+        names: &["device.status"],
+        summary: "device.status       - Show device status",
+        detail: "State, alert level and tilt, thresholds, temperature and its limit,\r\n\
+                 LED polarity, tilt reference, and any fault or debug override.\r\n\
+                 Example: device.status",
+    },
+    HelpEntry {
+        names: &["gpio.on", "gpio.off"],
+        summary: "gpio.on/off         - LED control",
+        detail: "Turns the LED on GPIO12 on or off. While monitoring, alerts also\r\n\
+                 drive it, and a fault blink code overrides it.\r\n\
+                 Example: gpio.on",
+    },
+    HelpEntry {
+        names: &["gpio.read"],
+        summary: "gpio.read <pin>     - Read an input: 9 = button, 4/5 = encoder A/B",
+        detail: "Prints the level of an input pin: 9 (button, low when pressed),\r\n\
+                 4 and 5 (encoder A/B). Output pins are refused.\r\n\
+                 Example: gpio.read 9",
+    },
+    HelpEntry {
+        names: &["neo.color"],
+        summary: "neo.color <r> <g> <b> - Set Neopixel",
+        detail: "Each channel is 0-255, scaled by neo.bright. Stops a strobe. While\r\n\
+                 monitoring, the next alert level change repaints the pixel.\r\n\
+                 Example: neo.color 255 0 0",
+    },
+    HelpEntry {
+        names: &["neo.off"],
+        summary: "neo.off             - Neopixel off (stops strobe)",
+        detail: "Turns the pixel off and stops a running neo.strobe.\r\n\
+                 Example: neo.off",
+    },
+    HelpEntry {
+        names: &["neo.bright"],
+        summary: "neo.bright [0-255]  - Show or set Neopixel brightness (255 = full)",
+        detail: "Scales every color shown, including alert colors (0-255, 255 =\r\n\
+                 full). Without an argument shows the current value.\r\n\
+                 Example: neo.bright 64",
+    },
+    HelpEntry {
+        names: &["buzzer.test"],
+        summary: "buzzer.test <hz>    - Sound the buzzer for 0.5 s (100-10000 Hz)",
+        detail: "Plays a 0.5 s tone at 100-10000 Hz, then restores the 2 kHz alert\r\n\
+                 tone. Blocks the CLI while it sounds; ignores buzzer.mute.\r\n\
+                 Example: buzzer.test 440",
+    },
+    HelpEntry {
+        names: &["buzzer.mute"],
+        summary: "buzzer.mute [on|off] - Silence the alert beeps (no argument toggles)",
+        detail: "Mutes or unmutes the warning/alert beeps; without an argument\r\n\
+                 toggles. The Neopixel still shows the alert level.\r\n\
+                 Example: buzzer.mute on",
+    },
+    HelpEntry {
+        names: &["buzzer.pattern"],
+        summary: "buzzer.pattern [warning|alert <n> <on> <gap> <period>] - Show or set a beep pattern",
+        detail: "<n> beeps of <on> ms, <gap> ms apart, repeated every <period> ms\r\n\
+                 (all ms 0-65535; n = 0 silences the level). The beeps must fit in\r\n\
+                 the period. Defaults: warning 1 100 0 2000, alert 2 80 80 600.\r\n\
+                 Example: buzzer.pattern alert 3 60 60 800",
+    },
+    HelpEntry {
+        names: &["neo.strobe"],
+        summary: "neo.strobe <hz>     - White strobe, max 3 Hz\r\n\
+                   \x20                       WARNING: flashing light, photosensitivity risk",
+        detail: "Flashes the pixel white at <hz> (at least 1). Rates above 3 Hz are\r\n\
+                 capped at 3 Hz (photosensitivity guideline). neo.off or neo.color\r\n\
+                 stops it. An impact also starts it at 3 Hz for 3 s.\r\n\
+                 Example: neo.strobe 2",
+    },
+    HelpEntry {
+        names: &["posture.color"],
+        summary: "posture.color <normal|warning|alert> <r> <g> <b> - Alert color",
+        detail: "Sets the Neopixel color for one alert level, each channel 0-255.\r\n\
+                 Saved to flash and restored at boot. See posture.colors.\r\n\
+                 Example: posture.color warning 40 20 0",
+    },
+    HelpEntry {
+        names: &["posture.colors"],
+        summary: "posture.colors      - Show alert colors",
+        detail: "Prints the RGB color used for each alert level.\r\n\
+                 Example: posture.colors",
+    },
+    HelpEntry {
+        names: &["posture.filter"],
+        summary: "posture.filter <none|comp|kalman> [q_angle q_bias r] - Tilt estimator",
+        detail: "none: raw accel angle. comp: complementary filter (see imu.alpha).\r\n\
+                 kalman: Kalman filter, optionally with all three noise values\r\n\
+                 (positive numbers); it restarts from the current angle.\r\n\
+                 Example: posture.filter kalman 0.001 0.003 0.03",
+    },
+    HelpEntry {
+        names: &["posture.compare"],
+        summary: "posture.compare <on|off> - Print raw/complementary/Kalman angle per sample",
+        detail: "`on` prints one line per Monitoring sample with every estimator's\r\n\
+                 angle side by side, to choose a posture.filter. `off` stops it.\r\n\
+                 Example: posture.compare on",
+    },
+    HelpEntry {
+        names: &["posture.alert_count"],
+        summary: "posture.alert_count [reset] - Times each alert level was entered",
+        detail: "Counts of entries into normal, warning and alert since boot (or\r\n\
+                 the last reset). `reset` zeroes them.\r\n\
+                 Example: posture.alert_count reset",
+    },
+    HelpEntry {
+        names: &["posture.score"],
+        summary: "posture.score       - Percent of the last 60 s in good posture",
+        detail: "Share of the last 60 s spent at each alert level, refreshed once a\r\n\
+                 second while monitoring. Fails until monitoring has started.\r\n\
+                 Example: posture.score",
+    },
+    HelpEntry {
+        names: &["posture.cooldown"],
+        summary: "posture.cooldown <s> - Suppress repeat alert notifications (0 = off)",
+        detail: "Re-entering warning or alert within <s> seconds of its last\r\n\
+                 notification updates the color but doesn't log or beep (default 10).\r\n\
+                 0 notifies every time.\r\n\
+                 Example: posture.cooldown 30",
+    },
+    HelpEntry {
+        names: &["posture.warn"],
+        summary: "posture.warn <deg>  - Set the warning threshold (0-90, below alert)",
+        detail: "Tilt in degrees (0-90) at which the warning level starts. Must stay\r\n\
+                 below the alert threshold. Refused while posture.knob is on.\r\n\
+                 Example: posture.warn 15",
+    },
+    HelpEntry {
+        names: &["posture.alert"],
+        summary: "posture.alert <deg> - Set the alert threshold (0-90, above warning)",
+        detail: "Tilt in degrees (0-90) at which the alert level starts. Must stay\r\n\
+                 above the warning threshold.\r\n\
+                 Example: posture.alert 30",
+    },
+    HelpEntry {
+        names: &["posture.thresholds"],
+        summary: "posture.thresholds [w a] - Show or set the warning/alert thresholds",
+        detail: "Without arguments prints both thresholds in degrees. With two, sets\r\n\
+                 warning and alert together (0 < warn < alert < 90).\r\n\
+                 Example: posture.thresholds 25 55",
+    },
+    HelpEntry {
+        names: &["posture.knob"],
+        summary: "posture.knob <on|off> - Warning threshold follows potentiometer",
+        detail: "`on` keeps setting the warning threshold from the potentiometer\r\n\
+                 (posture.warn is refused meanwhile); `off` leaves it where it is.\r\n\
+                 Example: posture.knob on",
+    },
+    HelpEntry {
+        names: &["posture.sedentary_timeout"],
+        summary: "posture.sedentary_timeout <min> - Blue nudge after no movement (0 = off)",
+        detail: "Pulses the Neopixel blue after <min> minutes without movement\r\n\
+                 (0-1440, 0 = off).\r\n\
+                 Example: posture.sedentary_timeout 45",
+    },
+    HelpEntry {
+        names: &["posture.autocal"],
+        summary: "posture.autocal [on [s]|off] - Calibrate once still for <s> seconds if uncalibrated",
+        detail: "`on` runs device.cal_zero automatically once an uncalibrated device\r\n\
+                 has been still for <s> seconds (1-600, default unchanged). Without\r\n\
+                 an argument shows the setting and whether the device is calibrated.\r\n\
+                 Example: posture.autocal on 10",
+    },
+    HelpEntry {
+        names: &["knob.target"],
+        summary: "knob.target <none|warn|alert|period> - Setting the rotary encoder adjusts",
+        detail: "Picks what turning the rotary encoder changes: the warning or alert\r\n\
+                 threshold, the sample period, or nothing. Thresholds stay within\r\n\
+                 the posture.warn/alert rule (0 < warn < alert < 90°).\r\n\
+                 Example: knob.target alert",
+    },
+    HelpEntry {
+        names: &["posture.sample_period"],
+        summary: "posture.sample_period <ms> - IMU sample period (10-1000)",
+        detail: "Monitoring sample period in ms (10-1000). Shorter reacts faster\r\n\
+                 and streams more data.\r\n\
+                 Example: posture.sample_period 20",
+    },
+    HelpEntry {
+        names: &["imu.addr"],
+        summary: "imu.addr [0x68|0x69] - Show or set the MPU6050 I2C address",
+        detail: "0x68 with AD0 low, 0x69 with AD0 high. Only switches if a sensor\r\n\
+                 answers at the new address, which is then set up again.\r\n\
+                 Example: imu.addr 0x69",
+    },
+    HelpEntry {
+        names: &["imu.read"],
+        summary: "imu.read            - Read accel data",
+        detail: "One raw accel reading (counts, no calibration applied).\r\n\
+                 Example: imu.read",
+    },
+    HelpEntry {
+        names: &["imu.dump"],
+        summary: "imu.dump            - Raw MPU6050 config, interrupt and power registers in hex",
+        detail: "Reads SMPLRT_DIV..ACCEL_CONFIG (0x19-0x1C), INT_PIN_CFG/INT_ENABLE\r\n\
+                 (0x37-0x38), USER_CTRL/PWR_MGMT_1/PWR_MGMT_2 (0x6A-0x6C) and\r\n\
+                 WHO_AM_I (0x75), printed as register:value pairs.\r\n\
+                 Example: imu.dump",
+    },
+    HelpEntry {
+        names: &["imu.alpha"],
+        summary: "imu.alpha [a]       - Show or set the complementary filter gyro weight (0-1)",
+        detail: "Weight of the gyro in the complementary filter, 0.0-1.0: higher is\r\n\
+                 smoother but slower to follow the accel. Keeps the current angle.\r\n\
+                 Example: imu.alpha 0.98",
+    },
+    HelpEntry {
+        names: &["imu.smooth"],
+        summary: "imu.smooth [a]      - Show or set accel smoothing before the angle (0-1, 1 = off)",
+        detail: "Weight of each new accel sample in the smoothing average, above 0\r\n\
+                 and up to 1.0 (1.0 = no smoothing).\r\n\
+                 Example: imu.smooth 0.3",
+    },
+    HelpEntry {
+        names: &["imu.dlpf"],
+        summary: "imu.dlpf [0-6]      - Show or set the on-chip low-pass filter (0 = 260 Hz .. 6 = 5 Hz)",
+        detail: "MPU6050 digital low-pass filter setting 0-6, from 260 Hz (0) down\r\n\
+                 to 5 Hz (6) accel bandwidth. Lower bandwidth means less noise and\r\n\
+                 more delay.\r\n\
+                 Example: imu.dlpf 3",
+    },
+    HelpEntry {
+        names: &["imu.capture"],
+        summary: "imu.capture <n> [csv|hex] - Burst-capture n accel/gyro samples (max 512), then dump",
+        detail: "Reads 1-512 accel/gyro samples back to back into RAM, then prints\r\n\
+                 them with microsecond timestamps as CSV (default) or big-endian hex.\r\n\
+                 Example: imu.capture 100 csv",
+    },
+    HelpEntry {
+        names: &["imu.fifo"],
+        summary: "imu.fifo [on|off|read] - FIFO byte count; start/stop buffering; drain and print",
+        detail: "Without an argument shows the FIFO byte count. `on` starts\r\n\
+                 buffering accel+gyro frames in the MPU6050 FIFO, `off` stops,\r\n\
+                 `read` drains and prints them (a full FIFO is reported as\r\n\
+                 overflowed and restarted).\r\n\
+                 Example: imu.fifo read",
+    },
+    HelpEntry {
+        names: &["imu.yaw"],
+        summary: "imu.yaw             - Heading from the integrated Z gyro (drifts over time)",
+        detail: "Heading in degrees from integrating the bias-corrected Z gyro.\r\n\
+                 Nothing corrects it, so use it for relative turns. yaw.reset zeroes it.\r\n\
+                 Example: imu.yaw",
+    },
+    HelpEntry {
+        names: &["imu.rpy"],
+        summary: "imu.rpy             - Roll (side lean) and pitch (forward lean) in degrees",
+        detail: "Roll about X (-180..180) and pitch about Y (-90..90) from the\r\n\
+                 accelerometer, fused with the X/Y gyro using the imu.alpha weight.\r\n\
+                 Updated while Monitoring.\r\n\
+                 Example: imu.rpy",
+    },
+    HelpEntry {
+        names: &["yaw.reset"],
+        summary: "yaw.reset           - Zero the heading",
+        detail: "Makes the current heading 0°.\r\n\
+                 Example: yaw.reset",
+    },
+    HelpEntry {
+        names: &["imu.gyrobias"],
+        summary: "imu.gyrobias        - Gyro bias learned while still",
+        detail: "Gyro bias per axis, learned while the device has been still for 2 s,\r\n\
+                 and how many samples went into it.\r\n\
+                 Example: imu.gyrobias",
+    },
+    HelpEntry {
+        names: &["gyrobias.reset"],
+        summary: "gyrobias.reset      - Forget the gyro bias and learn it again",
+        detail: "Clears the learned gyro bias; it is learned again the next time the\r\n\
+                 device is still.\r\n\
+                 Example: gyrobias.reset",
+    },
+    HelpEntry {
+        names: &["imu2.addr"],
+        summary: "imu2.addr [0x68|0x69] - Show or set the second IMU address",
+        detail: "Address of the second MPU6050 on the same bus, used by imu2.read\r\n\
+                 and joint.angle. Must differ from imu.addr.\r\n\
+                 Example: imu2.addr 0x69",
+    },
+    HelpEntry {
+        names: &["imu2.read"],
+        summary: "imu2.read           - Read second IMU accel data",
+        detail: "One raw accel reading from the second IMU.\r\n\
+                 Example: imu2.read",
+    },
+    HelpEntry {
+        names: &["joint.angle"],
+        summary: "joint.angle         - Angle between the two IMUs",
+        detail: "Angle between the two IMUs' gravity vectors, e.g. across a hinge.\r\n\
+                 Uses raw readings, without calibration offsets.\r\n\
+                 Example: joint.angle",
+    },
+    HelpEntry {
+        names: &["imu.noise"],
+        summary: "imu.noise [n]       - Accel noise floor, keep still (n=200)",
+        detail: "Standard deviation per accel axis in counts over n reads 2 ms apart\r\n\
+                 (2-2000, default 200). Keep the board still while it runs.\r\n\
+                 Example: imu.noise 500",
+    },
+    HelpEntry {
+        names: &["stream.start", "stream.stop"],
+        summary: "stream.start/stop   - Toggle streaming",
+        detail: "stream.start prints a telemetry line per Monitoring sample until\r\n\
+                 stream.stop. See stream.csv, stream.aggregate and stream.crc.\r\n\
+                 Example: stream.start",
+    },
+    HelpEntry {
+        names: &["stream.csv"],
+        summary: "stream.csv          - Stream CSV rows (header line first)",
+        detail: "Like stream.start but as CSV rows, after one header line. Stop with\r\n\
+                 stream.stop.\r\n\
+                 Example: stream.csv",
+    },
+    HelpEntry {
+        names: &["stream.raw"],
+        summary: "stream.raw <on|off> - 4-byte binary samples (see README)",
+        detail: "`on` sends a 4-byte frame per sample: tilt (i16 LE, 0.01°), flags,\r\n\
+                 sequence number. Nothing is echoed; type stream.raw off and Enter\r\n\
+                 to get the CLI back.\r\n\
+                 Example: stream.raw on",
+    },
+    HelpEntry {
+        names: &["stream.aggregate"],
+        summary: "stream.aggregate <last|avg> - Stream latest sample or mean since last line",
+        detail: "`last` streams the latest sample; `avg` the mean of all samples\r\n\
+                 since the previous line.\r\n\
+                 Example: stream.aggregate avg",
+    },
+    HelpEntry {
+        names: &["stream.crc"],
+        summary: "stream.crc <on|off> - Append *XX CRC-8 to streamed lines",
+        detail: "`on` ends each streamed text line with *XX, the CRC-8 of the line\r\n\
+                 in hex, so the host can drop corrupted lines.\r\n\
+                 Example: stream.crc on",
+    },
+    HelpEntry {
+        names: &["debug.freeze"],
+        summary: "debug.freeze <on|off> - Hold telemetry globals for GDB",
+        detail: "`on` stops updating the telemetry statics so GDB reads a stable\r\n\
+                 snapshot; `off` resumes.\r\n\
+                 Example: debug.freeze on",
+    },
+    HelpEntry {
+        names: &["config.dump_rust"],
+        summary: "config.dump_rust    - Print runtime config as Rust defaults",
+        detail: "Prints the current runtime settings (thresholds, IMU address, range\r\n\
+                 and filter, calibration, colors, limits, stream options) as the\r\n\
+                 `#[no_mangle] pub static mut` declarations in src/state.rs, ready\r\n\
+                 to paste over the defaults there.\r\n\
+                 Example: config.dump_rust",
+    },
+    HelpEntry {
+        names: &["log.dump"],
+        summary: "log.dump            - Show stored log entries (oldest first)",
+        detail: "Prints the entries kept in the RAM log buffer, oldest first.\r\n\
+                 Example: log.dump",
+    },
+    HelpEntry {
+        names: &["log.filter"],
+        summary: "log.filter <level>  - Store only off|error|warn|info|debug|trace",
+        detail: "Lowest level stored in the log buffer: off, error, warn, info,\r\n\
+                 debug or trace.\r\n\
+                 Example: log.filter warn",
+    },
+    HelpEntry {
+        names: &["log.clear"],
+        summary: "log.clear           - Empty the log buffer",
+        detail: "Drops every stored log entry.\r\n\
+                 Example: log.clear",
+    },
+    HelpEntry {
+        names: &["debug.profile"],
+        summary: "debug.profile <on|off> - Print execution time of each command",
+        detail: "`on` prints (took N us) after every command.\r\n\
+                 Example: debug.profile on",
+    },
+    HelpEntry {
+        names: &["cpu.wfi"],
+        summary: "cpu.wfi             - Park the CPU until the next timer tick",
+        detail: "Executes WFI: the core stops until the wake timer interrupt, at\r\n\
+                 most one tick. Not the device Sleep state.\r\n\
+                 Example: cpu.wfi",
+    },
+    HelpEntry {
+        names: &["sys.hang"],
+        summary: "sys.hang            - Spin forever; the watchdog resets the chip after ~3 s",
+        detail: "Stops feeding the watchdog to demonstrate a watchdog reset.\r\n\
+                 Example: sys.hang",
+    },
+    HelpEntry {
+        names: &["sys.reset"],
+        summary: "sys.reset           - Restart the chip (software reset)",
+        detail: "Software reset; settings not saved to flash are lost.\r\n\
+                 Example: sys.reset",
+    },
+    HelpEntry {
+        names: &["temp.limit"],
+        summary: "temp.limit <c> [warn|sleep] - Over-temperature limit and action",
+        detail: "MPU die temperature limit in °C, checked once a second. `warn`\r\n\
+                 shows magenta, `sleep` also enters Sleep; without an action the\r\n\
+                 current one is kept.\r\n\
+                 Example: temp.limit 60 sleep",
+    },
+    HelpEntry {
+        names: &["demo"],
+        summary: "demo                - Guided feature showcase (any key stops)",
+        detail: "Steps through the main features, 3 s each. Any key stops it.\r\n\
+                 Example: demo",
+    },
+    HelpEntry {
+        names: &["ping"],
+        summary: "ping                - Reply pong <uptime_ms>",
+        detail: "Replies pong and the uptime in ms, to check the link is alive.\r\n\
+                 Example: ping",
+    },
+    HelpEntry {
+        names: &["echo"],
+        summary: "echo <text>         - Echo text back",
+        detail: "Prints the rest of the line back unchanged.\r\n\
+                 Example: echo hello",
+    },
+    HelpEntry {
+        names: &["term.echo"],
+        summary: "term.echo <on|off>  - Echo typed characters (off if the terminal echoes)",
+        detail: "`off` stops echoing typed characters, for terminals with local echo.\r\n\
+                 Example: term.echo off",
+    },
+    HelpEntry {
+        names: &[],
+        summary: "<cmd>; <cmd>; ...   - Run in order, stop at first error",
+        detail: "",
+    },
+    HelpEntry {
+        names: &["macro.record", "macro.stop"],
+        summary: "macro.record <name> - Record the following lines (macro.stop to end)",
+        detail: "Records the following command lines under <name> (max 12\r\n\
+                 characters) until macro.stop. Lines still run as you type them;\r\n\
+                 only the ones that succeed are recorded.\r\n\
+                 Example: macro.record setup",
+    },
+    HelpEntry {
+        names: &["macro.run"],
+        summary: "macro.run <name>    - Replay a macro, stop at first error",
+        detail: "Runs the recorded lines in order, stopping at the first error.\r\n\
+                 Example: macro.run setup",
+    },
+    HelpEntry {
+        names: &["macro.list"],
+        summary: "macro.list          - Show recorded macros",
+        detail: "Lists the recorded macros and their lines.\r\n\
+                 Example: macro.list",
+    },
+];
+
+/// Help entry for a command name
+pub fn find(name: &str) -> Option<&'static HelpEntry> {
+    COMMANDS.iter().find(|e| e.names.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_by_any_name() {
+        assert!(find("neo.color").unwrap().detail.contains("neo.color 255 0 0"));
+        assert!(core::ptr::eq(find("macro.stop").unwrap(), find("macro.record").unwrap()));
+        assert!(find("neo.colour").is_none());
+        assert!(find("").is_none());
+    }
+
+    #[test]
+    fn test_every_command_has_one_entry_with_an_example() {
+        for (i, entry) in COMMANDS.iter().enumerate() {
+            if let Some(first) = entry.names.first() {
+                assert!(entry.summary.starts_with(first), "{}", first);
+            }
+            for name in entry.names {
+                assert!(entry.detail.contains("Example: "), "{}", name);
+                assert!(
+                    COMMANDS[i + 1..].iter().all(|e| !e.names.contains(name)),
+                    "{} listed twice",
+                    name
+                );
+            }
+        }
+    }
+}
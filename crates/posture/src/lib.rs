@@ -0,0 +1,24 @@
+//! Hardware-independent parts of the lesson 05 posture monitor
+//!
+//! Tilt math and filters, alert bookkeeping, the flash record format, the
+//! CLI help table and the other pieces that don't touch a peripheral. The
+//! lesson re-exports the modules, so its code keeps using `crate::tilt` etc.;
+//! keeping them here lets `cargo test` run them on the host.
+
+#![no_std]
+
+pub mod beep;
+pub mod crc;
+pub mod encoder;
+pub mod fade;
+pub mod fall;
+pub mod filter;
+pub mod gesture;
+pub mod help;
+pub mod logbuf;
+pub mod macros;
+pub mod persist;
+pub mod ratelimit;
+pub mod score;
+pub mod stats;
+pub mod tilt;
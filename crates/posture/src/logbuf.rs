@@ -0,0 +1,157 @@
+//! Log ring buffer
+//!
+//! Replaces the plain esp-println logger: records are still printed live
+//! (through the function the firmware passes to `init`), and the most recent
+//! ones are also kept in RAM with their level and a short
+//! source tag so `log.dump` can show what led up to an alert or fault.
+//!
+//! The tag comes from the `[TAG]` prefix the firmware already puts on its
+//! messages (`info!("[STATE] ...")` is stored as `[INFO][state] ...`), falling
+//! back to the record target. What gets stored (`log.filter`) is independent
+//! of what is printed live.
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use heapless::{Deque, String};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Entries kept (oldest are dropped first)
+pub const LOG_CAPACITY: usize = 32;
+/// Message bytes kept per entry (longer messages are truncated)
+pub const LOG_MSG_LEN: usize = 80;
+pub const LOG_TAG_LEN: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: Level,
+    pub tag: String<LOG_TAG_LEN>,
+    pub msg: String<LOG_MSG_LEN>,
+}
+
+static ENTRIES: Mutex<RefCell<Deque<LogEntry, LOG_CAPACITY>>> = Mutex::new(RefCell::new(Deque::new()));
+
+static mut LIVE_LEVEL: LevelFilter = LevelFilter::Info;
+static mut STORE_LEVEL: LevelFilter = LevelFilter::Info;
+static mut LIVE_PRINT: Option<fn(&Record)> = None;
+
+static LOGGER: RingLogger = RingLogger;
+
+struct RingLogger;
+
+impl Log for RingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= live_level() || metadata.level() <= store_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let level = record.level();
+        if level <= live_level() {
+            if let Some(print) = unsafe { LIVE_PRINT } {
+                print(record);
+            }
+        }
+        if level <= store_level() {
+            let mut text: String<LOG_MSG_LEN> = String::new();
+            write!(text, "{}", record.args()).ok();
+            let (tag, msg) = split_tag(&text).unwrap_or((record.target(), text.as_str()));
+
+            let mut entry = LogEntry {
+                level,
+                tag: String::new(),
+                msg: String::new(),
+            };
+            for c in tag.chars().take(LOG_TAG_LEN) {
+                entry.tag.push(c.to_ascii_lowercase()).ok();
+            }
+            entry.msg.push_str(msg.trim_end()).ok();
+
+            critical_section::with(|cs| {
+                let mut entries = ENTRIES.borrow_ref_mut(cs);
+                if entries.is_full() {
+                    entries.pop_front();
+                }
+                entries.push_back(entry).ok();
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger, passing records up to `live` to `print` (stores `Info`
+/// and up by default)
+pub fn init(live: LevelFilter, print: fn(&Record)) {
+    unsafe {
+        LIVE_PRINT = Some(print);
+    }
+    log::set_logger(&LOGGER).ok();
+    set_live_level(live);
+}
+
+pub fn live_level() -> LevelFilter {
+    unsafe { LIVE_LEVEL }
+}
+
+pub fn store_level() -> LevelFilter {
+    unsafe { STORE_LEVEL }
+}
+
+pub fn set_live_level(level: LevelFilter) {
+    unsafe {
+        LIVE_LEVEL = level;
+    }
+    log::set_max_level(level.max(store_level()));
+}
+
+/// Set which levels are kept in the buffer (`log.filter`)
+pub fn set_store_level(level: LevelFilter) {
+    unsafe {
+        STORE_LEVEL = level;
+    }
+    log::set_max_level(level.max(live_level()));
+}
+
+/// Visit stored entries, oldest first
+///
+/// Runs inside a critical section; keep `f` short (a UART write is fine).
+pub fn for_each(mut f: impl FnMut(&LogEntry)) {
+    critical_section::with(|cs| {
+        for entry in ENTRIES.borrow_ref(cs).iter() {
+            f(entry);
+        }
+    });
+}
+
+pub fn len() -> usize {
+    critical_section::with(|cs| ENTRIES.borrow_ref(cs).len())
+}
+
+pub fn clear() {
+    critical_section::with(|cs| ENTRIES.borrow_ref_mut(cs).clear());
+}
+
+/// Split a leading `[TAG] ` off a message
+fn split_tag(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let tag = &rest[..end];
+    if tag.is_empty() || tag.len() > LOG_TAG_LEN || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((tag, rest[end + 1..].trim_start()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_tag() {
+        assert_eq!(split_tag("[STATE] Sleep → Monitoring"), Some(("STATE", "Sleep → Monitoring")));
+        assert_eq!(split_tag("[ALERT] Warning (tilt=31.0°)"), Some(("ALERT", "Warning (tilt=31.0°)")));
+        assert_eq!(split_tag("no tag here"), None);
+        assert_eq!(split_tag("[dev=PostureMonitor state=Sleep]"), None);
+        assert_eq!(split_tag("[] empty"), None);
+    }
+}
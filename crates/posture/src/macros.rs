@@ -0,0 +1,174 @@
+//! Command macros recorded from the CLI
+//!
+//! `macro.record <name>` starts capturing entered lines, `macro.stop` ends
+//! it, and `macro.run <name>` replays them. Everything lives in RAM and is
+//! lost on reset; sizes are fixed so the store is a plain static.
+
+use heapless::{String, Vec};
+
+/// Macros kept at once
+pub const MACRO_SLOTS: usize = 4;
+/// Lines per macro (each line may itself be a `;` chain)
+pub const MACRO_LINES: usize = 8;
+pub const MACRO_NAME_LEN: usize = 12;
+pub const MACRO_LINE_LEN: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String<MACRO_NAME_LEN>,
+    pub lines: Vec<String<MACRO_LINE_LEN>, MACRO_LINES>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroError {
+    NameTooLong,
+    /// All slots are used by other macros
+    Full,
+    AlreadyRecording,
+    NotRecording,
+    TooManyLines,
+    LineTooLong,
+}
+
+pub struct MacroStore {
+    macros: Vec<Macro, MACRO_SLOTS>,
+    /// Index of the macro being recorded
+    recording: Option<usize>,
+}
+
+impl MacroStore {
+    pub const fn new() -> Self {
+        Self {
+            macros: Vec::new(),
+            recording: None,
+        }
+    }
+
+    /// Start recording `name`, replacing an existing macro of that name
+    pub fn start(&mut self, name: &str) -> Result<(), MacroError> {
+        if self.recording.is_some() {
+            return Err(MacroError::AlreadyRecording);
+        }
+        let name = String::try_from(name).map_err(|_| MacroError::NameTooLong)?;
+        self.macros.retain(|m| m.name != name);
+        self.macros
+            .push(Macro { name, lines: Vec::new() })
+            .map_err(|_| MacroError::Full)?;
+        self.recording = Some(self.macros.len() - 1);
+        Ok(())
+    }
+
+    /// Append a line to the macro being recorded
+    pub fn record(&mut self, line: &str) -> Result<(), MacroError> {
+        let index = self.recording.ok_or(MacroError::NotRecording)?;
+        let line = String::try_from(line).map_err(|_| MacroError::LineTooLong)?;
+        self.macros[index]
+            .lines
+            .push(line)
+            .map_err(|_| MacroError::TooManyLines)
+    }
+
+    /// Finish recording; returns the number of lines kept. An empty macro
+    /// is discarded rather than left taking a slot.
+    pub fn stop(&mut self) -> Result<usize, MacroError> {
+        let index = self.recording.take().ok_or(MacroError::NotRecording)?;
+        let count = self.macros[index].lines.len();
+        if count == 0 {
+            self.macros.remove(index);
+        }
+        Ok(count)
+    }
+
+    /// Name of the macro being recorded
+    pub fn recording(&self) -> Option<&str> {
+        self.recording.map(|i| self.macros[i].name.as_str())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Macro> {
+        self.macros.iter()
+    }
+}
+
+impl Default for MacroStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// True when any `;` segment of `line` runs a `macro.*` command; such lines
+/// are never recorded (a macro replaying itself would recurse)
+pub fn runs_macro_command(line: &str) -> bool {
+    line.split(';')
+        .any(|segment| segment.split_whitespace().next().is_some_and(|cmd| cmd.starts_with("macro.")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replace() {
+        let mut store = MacroStore::new();
+        store.start("setup").unwrap();
+        assert_eq!(store.recording(), Some("setup"));
+        store.record("posture.filter kalman").unwrap();
+        store.record("stream.aggregate on; device.start").unwrap();
+        assert_eq!(store.stop(), Ok(2));
+        assert_eq!(store.recording(), None);
+        assert_eq!(store.get("setup").unwrap().lines[1], "stream.aggregate on; device.start");
+
+        // Same name again replaces the old macro instead of using a new slot
+        store.start("setup").unwrap();
+        store.record("device.sleep").unwrap();
+        store.stop().unwrap();
+        assert_eq!(store.iter().count(), 1);
+        assert_eq!(store.get("setup").unwrap().lines.len(), 1);
+    }
+
+    #[test]
+    fn test_runs_macro_command() {
+        assert!(runs_macro_command("macro.stop"));
+        assert!(runs_macro_command("ping; macro.run setup"));
+        assert!(runs_macro_command("  macro.list  "));
+        // Only the command word counts, not arguments that mention macro.
+        assert!(!runs_macro_command("help macro.run"));
+        assert!(!runs_macro_command("neo.color 255 0 0; device.status"));
+        assert!(!runs_macro_command(""));
+    }
+
+    #[test]
+    fn test_empty_macro_is_dropped() {
+        let mut store = MacroStore::new();
+        store.start("nothing").unwrap();
+        assert_eq!(store.stop(), Ok(0));
+        assert!(store.get("nothing").is_none());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut store = MacroStore::new();
+        assert_eq!(store.start("a_very_long_name"), Err(MacroError::NameTooLong));
+        assert_eq!(store.record("ping"), Err(MacroError::NotRecording));
+        assert_eq!(store.stop(), Err(MacroError::NotRecording));
+
+        store.start("m").unwrap();
+        assert_eq!(store.start("n"), Err(MacroError::AlreadyRecording));
+        for _ in 0..MACRO_LINES {
+            store.record("ping").unwrap();
+        }
+        assert_eq!(store.record("ping"), Err(MacroError::TooManyLines));
+        store.stop().unwrap();
+
+        for name in ["b", "c", "d"] {
+            store.start(name).unwrap();
+            store.record("ping").unwrap();
+            store.stop().unwrap();
+        }
+        assert_eq!(store.start("e"), Err(MacroError::Full));
+        assert_eq!(store.recording(), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Flash-persisted records
+//!
+//! Each record key owns a whole 4 KiB sector in the `nvs` partition of the
+//! default partition table (unused: there is no ESP-IDF NVS here), so
+//! writing one record never erases another.
+//!
+//! On flash a record is a 6-byte header followed by the payload:
+//!
+//! | byte | content                                   |
+//! |------|-------------------------------------------|
+//! | 0-1  | magic `0xC6A5`, little-endian             |
+//! | 2    | key                                       |
+//! | 3    | payload length                            |
+//! | 4-5  | Fletcher-16 of bytes 2-3 and the payload  |
+//!
+//! Erased (0xFF) or foreign data fails the check and reads as "no record".
+
+use embedded_storage::{ReadStorage, Storage};
+
+/// Start of the `nvs` partition (0x9000, 24 KiB)
+pub const REGION_BASE: u32 = 0x9000;
+pub const SECTOR_SIZE: u32 = 4096;
+pub const HEADER_LEN: usize = 6;
+/// Largest payload a record can hold
+pub const MAX_PAYLOAD: usize = 64;
+
+const MAGIC: u16 = 0xC6A5;
+
+/// Layout version of the `Key::Calibration` payload
+pub const CAL_VERSION: u8 = 1;
+pub const CAL_PAYLOAD_LEN: usize = 8;
+
+/// Layout version of the `Key::Colors` payload
+pub const COLORS_VERSION: u8 = 1;
+pub const COLORS_PAYLOAD_LEN: usize = 10;
+
+/// Record namespaces; each one is a separate sector
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Key {
+    BootCount = 0,
+    /// Accel calibration offsets, see `encode_calibration`
+    Calibration = 1,
+    /// `cal.mount` reference vector, same payload layout as `Calibration`
+    Mount = 2,
+    /// Alert level colors (`posture.color`), see `encode_colors`
+    Colors = 3,
+}
+
+/// Why `store` failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// Payload longer than `MAX_PAYLOAD`
+    PayloadTooLong,
+    /// The flash write itself failed
+    Flash,
+}
+
+impl Key {
+    /// Flash offset of this record's sector
+    pub fn addr(self) -> u32 {
+        REGION_BASE + self as u32 * SECTOR_SIZE
+    }
+}
+
+fn fletcher16<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u16 {
+    let (mut a, mut b) = (0u16, 0u16);
+    for &byte in bytes {
+        a = (a + byte as u16) % 255;
+        b = (b + a) % 255;
+    }
+    b << 8 | a
+}
+
+/// Serialize `payload` under `key` into `buf`; returns the bytes used, or
+/// `None` if the payload is too long or `buf` too small
+pub fn encode(key: Key, payload: &[u8], buf: &mut [u8]) -> Option<usize> {
+    let len = HEADER_LEN + payload.len();
+    if payload.len() > MAX_PAYLOAD || buf.len() < len {
+        return None;
+    }
+    buf[0..2].copy_from_slice(&MAGIC.to_le_bytes());
+    buf[2] = key as u8;
+    buf[3] = payload.len() as u8;
+    buf[HEADER_LEN..len].copy_from_slice(payload);
+    let sum = fletcher16(buf[2..4].iter().chain(payload));
+    buf[4..6].copy_from_slice(&sum.to_le_bytes());
+    Some(len)
+}
+
+/// Validate a record read back from flash and return its payload
+pub fn decode(key: Key, buf: &[u8]) -> Option<&[u8]> {
+    if buf.len() < HEADER_LEN || u16::from_le_bytes([buf[0], buf[1]]) != MAGIC || buf[2] != key as u8 {
+        return None;
+    }
+    let len = buf[3] as usize;
+    if len > MAX_PAYLOAD || buf.len() < HEADER_LEN + len {
+        return None;
+    }
+    let payload = &buf[HEADER_LEN..HEADER_LEN + len];
+    if fletcher16(buf[2..4].iter().chain(payload)) != u16::from_le_bytes([buf[4], buf[5]]) {
+        return None;
+    }
+    Some(payload)
+}
+
+/// Read the record for `key` into `buf` (at least `HEADER_LEN + MAX_PAYLOAD`
+/// bytes); returns the payload, or `None` if there is no valid record
+pub fn load<'a, S: ReadStorage>(flash: &mut S, key: Key, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    let len = buf.len().min(HEADER_LEN + MAX_PAYLOAD);
+    flash.read(key.addr(), &mut buf[..len]).ok()?;
+    decode(key, &buf[..len])
+}
+
+/// Write the record for `key`, replacing the previous one
+pub fn store<S: Storage>(flash: &mut S, key: Key, payload: &[u8]) -> Result<(), Error> {
+    let mut buf = [0u8; HEADER_LEN + MAX_PAYLOAD];
+    let len = encode(key, payload, &mut buf).ok_or(Error::PayloadTooLong)?;
+    flash.write(key.addr(), &buf[..len]).map_err(|_| Error::Flash)
+}
+
+/// Calibration payload: version, the accel range (AFS_SEL) the offsets were
+/// taken at, then X/Y/Z offsets in raw counts as little-endian i16
+pub fn encode_calibration(range: u8, offsets: [i16; 3]) -> [u8; CAL_PAYLOAD_LEN] {
+    let mut payload = [CAL_VERSION, range, 0, 0, 0, 0, 0, 0];
+    for (chunk, offset) in payload[2..].chunks_exact_mut(2).zip(offsets) {
+        chunk.copy_from_slice(&offset.to_le_bytes());
+    }
+    payload
+}
+
+/// Inverse of `encode_calibration`; None for another version or length
+pub fn decode_calibration(payload: &[u8]) -> Option<(u8, [i16; 3])> {
+    if payload.len() != CAL_PAYLOAD_LEN || payload[0] != CAL_VERSION {
+        return None;
+    }
+    let mut offsets = [0i16; 3];
+    for (offset, chunk) in offsets.iter_mut().zip(payload[2..].chunks_exact(2)) {
+        *offset = i16::from_le_bytes([chunk[0], chunk[1]]);
+    }
+    Some((payload[1], offsets))
+}
+
+/// Colors payload: version, then R, G, B for Normal, Warning and Alert
+pub fn encode_colors(colors: [[u8; 3]; 3]) -> [u8; COLORS_PAYLOAD_LEN] {
+    let mut payload = [0u8; COLORS_PAYLOAD_LEN];
+    payload[0] = COLORS_VERSION;
+    for (chunk, rgb) in payload[1..].chunks_exact_mut(3).zip(colors) {
+        chunk.copy_from_slice(&rgb);
+    }
+    payload
+}
+
+/// Inverse of `encode_colors`; None for another version or length
+pub fn decode_colors(payload: &[u8]) -> Option<[[u8; 3]; 3]> {
+    if payload.len() != COLORS_PAYLOAD_LEN || payload[0] != COLORS_VERSION {
+        return None;
+    }
+    let mut colors = [[0u8; 3]; 3];
+    for (rgb, chunk) in colors.iter_mut().zip(payload[1..].chunks_exact(3)) {
+        rgb.copy_from_slice(chunk);
+    }
+    Some(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut buf = [0u8; HEADER_LEN + MAX_PAYLOAD];
+        let len = encode(Key::BootCount, &42u32.to_le_bytes(), &mut buf).unwrap();
+        assert_eq!(len, HEADER_LEN + 4);
+        assert_eq!(decode(Key::BootCount, &buf), Some(&42u32.to_le_bytes()[..]));
+    }
+
+    #[test]
+    fn test_rejects_blank_and_corrupt() {
+        assert_eq!(decode(Key::BootCount, &[0xFF; 16]), None);
+
+        let mut buf = [0u8; 16];
+        encode(Key::BootCount, &7u32.to_le_bytes(), &mut buf).unwrap();
+        buf[HEADER_LEN] ^= 0x01;
+        assert_eq!(decode(Key::BootCount, &buf), None);
+    }
+
+    #[test]
+    fn test_calibration_payload() {
+        let payload = encode_calibration(0, [-120, 45, -1030]);
+        assert_eq!(decode_calibration(&payload), Some((0, [-120, 45, -1030])));
+
+        let mut future = payload;
+        future[0] = CAL_VERSION + 1;
+        assert_eq!(decode_calibration(&future), None);
+        assert_eq!(decode_calibration(&payload[..6]), None);
+    }
+
+    #[test]
+    fn test_colors_payload() {
+        let colors = [[0, 0, 40], [40, 20, 0], [255, 0, 255]];
+        assert_eq!(decode_colors(&encode_colors(colors)), Some(colors));
+        assert_eq!(decode_colors(&encode_colors(colors)[..9]), None);
+        assert_eq!(decode_colors(&[0xFF; COLORS_PAYLOAD_LEN]), None);
+    }
+
+    /// One sector of RAM at `Key::BootCount`, or a flash that fails every write
+    struct RamFlash {
+        data: [u8; 128],
+        broken: bool,
+    }
+
+    impl ReadStorage for RamFlash {
+        type Error = ();
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), ()> {
+            let start = (offset - Key::BootCount.addr()) as usize;
+            bytes.copy_from_slice(&self.data[start..start + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.data.len()
+        }
+    }
+
+    impl Storage for RamFlash {
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), ()> {
+            if self.broken {
+                return Err(());
+            }
+            let start = (offset - Key::BootCount.addr()) as usize;
+            self.data[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_store_errors() {
+        let mut flash = RamFlash { data: [0xFF; 128], broken: false };
+        assert_eq!(store(&mut flash, Key::BootCount, &[0; MAX_PAYLOAD + 1]), Err(Error::PayloadTooLong));
+        assert_eq!(store(&mut flash, Key::BootCount, &5u32.to_le_bytes()), Ok(()));
+        let mut buf = [0u8; HEADER_LEN + MAX_PAYLOAD];
+        assert_eq!(load(&mut flash, Key::BootCount, &mut buf), Some(&5u32.to_le_bytes()[..]));
+
+        flash.broken = true;
+        assert_eq!(store(&mut flash, Key::BootCount, &6u32.to_le_bytes()), Err(Error::Flash));
+    }
+
+    #[test]
+    fn test_payload_limits() {
+        let mut buf = [0u8; 8];
+        assert_eq!(encode(Key::BootCount, &[0; 4], &mut buf), None); // buf too small
+        let mut buf = [0u8; 128];
+        assert_eq!(encode(Key::BootCount, &[0; MAX_PAYLOAD + 1], &mut buf), None);
+    }
+}
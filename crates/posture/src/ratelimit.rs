@@ -0,0 +1,109 @@
+//! Rate limiting for log lines that can fire in bursts
+//!
+//! An oscillating condition (tilt hovering at a threshold, a state bouncing
+//! back and forth) can log many lines a second. A `RateLimiter` per message
+//! lets the first line through, then at most one per `min_interval_ms`; the
+//! ones in between are counted so the next line can say how many were
+//! dropped (`Suppressed`).
+
+use core::fmt;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimiter {
+    min_interval_ms: u32,
+    /// Time of the last allowed line (None until the first one)
+    last_ms: Option<u32>,
+    suppressed: u32,
+}
+
+impl RateLimiter {
+    pub const fn new(min_interval_ms: u32) -> Self {
+        Self {
+            min_interval_ms,
+            last_ms: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Whether a line at `now_ms` may be logged: the first one always is,
+    /// later ones once `min_interval_ms` has passed since the last allowed one
+    pub fn allow(&mut self, now_ms: u32) -> bool {
+        match self.last_ms {
+            Some(last) if now_ms.wrapping_sub(last) < self.min_interval_ms => {
+                self.suppressed = self.suppressed.saturating_add(1);
+                false
+            }
+            _ => {
+                self.last_ms = Some(now_ms);
+                true
+            }
+        }
+    }
+
+    /// Lines suppressed since the last call; call it when a line is allowed
+    pub fn take_suppressed(&mut self) -> u32 {
+        core::mem::take(&mut self.suppressed)
+    }
+
+    /// `allow` plus the suffix for the line: None to drop it, otherwise how
+    /// many repeats were dropped since the last one logged
+    pub fn check(&mut self, now_ms: u32) -> Option<Suppressed> {
+        self.allow(now_ms).then(|| Suppressed(self.take_suppressed()))
+    }
+}
+
+/// Log line suffix: nothing, or " (N repeats suppressed)"
+pub struct Suppressed(pub u32);
+
+impl fmt::Display for Suppressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            Ok(())
+        } else {
+            write!(f, " ({} repeats suppressed)", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_line_passes_then_repeats_are_limited() {
+        let mut limit = RateLimiter::new(1000);
+        assert!(limit.allow(5000));
+        // Oscillating every 100 ms: everything within the second is dropped
+        for t in (5100..6000).step_by(100) {
+            assert!(!limit.allow(t));
+        }
+        assert!(limit.allow(6000));
+        assert_eq!(limit.take_suppressed(), 9);
+        assert_eq!(limit.take_suppressed(), 0);
+        // Across the u32 wrap of the uptime counter
+        let mut limit = RateLimiter::new(1000);
+        assert!(limit.allow(u32::MAX - 100));
+        assert!(!limit.allow(500));
+        assert!(limit.allow(900));
+    }
+
+    #[test]
+    fn test_check_reports_the_drops_with_the_next_line() {
+        let mut limit = RateLimiter::new(1000);
+        assert_eq!(limit.check(0).map(|s| s.0), Some(0));
+        assert!(limit.check(10).is_none());
+        assert!(limit.check(20).is_none());
+        assert_eq!(limit.check(1000).map(|s| s.0), Some(2));
+    }
+
+    #[test]
+    fn test_suppressed_suffix() {
+        use core::fmt::Write;
+        let mut line: heapless::String<32> = heapless::String::new();
+        write!(line, "x{}", Suppressed(0)).unwrap();
+        assert_eq!(line, "x");
+        line.clear();
+        write!(line, "x{}", Suppressed(3)).unwrap();
+        assert_eq!(line, "x (3 repeats suppressed)");
+    }
+}
@@ -0,0 +1,101 @@
+//! Rolling posture score: share of recent time spent at each alert level
+//!
+//! `ScoreWindow` is a ring buffer of the last `N` alert levels (0 = Normal,
+//! 1 = Warning, 2 = Alert) with a running count per level, so a push and a
+//! percentage are O(1). Until it fills, percentages cover the samples it has.
+
+pub const LEVELS: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreWindow<const N: usize> {
+    levels: [u8; N],
+    next: usize,
+    len: usize,
+    counts: [u32; LEVELS],
+}
+
+impl<const N: usize> ScoreWindow<N> {
+    pub const fn new() -> Self {
+        Self {
+            levels: [0; N],
+            next: 0,
+            len: 0,
+            counts: [0; LEVELS],
+        }
+    }
+
+    /// Record one sample's level, dropping the oldest once full
+    pub fn push(&mut self, level: u8) {
+        let level = level.min(LEVELS as u8 - 1);
+        if self.len == N {
+            self.counts[self.levels[self.next] as usize] -= 1;
+        } else {
+            self.len += 1;
+        }
+        self.levels[self.next] = level;
+        self.counts[level as usize] += 1;
+        self.next = (self.next + 1) % N;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Rounded percentage of the held samples at `level`; None while empty
+    pub fn percent(&self, level: u8) -> Option<u8> {
+        let count = *self.counts.get(level as usize)?;
+        if self.len == 0 {
+            return None;
+        }
+        let len = self.len as u32;
+        Some(((count * 100 + len / 2) / len) as u8)
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+        self.counts = [0; LEVELS];
+    }
+}
+
+impl<const N: usize> Default for ScoreWindow<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_window_uses_samples_so_far() {
+        let mut window: ScoreWindow<10> = ScoreWindow::new();
+        assert_eq!(window.percent(0), None);
+        window.push(0);
+        window.push(0);
+        window.push(0);
+        window.push(1);
+        assert_eq!(window.len(), 4);
+        assert_eq!(window.percent(0), Some(75));
+        assert_eq!(window.percent(1), Some(25));
+        assert_eq!(window.percent(2), Some(0));
+    }
+
+    #[test]
+    fn test_full_window_drops_oldest() {
+        let mut window: ScoreWindow<4> = ScoreWindow::new();
+        for level in [2, 2, 0, 0, 0, 0] {
+            window.push(level);
+        }
+        assert_eq!(window.len(), 4);
+        assert_eq!(window.percent(0), Some(100));
+        assert_eq!(window.percent(2), Some(0));
+        window.clear();
+        assert!(window.is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+//! Running statistics for sensor characterization
+//!
+//! Allocation-free, single-pass (no sample buffer needed).
+
+use crate::tilt::isqrt;
+
+/// Running mean/variance using Welford's algorithm
+///
+/// Numerically stable for long runs, unlike the naive sum / sum-of-squares.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Welford {
+    count: u32,
+    mean: f32,
+    m2: f32,
+}
+
+impl Welford {
+    pub const fn new() -> Self {
+        Self { count: 0, mean: 0.0, m2: 0.0 }
+    }
+
+    /// Add one sample
+    pub fn update(&mut self, x: f32) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    /// Population variance (0 until two samples have been seen)
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / self.count as f32
+        }
+    }
+
+    /// Standard deviation to 0.01 resolution
+    ///
+    /// Uses the integer sqrt so it also works in the no-float (no libm) build.
+    pub fn std_dev(&self) -> f32 {
+        isqrt((self.variance() * 10_000.0) as u64) as f32 / 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_and_single_sample() {
+        let mut w = Welford::new();
+        assert_eq!(w.count(), 0);
+        assert_eq!(w.variance(), 0.0);
+        w.update(42.0);
+        assert_eq!(w.mean(), 42.0);
+        assert_eq!(w.variance(), 0.0);
+    }
+
+    #[test]
+    fn test_known_distribution() {
+        // Classic example: mean 5, population std dev 2
+        let mut w = Welford::new();
+        for x in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            w.update(x);
+        }
+        assert_eq!(w.count(), 8);
+        assert!((w.mean() - 5.0).abs() < 1e-5);
+        assert!((w.variance() - 4.0).abs() < 1e-4);
+        assert!((w.std_dev() - 2.0).abs() < 0.011);
+    }
+
+    #[test]
+    fn test_large_offset_is_stable() {
+        // Accel Z sits near 16384 counts; noise must not be swamped by the offset
+        let mut w = Welford::new();
+        for i in 0..1000 {
+            w.update(16384.0 + if i % 2 == 0 { 3.0 } else { -3.0 });
+        }
+        assert!((w.std_dev() - 3.0).abs() < 0.05);
+    }
+}
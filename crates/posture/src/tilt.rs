@@ -0,0 +1,327 @@
+//! Tilt angle math
+//!
+//! Two implementations of `atan2(sqrt(x² + y²), z)` in degrees:
+//! - `tilt_angle_float`: libm `sqrtf`/`atan2f` (default, `float` feature)
+//! - `tilt_angle_fixed`: integer sqrt + CORDIC, no libm and no soft-float trig
+//!
+//! `roll_pitch` splits the tilt into side lean and forward lean, with the
+//! same two implementations. `tilt_angle` and `roll_pitch` pick one at
+//! compile time. Build with
+//! `cargo build --release --no-default-features` for the fixed-point variant.
+
+/// atan(2^-i) in millidegrees, i = 0..15
+const CORDIC_ATAN_MDEG: [i32; 16] = [
+    45000, 26565, 14036, 7125, 3576, 1790, 895, 448, 224, 112, 56, 28, 14, 7, 3, 2,
+];
+
+/// Input pre-scale so the low CORDIC iterations keep their precision
+/// (inputs are normalized below 2^17 first, so << 10 stays well inside i32).
+const CORDIC_PRESCALE: u32 = 10;
+const CORDIC_MAX_INPUT: i32 = 1 << 17;
+
+/// Tilt from vertical in degrees (0° = Z axis pointing up, 180° = upside down)
+///
+/// Inputs are calibrated accelerometer counts (any consistent scale).
+#[cfg(feature = "float")]
+pub fn tilt_angle(ax: i32, ay: i32, az: i32) -> f32 {
+    tilt_angle_float(ax, ay, az)
+}
+
+/// Tilt from vertical in degrees (0° = Z axis pointing up, 180° = upside down)
+///
+/// Inputs are calibrated accelerometer counts (any consistent scale).
+#[cfg(not(feature = "float"))]
+pub fn tilt_angle(ax: i32, ay: i32, az: i32) -> f32 {
+    tilt_angle_fixed(ax, ay, az)
+}
+
+/// Floating-point tilt using libm
+#[cfg(feature = "float")]
+pub fn tilt_angle_float(ax: i32, ay: i32, az: i32) -> f32 {
+    let (ax, ay, az) = (ax as f32, ay as f32, az as f32);
+    let xy_magnitude = libm::sqrtf(ax * ax + ay * ay);
+    libm::atan2f(xy_magnitude, az) * 180.0 / core::f32::consts::PI
+}
+
+/// Fixed-point tilt using integer sqrt and CORDIC
+pub fn tilt_angle_fixed(ax: i32, ay: i32, az: i32) -> f32 {
+    let xy_sq = (ax as i64 * ax as i64 + ay as i64 * ay as i64) as u64;
+    let xy_magnitude = isqrt(xy_sq) as i32;
+    atan2_mdeg(xy_magnitude, az) as f32 / 1000.0
+}
+
+/// Angle between two accelerometer vectors in degrees (0..=180)
+///
+/// `atan2(|a × b|, a · b)`, which stays accurate near 0° where `acos` doesn't.
+#[cfg(feature = "float")]
+pub fn angle_between(a: [i32; 3], b: [i32; 3]) -> f32 {
+    angle_between_float(a, b)
+}
+
+/// Angle between two accelerometer vectors in degrees (0..=180)
+///
+/// `atan2(|a × b|, a · b)`, which stays accurate near 0° where `acos` doesn't.
+#[cfg(not(feature = "float"))]
+pub fn angle_between(a: [i32; 3], b: [i32; 3]) -> f32 {
+    angle_between_fixed(a, b)
+}
+
+/// Floating-point vector angle using libm
+#[cfg(feature = "float")]
+pub fn angle_between_float(a: [i32; 3], b: [i32; 3]) -> f32 {
+    let a = [a[0] as f32, a[1] as f32, a[2] as f32];
+    let b = [b[0] as f32, b[1] as f32, b[2] as f32];
+    let cross = [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ];
+    let cross_magnitude = libm::sqrtf(cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]);
+    let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    libm::atan2f(cross_magnitude, dot) * 180.0 / core::f32::consts::PI
+}
+
+/// Fixed-point vector angle using integer sqrt and CORDIC
+pub fn angle_between_fixed(a: [i32; 3], b: [i32; 3]) -> f32 {
+    // Drop 4 LSBs so the cross product squares fit in u64
+    let a = [(a[0] >> 4) as i64, (a[1] >> 4) as i64, (a[2] >> 4) as i64];
+    let b = [(b[0] >> 4) as i64, (b[1] >> 4) as i64, (b[2] >> 4) as i64];
+    let cross = [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ];
+    let cross_sq = (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]) as u64;
+    let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    atan2_mdeg(isqrt(cross_sq) as i32, dot as i32) as f32 / 1000.0
+}
+
+/// Roll and pitch in degrees: `(atan2(y, z), atan2(-x, sqrt(y² + z²)))`
+///
+/// Roll is the rotation about X (-180..=180, side lean), pitch about Y
+/// (-90..=90, forward lean). Inputs are calibrated accelerometer counts.
+#[cfg(feature = "float")]
+pub fn roll_pitch(ax: i32, ay: i32, az: i32) -> (f32, f32) {
+    roll_pitch_float(ax, ay, az)
+}
+
+/// Roll and pitch in degrees: `(atan2(y, z), atan2(-x, sqrt(y² + z²)))`
+///
+/// Roll is the rotation about X (-180..=180, side lean), pitch about Y
+/// (-90..=90, forward lean). Inputs are calibrated accelerometer counts.
+#[cfg(not(feature = "float"))]
+pub fn roll_pitch(ax: i32, ay: i32, az: i32) -> (f32, f32) {
+    roll_pitch_fixed(ax, ay, az)
+}
+
+/// Floating-point roll/pitch using libm
+#[cfg(feature = "float")]
+pub fn roll_pitch_float(ax: i32, ay: i32, az: i32) -> (f32, f32) {
+    let (ax, ay, az) = (ax as f32, ay as f32, az as f32);
+    let roll = libm::atan2f(ay, az);
+    let pitch = libm::atan2f(-ax, libm::sqrtf(ay * ay + az * az));
+    (roll * 180.0 / core::f32::consts::PI, pitch * 180.0 / core::f32::consts::PI)
+}
+
+/// Fixed-point roll/pitch using integer sqrt and CORDIC
+pub fn roll_pitch_fixed(ax: i32, ay: i32, az: i32) -> (f32, f32) {
+    let yz_sq = (ay as i64 * ay as i64 + az as i64 * az as i64) as u64;
+    let roll = atan2_signed_mdeg(ay, az);
+    // Both sides scaled by 16 so the floored sqrt keeps 4 fractional bits
+    let pitch = atan2_signed_mdeg(-ax << 4, isqrt(yz_sq << 8) as i32);
+    (roll as f32 / 1000.0, pitch as f32 / 1000.0)
+}
+
+/// `atan2_mdeg` for either sign of `y` (-180000..=180000)
+fn atan2_signed_mdeg(y: i32, x: i32) -> i32 {
+    let angle = atan2_mdeg(y.saturating_abs(), x);
+    if y < 0 {
+        -angle
+    } else {
+        angle
+    }
+}
+
+/// Integer square root (floor) of a u64
+pub fn isqrt(n: u64) -> u32 {
+    let mut rem = n;
+    let mut root: u64 = 0;
+    let mut bit: u64 = 1 << 62;
+
+    while bit > rem {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root as u32
+}
+
+/// CORDIC atan2 for `y >= 0`, result in millidegrees (0..=180000)
+///
+/// Vectoring mode: rotate (x, y) onto the positive X axis and sum the
+/// rotation angles. Negative x is first rotated by 90° into the right half-plane.
+pub fn atan2_mdeg(mut y: i32, mut x: i32) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+
+    // Only the ratio matters; shrink large inputs so the pre-scale can't overflow
+    while x.abs() >= CORDIC_MAX_INPUT || y >= CORDIC_MAX_INPUT {
+        x >>= 1;
+        y >>= 1;
+    }
+
+    let (mut x, mut y, mut angle) = if x < 0 {
+        (y << CORDIC_PRESCALE, -x << CORDIC_PRESCALE, 90_000)
+    } else {
+        (x << CORDIC_PRESCALE, y << CORDIC_PRESCALE, 0)
+    };
+
+    for (i, step) in CORDIC_ATAN_MDEG.iter().enumerate() {
+        let (dx, dy) = (y >> i, x >> i);
+        if y > 0 {
+            x += dx;
+            y -= dy;
+            angle += step;
+        } else {
+            x -= dx;
+            y += dy;
+            angle -= step;
+        }
+    }
+    angle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE_DEG: f32 = 0.1;
+
+    #[test]
+    fn test_isqrt() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(16384 * 16384), 16384);
+        assert_eq!(isqrt(u64::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn test_fixed_on_axes() {
+        for &((ax, ay, az), deg) in &[
+            ((0, 0, 16384), 0.0),
+            ((16384, 0, 0), 90.0),
+            ((0, -16384, 0), 90.0),
+            ((11585, 0, 11585), 45.0),
+            ((0, 0, -16384), 180.0),
+        ] {
+            let diff = tilt_angle_fixed(ax, ay, az) - deg;
+            assert!(diff.abs() < TOLERANCE_DEG, "({ax},{ay},{az}) diff={diff}");
+        }
+    }
+
+    #[test]
+    fn test_tilt_angle_uses_selected_path() {
+        let (ax, ay, az) = (-3000, 5000, 15000);
+        #[cfg(feature = "float")]
+        assert_eq!(tilt_angle(ax, ay, az), tilt_angle_float(ax, ay, az));
+        #[cfg(not(feature = "float"))]
+        assert_eq!(tilt_angle(ax, ay, az), tilt_angle_fixed(ax, ay, az));
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn test_fixed_matches_float_on_axes() {
+        for &(ax, ay, az) in &[(0, 0, 16384), (16384, 0, 0), (0, -16384, 0), (0, 0, -16384)] {
+            let diff = tilt_angle_fixed(ax, ay, az) - tilt_angle_float(ax, ay, az);
+            assert!(diff.abs() < TOLERANCE_DEG, "({ax},{ay},{az}) diff={diff}");
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn test_angle_between_fixed_matches_float() {
+        let vectors = [
+            [0, 0, 16384],
+            [16384, 0, 0],
+            [-300, 250, 16100],
+            [11585, 0, 11585],
+            [0, 0, -16384],
+            [1200, -16000, 900],
+        ];
+        for &a in &vectors {
+            for &b in &vectors {
+                let diff = angle_between_fixed(a, b) - angle_between_float(a, b);
+                assert!(diff.abs() < TOLERANCE_DEG, "{a:?} {b:?} diff={diff}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_roll_pitch_on_axes() {
+        let cases = [
+            ((0, 0, 16384), (0.0, 0.0)),
+            ((0, 16384, 0), (90.0, 0.0)),
+            ((0, -16384, 0), (-90.0, 0.0)),
+            ((-16384, 0, 0), (0.0, 90.0)),
+            ((11585, 0, 11585), (0.0, -45.0)),
+        ];
+        for &((ax, ay, az), (roll, pitch)) in &cases {
+            #[cfg(feature = "float")]
+            let results = [roll_pitch_float(ax, ay, az), roll_pitch_fixed(ax, ay, az)];
+            #[cfg(not(feature = "float"))]
+            let results = [roll_pitch_fixed(ax, ay, az)];
+            for (r, p) in results {
+                assert!((r - roll).abs() < TOLERANCE_DEG, "({ax},{ay},{az}) roll={r}");
+                assert!((p - pitch).abs() < TOLERANCE_DEG, "({ax},{ay},{az}) pitch={p}");
+            }
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn test_roll_pitch_fixed_matches_float_sweep() {
+        let mut ay = -32768;
+        while ay <= 32767 {
+            let mut az = -32768;
+            while az <= 32767 {
+                for ax in [-20000, -150, 0, 150, 20000] {
+                    let (fr, fp) = roll_pitch_float(ax, ay, az);
+                    let (xr, xp) = roll_pitch_fixed(ax, ay, az);
+                    // ±180° is the same roll
+                    let roll_diff = (xr - fr + 180.0).rem_euclid(360.0) - 180.0;
+                    assert!(roll_diff.abs() < TOLERANCE_DEG, "({ax},{ay},{az}) roll diff={roll_diff}");
+                    assert!((xp - fp).abs() < TOLERANCE_DEG, "({ax},{ay},{az}) pitch diff={}", xp - fp);
+                }
+                az += 1021;
+            }
+            ay += 1021;
+        }
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn test_fixed_matches_float_sweep() {
+        let mut ax = -32768;
+        while ax <= 32767 {
+            let mut az = -32768;
+            while az <= 32767 {
+                for ay in [-20000, -150, 0, 150, 20000] {
+                    let diff = tilt_angle_fixed(ax, ay, az) - tilt_angle_float(ax, ay, az);
+                    assert!(diff.abs() < TOLERANCE_DEG, "({ax},{ay},{az}) diff={diff}");
+                }
+                az += 1021;
+            }
+            ax += 1021;
+        }
+    }
+}
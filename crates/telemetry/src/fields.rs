@@ -0,0 +1,63 @@
+//! Telemetry columns selected with `stream.fields`
+//!
+//! The selection is a bitmask, one bit per field of the text/JSON stream
+//! line; fields are always emitted in `NAMES` order. Binary frames have a
+//! fixed layout (see `frame.rs`) and ignore it.
+
+pub const STATE: u8 = 1 << 0;
+pub const ACCEL: u8 = 1 << 1;
+pub const GYRO: u8 = 1 << 2;
+pub const NEO: u8 = 1 << 3;
+pub const CAL: u8 = 1 << 4;
+pub const SEQ: u8 = 1 << 5;
+pub const CNT: u8 = 1 << 6;
+pub const T: u8 = 1 << 7;
+
+/// Every field: the default, and what `all` selects
+pub const ALL: u8 = 0xFF;
+
+/// Field names as they appear in the stream line, in output order
+pub const NAMES: [(&str, u8); 8] = [
+    ("state", STATE),
+    ("accel", ACCEL),
+    ("gyro", GYRO),
+    ("neo", NEO),
+    ("cal", CAL),
+    ("seq", SEQ),
+    ("cnt", CNT),
+    ("t", T),
+];
+
+/// Parse a comma-separated list such as `accel,gyro,t`, or `all`
+///
+/// On failure returns the name that isn't a field (empty for an empty list).
+pub fn parse(list: &str) -> Result<u8, &str> {
+    let mut mask = 0;
+    for name in list.split(',').map(str::trim) {
+        if name == "all" {
+            mask |= ALL;
+            continue;
+        }
+        match NAMES.iter().find(|(n, _)| *n == name) {
+            Some(&(_, bit)) => mask |= bit,
+            None => return Err(name),
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_lists() {
+        assert_eq!(parse("accel"), Ok(ACCEL));
+        assert_eq!(parse("t,accel, gyro"), Ok(ACCEL | GYRO | T));
+        assert_eq!(parse("all"), Ok(ALL));
+        assert_eq!(parse("accel,accel"), Ok(ACCEL));
+        assert_eq!(parse("accel,tilt"), Err("tilt"));
+        assert_eq!(parse("accel,"), Err(""));
+        assert_eq!(parse(""), Err(""));
+    }
+}
@@ -0,0 +1,98 @@
+//! Fixed-size binary sample frames for `stream.format bin`
+//!
+//! Formatting text dominates the stream cost at high rates; a frame is
+//! 18 bytes, all little-endian, with no padding:
+//!
+//! | offset | type    | content                               |
+//! |--------|---------|---------------------------------------|
+//! | 0      | u8      | sync, always `0xAA`                   |
+//! | 1      | u32     | timestamp, ms since boot              |
+//! | 5      | i16 × 3 | accel x, y, z (raw counts)            |
+//! | 11     | i16 × 3 | gyro x, y, z (raw counts)             |
+//! | 17     | u8      | CRC-8 of bytes 0-16                   |
+//!
+//! On the host: `struct.unpack("<BI6hB", frame)`. The CRC is CRC-8/SMBUS
+//! (poly 0x07, init 0, no reflection), the same one lesson 05 appends to
+//! its text lines.
+
+pub const SYNC: u8 = 0xAA;
+pub const FRAME_LEN: usize = 18;
+
+const CRC_POLY: u8 = 0x07;
+
+/// One sample as laid out on the wire
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct ImuFrame {
+    pub sync: u8,
+    pub t_ms: u32,
+    pub accel: [i16; 3],
+    pub gyro: [i16; 3],
+    pub crc: u8,
+}
+
+const _: () = assert!(core::mem::size_of::<ImuFrame>() == FRAME_LEN);
+
+impl ImuFrame {
+    /// Frame for one sample, with its CRC filled in
+    pub fn new(t_ms: u32, accel: [i16; 3], gyro: [i16; 3]) -> Self {
+        let mut frame = Self {
+            sync: SYNC,
+            t_ms,
+            accel,
+            gyro,
+            crc: 0,
+        };
+        frame.crc = crc8(&frame.to_bytes()[..FRAME_LEN - 1]);
+        frame
+    }
+
+    /// Serialize field by field, so the byte order doesn't depend on the target
+    pub fn to_bytes(&self) -> [u8; FRAME_LEN] {
+        // Copy out of the packed struct; references to its fields may be unaligned
+        let (t_ms, accel, gyro) = (self.t_ms, self.accel, self.gyro);
+        let mut bytes = [0u8; FRAME_LEN];
+        bytes[0] = self.sync;
+        bytes[1..5].copy_from_slice(&t_ms.to_le_bytes());
+        for (chunk, value) in bytes[5..17].chunks_exact_mut(2).zip(accel.iter().chain(gyro.iter())) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[17] = self.crc;
+        bytes
+    }
+}
+
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ CRC_POLY } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_layout() {
+        let bytes = ImuFrame::new(0x0102_0304, [1, -1, 16384], [-32768, 0, 258]).to_bytes();
+        assert_eq!(
+            bytes[..17],
+            [
+                0xAA, 0x04, 0x03, 0x02, 0x01, // sync, t
+                0x01, 0x00, 0xFF, 0xFF, 0x00, 0x40, // accel
+                0x00, 0x80, 0x00, 0x00, 0x02, 0x01, // gyro
+            ]
+        );
+        assert_eq!(bytes[17], crc8(&bytes[..17]));
+    }
+
+    #[test]
+    fn test_crc8_check_value() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+    }
+}
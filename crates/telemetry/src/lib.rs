@@ -0,0 +1,11 @@
+//! Hardware-independent parts of the lesson 04 telemetry stream
+//!
+//! The `stream.fields` column selection and the binary frame layout behind
+//! `stream.format bin`. The lesson re-exports the modules, so its code keeps
+//! using `mpu::frame` etc.; keeping them here lets `cargo test` run them on
+//! the host.
+
+#![no_std]
+
+pub mod fields;
+pub mod frame;
@@ -1,13 +1,17 @@
 #![no_std]
 #![no_main]
 
+use cli::{CmdError, CmdResult, Command, CommandOutput, CommandTable, LineReader, PROMPT};
 use core::fmt::Write;
 use esp_backtrace as _;
 use esp_hal::{
     delay::Delay,
-    gpio::{Level, Output, OutputConfig},
+    gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull},
     main,
+    system::software_reset,
+    time::Instant,
     uart::{Config as UartConfig, Uart},
+    Blocking,
 };
 use heapless::String;
 use log::info;
@@ -17,6 +21,16 @@ esp_bootloader_esp_idf::esp_app_desc!();
 // GPIO configuration
 const LED_PIN: u8 = 12;
 
+// Pins the CLI may drive; `Ctx::outputs` holds one Output per entry, same order
+const GPIO_PINS: [u8; 4] = [2, LED_PIN, 18, 19];
+const UNSUPPORTED_PIN: &str = "Unsupported pin (use 2, 12, 18 or 19)";
+
+// Pins gpio.read may read, pulled up (GPIO9 is the BOOT button);
+// `Ctx::inputs` holds one Input per entry, same order
+const INPUT_PINS: [u8; 3] = [4, 5, 9];
+const UNSUPPORTED_INPUT_PIN: &str = "Unsupported pin (inputs: 4, 5, 9)";
+const OUTPUT_NOT_READABLE: &str = "Pin is configured as an output (gpio.read takes 4, 5 or 9)";
+
 // UART configuration (FTDI adapter)
 const UART_TX_PIN: u8 = 23;
 const UART_RX_PIN: u8 = 15;
@@ -25,6 +39,14 @@ const UART_BAUD: u32 = 115200;
 // CLI buffer size
 const CMD_BUFFER_SIZE: usize = 128;
 
+// stream.rate limits (ms): the loop ticks every 10 ms
+const STREAM_PERIOD_MIN_MS: u32 = 10;
+const STREAM_PERIOD_MAX_MS: u32 = 5000;
+
+// sys.reset: wait this long after the confirmation so the UART TX FIFO
+// (128 bytes, ~11 ms at 115200 baud) drains before the chip resets
+const RESET_FLUSH_MS: u32 = 50;
+
 // Operating modes
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
@@ -41,9 +63,46 @@ static mut MODE: Mode = Mode::CLI;
 #[no_mangle]
 static mut GPIO_CHANGES: u32 = 0;
 
+// Milliseconds since boot, read from the system timer at the top of each
+// loop iteration (wraps after ~49 days; compare with wrapping_sub)
 #[no_mangle]
 static mut UPTIME_MS: u32 = 0;
 
+// Time between streamed lines (stream.rate), 100 ms = 10 Hz
+#[no_mangle]
+static mut STREAM_PERIOD_MS: u32 = 100;
+
+// term.echo: echo typed characters back (off for terminals with local echo)
+#[no_mangle]
+static mut TERM_ECHO: bool = true;
+
+// Every CLI command: dispatch, usage errors and `help` all come from this table
+static COMMANDS: CommandTable<Ctx> = CommandTable::new(&[
+    Command { name: "gpio.init", usage: "gpio.init <pin>", help: "Initialize GPIO as output", handler: cmd_gpio_init },
+    Command { name: "gpio.on", usage: "gpio.on <pin>", help: "Set GPIO high", handler: cmd_gpio_on },
+    Command { name: "gpio.off", usage: "gpio.off <pin>", help: "Set GPIO low", handler: cmd_gpio_off },
+    Command { name: "gpio.deinit", usage: "gpio.deinit <pin>", help: "Deinitialize GPIO", handler: cmd_gpio_deinit },
+    Command { name: "gpio.read", usage: "gpio.read <pin>", help: "Read an input pin (HIGH/LOW)", handler: cmd_gpio_read },
+    Command { name: "stream.start", usage: "stream.start", help: "Start streaming mode", handler: cmd_stream_start },
+    Command { name: "stream.stop", usage: "stream.stop", help: "Stop streaming (back to CLI)", handler: cmd_stream_stop },
+    Command { name: "stream.rate", usage: "stream.rate <ms>", help: "Time between streamed lines (10-5000)", handler: cmd_stream_rate },
+    Command { name: "help", usage: "help", help: "Show this help", handler: cmd_help },
+    Command { name: "ping", usage: "ping", help: "Reply pong <uptime_ms>", handler: cmd_ping },
+    Command { name: "echo", usage: "echo <text>", help: "Echo text back", handler: cmd_echo },
+    Command { name: "term.echo", usage: "term.echo <on|off>", help: "Echo typed characters (off for local echo)", handler: cmd_term_echo },
+    Command { name: "sys.reset", usage: "sys.reset", help: "Restart the chip (software reset)", handler: cmd_sys_reset },
+]);
+
+/// What the command handlers work on
+struct Ctx {
+    /// One Output per GPIO_PINS entry, same order
+    outputs: [Option<Output<'static>>; GPIO_PINS.len()],
+    /// One Input per INPUT_PINS entry, same order
+    inputs: [Input<'static>; INPUT_PINS.len()],
+    /// Replies are returned, not written here; only sys.reset writes directly
+    uart: Uart<'static, Blocking>,
+}
+
 #[main]
 fn main() -> ! {
     esp_println::logger::init_logger_from_env();
@@ -54,35 +113,50 @@ fn main() -> ! {
     let peripherals = esp_hal::init(esp_hal::Config::default());
     let mut delay = Delay::new();
 
-    // Initialize LED
-    info!("[INIT] Configuring GPIO{} as output for LED...", LED_PIN);
-    let mut led = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
-    info!("[INIT] LED ready");
+    // Initialize CLI-controlled outputs (LED on GPIO12), all low at boot
+    info!("[INIT] Configuring GPIO{:?} as outputs (LED on GPIO{})...", GPIO_PINS, LED_PIN);
+    let outputs = [
+        Some(Output::new(peripherals.GPIO2, Level::Low, OutputConfig::default())),
+        Some(Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default())),
+        Some(Output::new(peripherals.GPIO18, Level::Low, OutputConfig::default())),
+        Some(Output::new(peripherals.GPIO19, Level::Low, OutputConfig::default())),
+    ];
+    info!("[INIT] Configuring GPIO{:?} as inputs with pull-ups...", INPUT_PINS);
+    let input_config = InputConfig::default().with_pull(Pull::Up);
+    let inputs = [
+        Input::new(peripherals.GPIO4, input_config),
+        Input::new(peripherals.GPIO5, input_config),
+        Input::new(peripherals.GPIO9, input_config),
+    ];
+    info!("[INIT] GPIO ready");
 
     // Initialize UART (TX=GPIO23, RX=GPIO15)
     info!("[INIT] Configuring UART on GPIO{} (TX), GPIO{} (RX) @ {} baud...",
           UART_TX_PIN, UART_RX_PIN, UART_BAUD);
 
     let uart_config = UartConfig::default().with_baudrate(UART_BAUD);
-    let mut uart = Uart::new(peripherals.UART1, uart_config)
+    let uart = Uart::new(peripherals.UART1, uart_config)
         .unwrap()
         .with_tx(peripherals.GPIO23)
         .with_rx(peripherals.GPIO15);
 
     info!("[INIT] UART ready\n");
 
+    let mut ctx = Ctx { outputs, inputs, uart };
+
     // Send welcome message over UART
-    let _ = uart.write_str("\r\n=== ESP32-C6 UART CLI ===\r\n");
-    let _ = uart.write_str("Type 'help' for commands\r\n\r\n> ");
+    let _ = ctx.uart.write_str("\r\n=== ESP32-C6 UART CLI ===\r\n");
+    let _ = ctx.uart.write_str("Type 'help' for commands\r\n\r\n> ");
 
-    let mut cmd_buffer: String<CMD_BUFFER_SIZE> = String::new();
-    let mut led_state = false;
+    // Echo, backspace, history and overlong lines are handled by the reader
+    let mut line_reader: LineReader<CMD_BUFFER_SIZE> = LineReader::new();
     let mut counter: u32 = 0;
+    let mut seq: u32 = 0; // per emitted line, for host-side gap detection
     let mut last_stream_time_ms: u32 = 0;
 
     loop {
         unsafe {
-            UPTIME_MS = UPTIME_MS.wrapping_add(10);
+            UPTIME_MS = Instant::now().duration_since_epoch().as_millis() as u32;
         }
 
         // Check current mode (can be changed via GDB)
@@ -91,58 +165,39 @@ fn main() -> ! {
         match current_mode {
             Mode::CLI => {
                 // CLI mode: process commands from UART
-                let mut rx_byte = [0u8; 1];
-                if uart.read(&mut rx_byte).is_ok() {
-                    let ch = rx_byte[0] as char;
-
-                    if ch == '\r' || ch == '\n' {
-                        if !cmd_buffer.is_empty() {
-                            // Echo newline
-                            let _ = uart.write_str("\r\n");
-
-                            // Process command
-                            process_command(&cmd_buffer, &mut led, &mut led_state, &mut uart);
-
-                            // Clear buffer
-                            cmd_buffer.clear();
-
-                            // Show prompt
-                            let _ = uart.write_str("> ");
-                        }
-                    } else if ch == '\x08' || ch == '\x7f' {
-                        // Backspace
-                        if cmd_buffer.pop().is_some() {
-                            let _ = uart.write_str("\x08 \x08");
-                        }
-                    } else if ch.is_ascii_graphic() || ch == ' ' {
-                        // Echo character
-                        let _ = uart.write(&[ch as u8]);
-
-                        // Add to buffer (ignore if full)
-                        let _ = cmd_buffer.push(ch);
-                    }
+                line_reader.set_echo(unsafe { TERM_ECHO });
+                if let Some(line) = line_reader.poll(&mut ctx.uart) {
+                    let result = process_command(line, &mut ctx);
+                    let _ = COMMANDS.write_result(&mut ctx.uart, line, &result);
+                    let _ = ctx.uart.write_str(PROMPT);
                 }
             }
             Mode::Streaming => {
-                // Streaming mode: output telemetry at 10 Hz
+                // Streaming mode: output telemetry every STREAM_PERIOD_MS
                 let current_time_ms = unsafe { UPTIME_MS };
-                if current_time_ms.wrapping_sub(last_stream_time_ms) >= 100 {
+                if current_time_ms.wrapping_sub(last_stream_time_ms) >= unsafe { STREAM_PERIOD_MS } {
                     last_stream_time_ms = current_time_ms;
                     counter = counter.wrapping_add(1);
+                    seq = seq.wrapping_add(1);
+                    if seq == 0 {
+                        let _ = ctx.uart.write_str("[seq wrapped]\r\n");
+                    }
 
                     // Stream telemetry in parseable format
+                    let led_on = output_for(&mut ctx.outputs, LED_PIN).is_some_and(|led| led.is_set_high());
                     let mut msg: String<128> = String::new();
                     write!(
                         msg,
-                        "[gpio{}={} counter={} uptime_ms={}]\r\n",
+                        "[gpio{}={} seq={} counter={} uptime_ms={}]\r\n",
                         LED_PIN,
-                        if led_state { 1 } else { 0 },
+                        if led_on { 1 } else { 0 },
+                        seq,
                         counter,
                         current_time_ms
                     )
                     .ok();
 
-                    let _ = uart.write_str(&msg);
+                    let _ = ctx.uart.write_str(&msg);
                 }
             }
         }
@@ -151,112 +206,147 @@ fn main() -> ! {
     }
 }
 
-fn process_command<W: Write>(
-    cmd: &str,
-    led: &mut Output,
-    led_state: &mut bool,
-    uart: &mut W,
-) {
-    let cmd_trimmed = cmd.trim();
-    let parts: heapless::Vec<&str, 4> = cmd_trimmed.split_whitespace().collect();
-
-    if parts.is_empty() {
-        return;
+/// The output driving `pin`, or None if the pin is not in GPIO_PINS
+fn output_for<'a, 'd>(outputs: &'a mut [Option<Output<'d>>], pin: u8) -> Option<&'a mut Output<'d>> {
+    let index = GPIO_PINS.iter().position(|&p| p == pin)?;
+    outputs.get_mut(index)?.as_mut()
+}
+
+/// The input reading `pin`, or None if the pin is not in INPUT_PINS
+fn input_for<'a>(inputs: &'a [Input<'static>], pin: u8) -> Option<&'a Input<'static>> {
+    let index = INPUT_PINS.iter().position(|&p| p == pin)?;
+    inputs.get(index)
+}
+
+/// Run one command line; the caller prints the reply or the error
+fn process_command(cmd: &str, ctx: &mut Ctx) -> CmdResult {
+    COMMANDS.dispatch(cmd, ctx)
+}
+
+/// The `<pin>` argument of the gpio.* commands
+fn pin_arg(args: &[&str]) -> Result<u8, CmdError> {
+    let arg = args.first().ok_or(CmdError::Usage)?;
+    arg.parse::<u8>().map_err(|_| CmdError::Invalid("Invalid pin number"))
+}
+
+/// Drive the output for `<pin>` high or low and report it
+fn set_pin(args: &[&str], ctx: &mut Ctx, high: bool) -> CmdResult {
+    let pin = pin_arg(args)?;
+    let output = output_for(&mut ctx.outputs, pin).ok_or(CmdError::Invalid(UNSUPPORTED_PIN))?;
+    if high {
+        output.set_high();
+    } else {
+        output.set_low();
     }
+    unsafe { GPIO_CHANGES += 1; }
+    let mut out = CommandOutput::new();
+    write!(out, "OK [GPIO{} = {}]\r\n", pin, if high { "HIGH" } else { "LOW" }).ok();
+    Ok(out)
+}
 
-    match parts[0] {
-        "help" => {
-            let _ = uart.write_str("Commands:\r\n");
-            let _ = uart.write_str("  gpio.init <pin>     - Initialize GPIO as output\r\n");
-            let _ = uart.write_str("  gpio.on <pin>       - Set GPIO high\r\n");
-            let _ = uart.write_str("  gpio.off <pin>      - Set GPIO low\r\n");
-            let _ = uart.write_str("  gpio.deinit <pin>   - Deinitialize GPIO\r\n");
-            let _ = uart.write_str("  stream.start        - Start streaming mode\r\n");
-            let _ = uart.write_str("  stream.stop         - Stop streaming (back to CLI)\r\n");
-            let _ = uart.write_str("  help                - Show this help\r\n");
-        }
-        "gpio.init" => {
-            if parts.len() < 2 {
-                let _ = uart.write_str("ERROR: Usage: gpio.init <pin>\r\n");
-            } else if let Ok(pin) = parts[1].parse::<u8>() {
-                if pin == LED_PIN {
-                    let _ = uart.write_str("OK [GPIO");
-                    let mut buf: String<16> = String::new();
-                    write!(buf, "{}", pin).ok();
-                    let _ = uart.write_str(&buf);
-                    let _ = uart.write_str(" initialized as output]\r\n");
-                } else {
-                    let _ = uart.write_str("ERROR: Only GPIO12 supported in this lesson\r\n");
-                }
-            } else {
-                let _ = uart.write_str("ERROR: Invalid pin number\r\n");
-            }
-        }
-        "gpio.on" => {
-            if parts.len() < 2 {
-                let _ = uart.write_str("ERROR: Usage: gpio.on <pin>\r\n");
-            } else if let Ok(pin) = parts[1].parse::<u8>() {
-                if pin == LED_PIN {
-                    led.set_high();
-                    *led_state = true;
-                    unsafe { GPIO_CHANGES += 1; }
-                    let _ = uart.write_str("OK [GPIO");
-                    let mut buf: String<16> = String::new();
-                    write!(buf, "{}", pin).ok();
-                    let _ = uart.write_str(&buf);
-                    let _ = uart.write_str(" = HIGH]\r\n");
-                } else {
-                    let _ = uart.write_str("ERROR: Only GPIO12 supported\r\n");
-                }
-            } else {
-                let _ = uart.write_str("ERROR: Invalid pin number\r\n");
-            }
-        }
-        "gpio.off" => {
-            if parts.len() < 2 {
-                let _ = uart.write_str("ERROR: Usage: gpio.off <pin>\r\n");
-            } else if let Ok(pin) = parts[1].parse::<u8>() {
-                if pin == LED_PIN {
-                    led.set_low();
-                    *led_state = false;
-                    unsafe { GPIO_CHANGES += 1; }
-                    let _ = uart.write_str("OK [GPIO");
-                    let mut buf: String<16> = String::new();
-                    write!(buf, "{}", pin).ok();
-                    let _ = uart.write_str(&buf);
-                    let _ = uart.write_str(" = LOW]\r\n");
-                } else {
-                    let _ = uart.write_str("ERROR: Only GPIO12 supported\r\n");
-                }
-            } else {
-                let _ = uart.write_str("ERROR: Invalid pin number\r\n");
-            }
-        }
-        "gpio.deinit" => {
-            if parts.len() < 2 {
-                let _ = uart.write_str("ERROR: Usage: gpio.deinit <pin>\r\n");
-            } else if let Ok(pin) = parts[1].parse::<u8>() {
-                let _ = uart.write_str("OK [GPIO");
-                let mut buf: String<16> = String::new();
-                write!(buf, "{}", pin).ok();
-                let _ = uart.write_str(&buf);
-                let _ = uart.write_str(" deinitialized]\r\n");
-            } else {
-                let _ = uart.write_str("ERROR: Invalid pin number\r\n");
-            }
-        }
-        "stream.start" => {
-            unsafe { MODE = Mode::Streaming; }
-            let _ = uart.write_str("[Switching to streaming mode...]\r\n");
-        }
-        "stream.stop" => {
-            unsafe { MODE = Mode::CLI; }
-            let _ = uart.write_str("[Switching to CLI mode...]\r\n");
-        }
-        _ => {
-            let _ = uart.write_str("ERROR: Unknown command. Type 'help' for commands.\r\n");
+fn cmd_help(_args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let mut out = CommandOutput::from("Commands:\r\n");
+    COMMANDS.write_help(&mut out).ok();
+    Ok(out)
+}
+
+fn cmd_gpio_init(args: &[&str], ctx: &mut Ctx) -> CmdResult {
+    let pin = pin_arg(args)?;
+    if output_for(&mut ctx.outputs, pin).is_none() {
+        return Err(CmdError::Invalid(UNSUPPORTED_PIN));
+    }
+    let mut out = CommandOutput::new();
+    write!(out, "OK [GPIO{} initialized as output]\r\n", pin).ok();
+    Ok(out)
+}
+
+fn cmd_gpio_on(args: &[&str], ctx: &mut Ctx) -> CmdResult {
+    set_pin(args, ctx, true)
+}
+
+fn cmd_gpio_off(args: &[&str], ctx: &mut Ctx) -> CmdResult {
+    set_pin(args, ctx, false)
+}
+
+fn cmd_gpio_deinit(args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let pin = pin_arg(args)?;
+    let mut out = CommandOutput::new();
+    write!(out, "OK [GPIO{} deinitialized]\r\n", pin).ok();
+    Ok(out)
+}
+
+fn cmd_gpio_read(args: &[&str], ctx: &mut Ctx) -> CmdResult {
+    let pin = pin_arg(args)?;
+    let Some(input) = input_for(&ctx.inputs, pin) else {
+        // An output pin's input path isn't enabled, so reading it would be meaningless
+        let reason = if GPIO_PINS.contains(&pin) { OUTPUT_NOT_READABLE } else { UNSUPPORTED_INPUT_PIN };
+        return Err(CmdError::Invalid(reason));
+    };
+    let mut out = CommandOutput::new();
+    write!(out, "GPIO{} = {}\r\n", pin, if input.is_high() { "HIGH" } else { "LOW" }).ok();
+    Ok(out)
+}
+
+fn cmd_stream_start(_args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    unsafe { MODE = Mode::Streaming; }
+    Ok(CommandOutput::from("[Switching to streaming mode...]\r\n"))
+}
+
+fn cmd_stream_stop(_args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    unsafe { MODE = Mode::CLI; }
+    Ok(CommandOutput::from("[Switching to CLI mode...]\r\n"))
+}
+
+fn cmd_stream_rate(args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let ms = args
+        .first()
+        .ok_or(CmdError::Usage)?
+        .parse::<u32>()
+        .map_err(|_| CmdError::Invalid("Invalid period"))?;
+    if !(STREAM_PERIOD_MIN_MS..=STREAM_PERIOD_MAX_MS).contains(&ms) {
+        return Err(CmdError::Invalid("Rate must be 10-5000 ms"));
+    }
+    unsafe { STREAM_PERIOD_MS = ms; }
+    let mut out = CommandOutput::new();
+    write!(out, "OK [Stream period = {} ms]\r\n", ms).ok();
+    Ok(out)
+}
+
+fn cmd_ping(_args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let mut out = CommandOutput::new();
+    write!(out, "pong {}\r\n", unsafe { UPTIME_MS }).ok();
+    Ok(out)
+}
+
+/// Words come back separated by single spaces
+fn cmd_echo(args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let mut out = CommandOutput::new();
+    for (i, word) in args.iter().enumerate() {
+        if i > 0 {
+            out.write_str(" ").ok();
         }
+        out.write_str(word).ok();
     }
+    out.write_str("\r\n").ok();
+    Ok(out)
+}
+
+fn cmd_term_echo(args: &[&str], _ctx: &mut Ctx) -> CmdResult {
+    let echo = match args.first().copied() {
+        Some("on") => true,
+        Some("off") => false,
+        _ => return Err(CmdError::Usage),
+    };
+    unsafe { TERM_ECHO = echo; }
+    Ok(CommandOutput::from(if echo { "OK [Echo on]\r\n" } else { "OK [Echo off]\r\n" }))
+}
+
+/// Never returns, so unlike the other handlers it writes its reply itself
+fn cmd_sys_reset(_args: &[&str], ctx: &mut Ctx) -> CmdResult {
+    let _ = ctx.uart.write_str("Resetting...\r\n");
+    // Give the UART time to shift the line out before the reset cuts it off
+    Delay::new().delay_millis(RESET_FLUSH_MS);
+    software_reset();
 }
 
 // GDB-callable functions for hardware validation
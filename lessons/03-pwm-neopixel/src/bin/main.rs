@@ -5,10 +5,15 @@ use core::fmt::Write;
 use esp_backtrace as _;
 use esp_hal::{
     delay::Delay,
-    gpio::{Level, Output, OutputConfig},
+    gpio::DriveMode,
+    ledc::{
+        channel::{self, Channel, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource, Ledc, LowSpeed,
+    },
     main,
     rmt::Rmt,
-    time::Rate,
+    time::{Instant, Rate},
     uart::{Config as UartConfig, Uart},
     Blocking,
 };
@@ -26,8 +31,19 @@ const UART_TX_PIN: u8 = 23;
 const UART_RX_PIN: u8 = 15;
 const UART_BAUD: u32 = 115200;
 
+// LED PWM: 10-bit resolution at 5 kHz (APB 80 MHz / 1024 / 5000 ~ 15.6 divider)
+const PWM_FREQ_KHZ: u32 = 5;
+
 const CMD_BUFFER_SIZE: usize = 128;
 
+// neo.rainbow: hue step per 10 ms loop tick, in degrees (1 = one turn in 3.6 s)
+const RAINBOW_DEFAULT_SPEED: u16 = 2;
+const RAINBOW_MAX_SPEED: u16 = 60;
+
+// neo.blink: allowed blink rates (full on/off cycles per second)
+const BLINK_MIN_HZ: u8 = 1;
+const BLINK_MAX_HZ: u8 = 10;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
 enum Mode {
@@ -38,6 +54,8 @@ enum Mode {
 #[no_mangle]
 static mut MODE: Mode = Mode::CLI;
 
+// Milliseconds since boot, read from the system timer at the top of each
+// loop iteration (wraps after ~49 days; compare with wrapping_sub)
 #[no_mangle]
 static mut UPTIME_MS: u32 = 0;
 
@@ -51,6 +69,31 @@ static mut NEO_G: u8 = 0;
 #[no_mangle]
 static mut NEO_B: u8 = 0;
 
+// neo.rainbow animation: running flag, current hue (0-359°) and step per tick.
+// neo.stop, neo.color and neo.off clear NEO_RAINBOW.
+#[no_mangle]
+static mut NEO_RAINBOW: bool = false;
+#[no_mangle]
+static mut NEO_HUE: u16 = 0;
+#[no_mangle]
+static mut NEO_RAINBOW_SPEED: u16 = RAINBOW_DEFAULT_SPEED;
+
+// neo.blink: rate (0 = not blinking), color, whether the pixel is currently
+// lit and when it last toggled (UPTIME_MS). neo.stop, neo.color, neo.off and
+// neo.rainbow set NEO_BLINK_HZ back to 0.
+#[no_mangle]
+static mut NEO_BLINK_HZ: u8 = 0;
+#[no_mangle]
+static mut NEO_BLINK_R: u8 = 0;
+#[no_mangle]
+static mut NEO_BLINK_G: u8 = 0;
+#[no_mangle]
+static mut NEO_BLINK_B: u8 = 0;
+#[no_mangle]
+static mut NEO_BLINK_LIT: bool = false;
+#[no_mangle]
+static mut NEO_BLINK_TOGGLE_MS: u32 = 0;
+
 #[main]
 fn main() -> ! {
     esp_println::logger::init_logger_from_env();
@@ -69,9 +112,26 @@ fn main() -> ! {
         .with_tx(peripherals.GPIO23)
         .with_rx(peripherals.GPIO15);
 
-    // Initialize GPIO12 as output (simplified - PWM will be added in future revision)
-    info!("[INIT] Configuring GPIO12 for LED...");
-    let mut led_gpio = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
+    // Initialize LEDC: low-speed timer0 at 5 kHz driving channel0 on GPIO12
+    info!("[INIT] Configuring LEDC PWM on GPIO12...");
+    let mut ledc = Ledc::new(peripherals.LEDC);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut pwm_timer = ledc.timer::<LowSpeed>(timer::Number::Timer0);
+    pwm_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: Rate::from_khz(PWM_FREQ_KHZ),
+        })
+        .expect("Failed to configure LEDC timer");
+    let mut led_pwm = ledc.channel(channel::Number::Channel0, peripherals.GPIO12);
+    led_pwm
+        .configure(channel::config::Config {
+            timer: &pwm_timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })
+        .expect("Failed to configure LEDC channel");
 
     // Initialize Neopixel (RMT)
     info!("[INIT] Configuring Neopixel (RMT)...");
@@ -89,12 +149,15 @@ fn main() -> ! {
     let _ = uart.write_str("Commands: help, gpio.*, pwm.*, neo.*, stream.*\r\n\r\n> ");
 
     let mut cmd_buffer: String<CMD_BUFFER_SIZE> = String::new();
+    // Set when a line outgrew cmd_buffer; the rest of it is ignored until Enter
+    let mut line_too_long = false;
     let mut counter: u32 = 0;
+    let mut seq: u32 = 0; // per emitted line, for host-side gap detection
     let mut last_stream_time_ms: u32 = 0;
 
     loop {
         unsafe {
-            UPTIME_MS = UPTIME_MS.wrapping_add(10);
+            UPTIME_MS = Instant::now().duration_since_epoch().as_millis() as u32;
         }
 
         let current_mode = unsafe { MODE };
@@ -106,9 +169,10 @@ fn main() -> ! {
                     let ch = rx_byte[0] as char;
 
                     if ch == '\r' || ch == '\n' {
+                        line_too_long = false;
                         if !cmd_buffer.is_empty() {
                             let _ = uart.write_str("\r\n");
-                            process_command(&cmd_buffer, &mut led_gpio, &mut led, &mut uart);
+                            process_command(&cmd_buffer, &led_pwm, &mut led, &mut uart);
                             cmd_buffer.clear();
                             let _ = uart.write_str("> ");
                         }
@@ -116,9 +180,15 @@ fn main() -> ! {
                         if cmd_buffer.pop().is_some() {
                             let _ = uart.write_str("\x08 \x08");
                         }
+                    } else if line_too_long {
+                        // Rest of an overlong line, already reported
                     } else if ch.is_ascii_graphic() || ch == ' ' {
-                        let _ = uart.write(&[ch as u8]);
-                        let _ = cmd_buffer.push(ch);
+                        if cmd_buffer.push(ch).is_ok() {
+                            let _ = uart.write(&[ch as u8]);
+                        } else {
+                            line_too_long = true;
+                            report_line_too_long(&mut cmd_buffer, &mut uart);
+                        }
                     }
                 }
             }
@@ -127,32 +197,112 @@ fn main() -> ! {
                 if current_time_ms.wrapping_sub(last_stream_time_ms) >= 100 {
                     last_stream_time_ms = current_time_ms;
                     counter = counter.wrapping_add(1);
+                    seq = seq.wrapping_add(1);
+                    if seq == 0 {
+                        let _ = uart.write_str("[seq wrapped]\r\n");
+                    }
 
                     let mut msg: String<128> = String::new();
                     let (pwm, r, g, b) = unsafe { (PWM_DUTY, NEO_R, NEO_G, NEO_B) };
-                    write!(
-                        msg,
-                        "[pwm{}={}% neo_r={} neo_g={} neo_b={} counter={} uptime_ms={}]\r\n",
-                        LED_PWM_PIN, pwm, r, g, b, counter, current_time_ms
-                    )
-                    .ok();
+                    write!(msg, "[pwm{}={}% neo_r={} neo_g={} neo_b={}", LED_PWM_PIN, pwm, r, g, b).ok();
+                    if unsafe { NEO_RAINBOW } {
+                        write!(msg, " rainbow hue={}", unsafe { NEO_HUE }).ok();
+                    }
+                    if unsafe { NEO_BLINK_HZ } != 0 {
+                        write!(msg, " blink={}Hz", unsafe { NEO_BLINK_HZ }).ok();
+                    }
+                    write!(msg, " seq={} counter={} uptime_ms={}]\r\n", seq, counter, current_time_ms).ok();
 
                     let _ = uart.write_str(&msg);
                 }
             }
         }
 
+        // Rainbow animation: one hue step per loop tick
+        if unsafe { NEO_RAINBOW } {
+            let color = unsafe {
+                NEO_HUE = (NEO_HUE + NEO_RAINBOW_SPEED) % 360;
+                hsv_to_rgb(NEO_HUE, 255, 255)
+            };
+            led.write([color].into_iter()).ok();
+            unsafe {
+                NEO_R = color.r;
+                NEO_G = color.g;
+                NEO_B = color.b;
+            }
+        }
+
+        // Blink: toggle every half period, same elapsed-time check as streaming
+        let blink_hz = unsafe { NEO_BLINK_HZ };
+        if blink_hz != 0 {
+            let now = unsafe { UPTIME_MS };
+            let half_period_ms = 500 / blink_hz as u32;
+            if now.wrapping_sub(unsafe { NEO_BLINK_TOGGLE_MS }) >= half_period_ms {
+                let color = unsafe {
+                    NEO_BLINK_TOGGLE_MS = now;
+                    NEO_BLINK_LIT = !NEO_BLINK_LIT;
+                    if NEO_BLINK_LIT {
+                        RGB8::new(NEO_BLINK_R, NEO_BLINK_G, NEO_BLINK_B)
+                    } else {
+                        RGB8::new(0, 0, 0)
+                    }
+                };
+                led.write([color].into_iter()).ok();
+                unsafe {
+                    NEO_R = color.r;
+                    NEO_G = color.g;
+                    NEO_B = color.b;
+                }
+            }
+        }
+
         delay.delay_millis(10);
     }
 }
 
+/// HSV to RGB, integer only: hue in degrees (0-359), saturation and value 0-255
+fn hsv_to_rgb(hue: u16, sat: u8, val: u8) -> RGB8 {
+    let (s, v) = (sat as u32, val as u32);
+    // Position within the 60° sector, scaled to 0-255
+    let f = (hue % 60) as u32 * 255 / 60;
+    let p = v * (255 - s) / 255;
+    let q = v * (255 - s * f / 255) / 255;
+    let t = v * (255 - s * (255 - f) / 255) / 255;
+    let (r, g, b) = match (hue % 360) / 60 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    RGB8::new(r as u8, g as u8, b as u8)
+}
+
+/// The line outgrew the command buffer: ring the bell, drop it and redraw
+/// the prompt. The rest of the line is ignored until Enter.
+fn report_line_too_long<W: Write>(cmd_buffer: &mut String<CMD_BUFFER_SIZE>, uart: &mut W) {
+    cmd_buffer.clear();
+    let mut buf: String<64> = String::new();
+    write!(buf, "\x07\r\nERROR: command too long (max {} characters)\r\n> ", CMD_BUFFER_SIZE).ok();
+    let _ = uart.write_str(&buf);
+}
+
 fn process_command<W: Write>(
     cmd: &str,
-    led_gpio: &mut Output,
+    led_pwm: &Channel<'_, LowSpeed>,
     neopixel: &mut SmartLedsAdapter<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>,
     uart: &mut W,
 ) {
     let cmd_trimmed = cmd.trim();
+
+    // `echo` takes free text, so handle it before splitting into a fixed-size arg list
+    if cmd_trimmed == "echo" || cmd_trimmed.starts_with("echo ") {
+        let _ = uart.write_str(cmd_trimmed[4..].trim_start());
+        let _ = uart.write_str("\r\n");
+        return;
+    }
+
     let parts: heapless::Vec<&str, 5> = cmd_trimmed.split_whitespace().collect();
 
     if parts.is_empty() {
@@ -166,18 +316,41 @@ fn process_command<W: Write>(
             let _ = uart.write_str("  pwm.duty <percent>  - Set PWM duty cycle (0-100)\r\n");
             let _ = uart.write_str("  neo.color <r> <g> <b> - Set Neopixel RGB (0-255)\r\n");
             let _ = uart.write_str("  neo.off             - Turn Neopixel off\r\n");
+            let _ = uart.write_str("  neo.rainbow [speed] - Cycle the color wheel (1-60 °/tick, default 2)\r\n");
+            let _ = uart.write_str("  neo.blink <r> <g> <b> <hz> - Blink a color on and off (1-10 Hz)\r\n");
+            let _ = uart.write_str("  neo.stop            - Stop the animation, keep the current color\r\n");
             let _ = uart.write_str("  stream.start/stop   - Toggle streaming mode\r\n");
+            let _ = uart.write_str("  ping                - Reply pong <uptime_ms>\r\n");
+            let _ = uart.write_str("  echo <text>         - Echo text back\r\n");
         }
         "gpio.on" => {
-            led_gpio.set_high();
+            led_pwm.set_duty(100).ok();
             unsafe { PWM_DUTY = 100; }
             let _ = uart.write_str("OK [GPIO12 = HIGH]\r\n");
         }
         "gpio.off" => {
-            led_gpio.set_low();
+            led_pwm.set_duty(0).ok();
             unsafe { PWM_DUTY = 0; }
             let _ = uart.write_str("OK [GPIO12 = LOW]\r\n");
         }
+        "pwm.duty" => {
+            if parts.len() < 2 {
+                let _ = uart.write_str("ERROR: Usage: pwm.duty <percent>\r\n");
+            } else if let Ok(percent) = parts[1].parse::<u32>() {
+                // Anything above 100% is full on
+                let duty = percent.min(100) as u8;
+                if led_pwm.set_duty(duty).is_ok() {
+                    unsafe { PWM_DUTY = duty; }
+                    let mut buf: String<32> = String::new();
+                    write!(buf, "OK [PWM duty = {}%]\r\n", duty).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Failed to set PWM duty\r\n");
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid duty percent\r\n");
+            }
+        }
         "neo.color" => {
             if parts.len() < 4 {
                 let _ = uart.write_str("ERROR: Usage: neo.color <r> <g> <b>\r\n");
@@ -188,6 +361,8 @@ fn process_command<W: Write>(
             ) {
                 neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
                 unsafe {
+                    NEO_RAINBOW = false;
+                    NEO_BLINK_HZ = 0;
                     NEO_R = r;
                     NEO_G = g;
                     NEO_B = b;
@@ -202,12 +377,73 @@ fn process_command<W: Write>(
         "neo.off" => {
             neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok();
             unsafe {
+                NEO_RAINBOW = false;
+                NEO_BLINK_HZ = 0;
                 NEO_R = 0;
                 NEO_G = 0;
                 NEO_B = 0;
             }
             let _ = uart.write_str("OK [Neopixel OFF]\r\n");
         }
+        "neo.rainbow" => {
+            let speed = match parts.get(1) {
+                None => Some(RAINBOW_DEFAULT_SPEED),
+                Some(arg) => arg.parse::<u16>().ok().filter(|s| (1..=RAINBOW_MAX_SPEED).contains(s)),
+            };
+            if let Some(speed) = speed {
+                unsafe {
+                    NEO_RAINBOW_SPEED = speed;
+                    NEO_RAINBOW = true;
+                    NEO_BLINK_HZ = 0;
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Rainbow, {} °/tick]\r\n", speed).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Usage: neo.rainbow [speed 1-60]\r\n");
+            }
+        }
+        "neo.blink" => {
+            if parts.len() < 5 {
+                let _ = uart.write_str("ERROR: Usage: neo.blink <r> <g> <b> <hz>\r\n");
+            } else if let (Ok(r), Ok(g), Ok(b), Ok(hz)) = (
+                parts[1].parse::<u8>(),
+                parts[2].parse::<u8>(),
+                parts[3].parse::<u8>(),
+                parts[4].parse::<u8>(),
+            ) {
+                if (BLINK_MIN_HZ..=BLINK_MAX_HZ).contains(&hz) {
+                    // Light it now; the main loop toggles it from here
+                    neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
+                    unsafe {
+                        NEO_RAINBOW = false;
+                        NEO_BLINK_R = r;
+                        NEO_BLINK_G = g;
+                        NEO_BLINK_B = b;
+                        NEO_BLINK_LIT = true;
+                        NEO_BLINK_TOGGLE_MS = UPTIME_MS;
+                        NEO_BLINK_HZ = hz;
+                        NEO_R = r;
+                        NEO_G = g;
+                        NEO_B = b;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "OK [Blink RGB=({},{},{}) at {} Hz]\r\n", r, g, b, hz).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Blink rate must be 1-10 Hz\r\n");
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid RGB or Hz values\r\n");
+            }
+        }
+        "neo.stop" => {
+            unsafe {
+                NEO_RAINBOW = false;
+                NEO_BLINK_HZ = 0;
+            }
+            let _ = uart.write_str("OK [Animation stopped]\r\n");
+        }
         "stream.start" => {
             unsafe { MODE = Mode::Streaming; }
             let _ = uart.write_str("[Switching to streaming mode...]\r\n");
@@ -216,6 +452,11 @@ fn process_command<W: Write>(
             unsafe { MODE = Mode::CLI; }
             let _ = uart.write_str("[Switching to CLI mode...]\r\n");
         }
+        "ping" => {
+            let mut buf: String<32> = String::new();
+            write!(buf, "pong {}\r\n", unsafe { UPTIME_MS }).ok();
+            let _ = uart.write_str(&buf);
+        }
         _ => {
             let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
         }
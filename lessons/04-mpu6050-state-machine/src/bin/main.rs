@@ -1,22 +1,30 @@
 #![no_std]
 #![no_main]
 
+use cli::history::{Escape, History};
 use core::fmt::Write;
+use errcode::ErrorCode;
 use esp_backtrace as _;
 use esp_hal::{
+    clock::Clocks,
     delay::Delay,
     gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull},
     i2c::master::{Config as I2cConfig, I2c},
     main,
     rmt::Rmt,
-    time::Rate,
+    rtc_cntl::{reset_reason, Rtc, RwdtStage, SocResetReason},
+    time::{Duration, Instant, Rate},
+    system::{software_reset, Cpu},
     uart::{Config as UartConfig, Uart},
     Blocking,
 };
 use esp_hal_smartled::{buffer_size, color_order, SmartLedsAdapter, Ws2812Timing};
 use heapless::String;
 use lesson_04_mpu6050_state_machine as mpu;
-use log::info;
+use log::{error, info, warn};
+use mpu::fields;
+use mpu::frame::ImuFrame;
+use mpu::{AccelRange, DlpfConfig, IntPolarity};
 use smart_leds::{SmartLedsWrite, RGB8};
 
 esp_bootloader_esp_idf::esp_app_desc!();
@@ -29,11 +37,69 @@ const UART_TX_PIN: u8 = 23;
 const UART_RX_PIN: u8 = 15;
 const I2C_SDA_PIN: u8 = 2;
 const I2C_SCL_PIN: u8 = 11;
+// MPU6050 INT output (data ready); optional, see setup_data_ready
+const IMU_INT_PIN: u8 = 6;
 
 const UART_BAUD: u32 = 115200;
 const I2C_FREQ: u32 = 100_000; // 100 kHz for MPU6050
 const CMD_BUFFER_SIZE: usize = 128;
 
+// RTC watchdog (RWDT) timeout: fed once per main-loop iteration, so only a
+// hung loop (or sys.hang) lets it expire and reset the chip
+const WATCHDOG_TIMEOUT_MS: u64 = 3000;
+
+// sys.reset: wait this long after the confirmation so the UART TX FIFO
+// (128 bytes, ~11 ms at 115200 baud) drains before the chip resets
+const RESET_FLUSH_MS: u32 = 50;
+
+// Calibration is aborted as soon as any gyro axis exceeds this rate (the
+// device is being moved); gyro counts per °/s at the default ±250 °/s range
+const CAL_STILL_GYRO_DPS: f32 = 5.0;
+const GYRO_LSB_PER_DPS: f32 = 131.0;
+const CAL_ABORT_FLASH_MS: u32 = 500;
+
+// Sensor fault pattern: Monitoring without an MPU6050 blinks the Neopixel
+// magenta instead of showing blue over readings that never update
+const SENSOR_FAULT_COLOR: RGB8 = RGB8::new(30, 0, 30);
+const SENSOR_FAULT_BLINK_MS: u32 = 250;
+
+// setup_data_ready: the INT line must rise within this long (several sample
+// periods at the 1 kHz sample rate) to count as wired
+const IMU_INT_DETECT_MS: u32 = 10;
+
+// Every command process_command accepts; Tab completes against this list and
+// anything not in it is rejected as unknown before dispatch
+const COMMANDS: &[&str] = &[
+    "help",
+    "gpio.on",
+    "gpio.off",
+    "gpio.read",
+    "neo.color",
+    "neo.off",
+    "imu.init",
+    "imu.whoami",
+    "imu.addr",
+    "imu.read",
+    "imu.temp",
+    "imu.range",
+    "imu.dlpf",
+    "state.get",
+    "state.set",
+    "state.force",
+    "stream.start",
+    "stream.stop",
+    "stream.format",
+    "stream.fields",
+    "stream.bin",
+    "term.echo",
+    "sys.info",
+    "sys.hang",
+    "sys.reset",
+    "sys.profile",
+    "ping",
+    "echo",
+];
+
 // State machine
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
@@ -51,6 +117,23 @@ enum Mode {
     Streaming = 1,
 }
 
+// Streamed sample format (stream.format)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+enum StreamFormat {
+    Text = 0,   // [state=... accel=(..) ...]
+    Json = 1,   // one compact object per line
+    Binary = 2, // 18-byte frames, see src/frame.rs
+}
+
+/// Outputs driven by state transitions and CLI commands
+struct Hw<N> {
+    led: Output<'static>,
+    neopixel: N,
+    button: Input<'static>,
+    imu_int: Input<'static>,
+}
+
 // Global state variables (modifiable from GDB)
 #[no_mangle]
 static mut DEVICE_STATE: DeviceState = DeviceState::Sleep;
@@ -58,9 +141,54 @@ static mut DEVICE_STATE: DeviceState = DeviceState::Sleep;
 #[no_mangle]
 static mut MODE: Mode = Mode::CLI;
 
+#[no_mangle]
+static mut STREAM_FORMAT: StreamFormat = StreamFormat::Text;
+
+// stream.fields: bitmask of the columns in text/JSON lines (see fields.rs)
+#[no_mangle]
+static mut STREAM_FIELDS: u8 = fields::ALL;
+
+// Milliseconds since boot, read from the system timer at the top of each
+// loop iteration (wraps after ~49 days; compare with wrapping_sub)
 #[no_mangle]
 static mut UPTIME_MS: u32 = 0;
 
+// term.echo: echo typed characters back (off for terminals with local echo)
+#[no_mangle]
+static mut TERM_ECHO: bool = true;
+
+// sys.profile: print how long each command's handler took after its reply
+#[no_mangle]
+static mut PROFILE_COMMANDS: bool = false;
+
+// Fatal error blinked on the LED (see crates/errcode); cleared once the IMU answers again
+#[no_mangle]
+static mut FAULT: Option<ErrorCode> = None;
+
+// MPU6050 answered at init (or later on imu.init / imu.addr); while false,
+// Monitoring doesn't poll the bus and the Neopixel shows the sensor fault
+#[no_mangle]
+static mut IMU_PRESENT: bool = true;
+
+// Where Monitoring learns about new samples: true = the MPU INT line on
+// IMU_INT_PIN (no I2C traffic until a sample is waiting), false = polling
+// INT_STATUS over I2C. Set by setup_data_ready, depending on whether INT is wired.
+#[no_mangle]
+static mut IMU_INT_GPIO: bool = false;
+
+// MPU6050 I2C address (imu.addr): 0x68, or 0x69 with AD0 strapped high
+#[no_mangle]
+static mut IMU_ADDR: u8 = mpu::MPU_ADDR;
+
+// Accelerometer full-scale range (imu.range); ±2 g after power-up
+#[no_mangle]
+static mut ACCEL_RANGE: AccelRange = AccelRange::G2;
+
+// On-chip low-pass filter (imu.dlpf); wake_sensor resets it to the default,
+// so it is written again whenever the sensor is woken
+#[no_mangle]
+static mut DLPF: DlpfConfig = DlpfConfig::DEFAULT;
+
 #[no_mangle]
 static mut IMU_ACCEL_X: i16 = 0;
 #[no_mangle]
@@ -93,6 +221,11 @@ fn main() -> ! {
     info!("\n=== Lesson 04: MPU6050 + State Machine ===\n");
 
     let peripherals = esp_hal::init(esp_hal::Config::default());
+    info!(
+        "[INIT] CPU {} MHz, reset reason: {}",
+        Clocks::get().cpu_clock.as_mhz(),
+        reset_reason_str(reset_reason(Cpu::ProCpu))
+    );
     let mut delay = Delay::new();
 
     // Initialize UART
@@ -111,12 +244,31 @@ fn main() -> ! {
         .with_sda(peripherals.GPIO2)
         .with_scl(peripherals.GPIO11);
 
+    // MPU INT line, pulled down so it reads LOW (no data) when not connected
+    let imu_int = Input::new(peripherals.GPIO6, InputConfig::default().with_pull(Pull::Down));
+
     // Initialize MPU6050
+    let imu_addr = unsafe { IMU_ADDR };
     info!("[INIT] Waking MPU6050...");
-    if mpu::wake_sensor(&mut i2c).is_ok() {
+    let detected = mpu::wake_sensor(&mut i2c, imu_addr).and_then(|()| {
         delay.delay_millis(100);
-        if let Ok(who_am_i) = mpu::read_who_am_i(&mut i2c) {
+        mpu::probe(&mut i2c, imu_addr)
+    });
+    match detected.map_err(ErrorCode::from) {
+        Ok(who_am_i) => {
             info!("[INIT] MPU WHO_AM_I = 0x{:02X}", who_am_i);
+            setup_data_ready(&mut i2c, imu_addr, &imu_int);
+        }
+        Err(code) => {
+            warn!("[INIT] MPU6050 NOT DETECTED at 0x{:02X}: {}", imu_addr, code.description());
+            error!("[FAULT] {} (LED blinks {}x)", code.description(), code.blinks());
+            let mut buf: String<96> = String::new();
+            write!(buf, "FAULT: {} (LED blinks {}x)\r\n", code.description(), code.blinks()).ok();
+            let _ = uart.write_str(&buf);
+            unsafe {
+                FAULT = Some(code);
+                IMU_PRESENT = false;
+            }
         }
     }
 
@@ -126,102 +278,143 @@ fn main() -> ! {
 
     // Initialize LED
     info!("[INIT] Configuring LED (GPIO{})...", LED_PIN);
-    let mut led = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
+    let led = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
 
     // Initialize Neopixel
     info!("[INIT] Configuring Neopixel (GPIO{})...", NEOPIXEL_PIN);
     let rmt = Rmt::new(peripherals.RMT, Rate::from_mhz(80)).expect("Failed to init RMT");
-    let mut neopixel = SmartLedsAdapter::<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>::new_with_memsize(
+    let neopixel = SmartLedsAdapter::<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>::new_with_memsize(
         rmt.channel0,
         peripherals.GPIO8,
         2,
     )
     .expect("Failed to create SmartLedsAdapter");
 
+    let mut hw = Hw {
+        led,
+        neopixel,
+        button,
+        imu_int,
+    };
+
+    // Initialize watchdog (esp_hal::init() leaves it disabled)
+    info!("[INIT] Watchdog (RWDT, {} ms)...", WATCHDOG_TIMEOUT_MS);
+    let mut rtc = Rtc::new(peripherals.LPWR);
+    rtc.rwdt.set_timeout(RwdtStage::Stage0, Duration::from_millis(WATCHDOG_TIMEOUT_MS));
+    rtc.rwdt.enable();
+
     info!("[INIT] All peripherals ready\n");
 
     let _ = uart.write_str("\r\n=== ESP32-C6 CLI (Lesson 04) ===\r\n");
     let _ = uart.write_str("Commands: help, gpio.*, neo.*, imu.*, state.*, stream.*\r\n\r\n> ");
 
     let mut cmd_buffer: String<CMD_BUFFER_SIZE> = String::new();
+    // Set when a line outgrew cmd_buffer; the rest of it is ignored until Enter
+    let mut line_too_long = false;
+    let mut history: History<CMD_BUFFER_SIZE> = History::new();
+    let mut escape = Escape::Idle;
     let mut counter: u32 = 0;
+    let mut seq: u32 = 0; // per emitted line, for host-side gap detection
     let mut last_stream_time_ms: u32 = 0;
-    let mut button_last_state = button.is_high();
+    let mut button_last_state = hw.button.is_high();
+    let mut sensor_fault_lit = false;
 
     // Calibration accumulators
     let mut cal_accel_x_sum: i32 = 0;
     let mut cal_accel_y_sum: i32 = 0;
     let mut cal_accel_z_sum: i32 = 0;
+    // State to go back to if calibration is aborted, and the red flash shown then
+    let mut last_state = unsafe { DEVICE_STATE };
+    let mut cal_return_state = DeviceState::Monitoring;
+    let mut cal_abort_flash_ms: Option<u32> = None;
 
     loop {
+        rtc.rwdt.feed();
+        let imu_addr = unsafe { IMU_ADDR };
         unsafe {
-            UPTIME_MS = UPTIME_MS.wrapping_add(10);
+            UPTIME_MS = Instant::now().duration_since_epoch().as_millis() as u32;
         }
 
         // Handle button press for state transitions
-        let button_current = button.is_high();
+        let button_current = hw.button.is_high();
         if button_last_state && !button_current {
             delay.delay_millis(50); // Debounce
-            if button.is_low() {
-                // Cycle states: Sleep → Monitoring → Calibrating → Monitoring
-                unsafe {
-                    DEVICE_STATE = match DEVICE_STATE {
-                        DeviceState::Sleep => {
-                            neopixel.write([RGB8::new(0, 0, 30)].into_iter()).ok(); // Blue for monitoring
-                            NEO_R = 0; NEO_G = 0; NEO_B = 30;
-                            info!("[STATE] Sleep → Monitoring");
-                            DeviceState::Monitoring
-                        }
-                        DeviceState::Monitoring => {
-                            neopixel.write([RGB8::new(30, 30, 0)].into_iter()).ok(); // Yellow for calibrating
-                            NEO_R = 30; NEO_G = 30; NEO_B = 0;
-                            CALIBRATION_SAMPLES = 0;
-                            cal_accel_x_sum = 0;
-                            cal_accel_y_sum = 0;
-                            cal_accel_z_sum = 0;
-                            info!("[STATE] Monitoring → Calibrating");
-                            DeviceState::Calibrating
-                        }
-                        DeviceState::Calibrating => {
-                            neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok(); // Off for sleep
-                            NEO_R = 0; NEO_G = 0; NEO_B = 0;
-                            info!("[STATE] Calibrating → Sleep");
-                            DeviceState::Sleep
-                        }
-                    };
-                }
+            if hw.button.is_low() {
+                // Cycle states: Sleep → Monitoring → Calibrating → Sleep
+                let next = match unsafe { DEVICE_STATE } {
+                    DeviceState::Sleep => DeviceState::Monitoring,
+                    DeviceState::Monitoring => DeviceState::Calibrating,
+                    DeviceState::Calibrating => DeviceState::Sleep,
+                };
+                transition_to(next, &mut hw);
             }
         }
         button_last_state = button_current;
 
         // State machine behavior
         let current_state = unsafe { DEVICE_STATE };
+        if current_state == DeviceState::Calibrating && last_state != DeviceState::Calibrating {
+            cal_return_state = last_state;
+        }
+        last_state = current_state;
         match current_state {
             DeviceState::Sleep => {
                 // Minimal activity in sleep
             }
+            DeviceState::Monitoring if !unsafe { IMU_PRESENT } => {
+                // Nothing to read: blink the sensor fault, repainting only on edges
+                let lit = unsafe { UPTIME_MS } / SENSOR_FAULT_BLINK_MS % 2 == 0;
+                if lit != sensor_fault_lit {
+                    let color = if lit { SENSOR_FAULT_COLOR } else { RGB8::new(0, 0, 0) };
+                    hw.neopixel.write([color].into_iter()).ok();
+                    sensor_fault_lit = lit;
+                }
+            }
             DeviceState::Monitoring => {
-                // Read IMU periodically
-                if let Ok(accel) = mpu::read_accel(&mut i2c) {
-                    unsafe {
-                        IMU_ACCEL_X = accel.x;
-                        IMU_ACCEL_Y = accel.y;
-                        IMU_ACCEL_Z = accel.z;
+                // Read IMU only when the sensor has a fresh sample: INT held high
+                // until the reads below, or INT_STATUS.DATA_RDY if INT isn't wired
+                let fresh = if unsafe { IMU_INT_GPIO } {
+                    hw.imu_int.is_high()
+                } else {
+                    matches!(mpu::data_ready(&mut i2c, imu_addr), Ok(true))
+                };
+                if fresh {
+                    if let Ok(accel) = mpu::read_accel(&mut i2c, imu_addr) {
+                        unsafe {
+                            IMU_ACCEL_X = accel.x;
+                            IMU_ACCEL_Y = accel.y;
+                            IMU_ACCEL_Z = accel.z;
+                        }
                     }
-                }
-                if let Ok(gyro) = mpu::read_gyro(&mut i2c) {
-                    unsafe {
-                        IMU_GYRO_X = gyro.x;
-                        IMU_GYRO_Y = gyro.y;
-                        IMU_GYRO_Z = gyro.z;
+                    if let Ok(gyro) = mpu::read_gyro(&mut i2c, imu_addr) {
+                        unsafe {
+                            IMU_GYRO_X = gyro.x;
+                            IMU_GYRO_Y = gyro.y;
+                            IMU_GYRO_Z = gyro.z;
+                        }
                     }
                 }
             }
             DeviceState::Calibrating => {
                 // Collect calibration samples
-                if let Ok(accel) = mpu::read_accel(&mut i2c) {
+                if let Ok(accel) = mpu::read_accel(&mut i2c, imu_addr) {
+                    let still_limit = (CAL_STILL_GYRO_DPS * GYRO_LSB_PER_DPS) as i32;
+                    let moving = mpu::read_gyro(&mut i2c, imu_addr)
+                        .is_ok_and(|g| [g.x, g.y, g.z].iter().any(|&v| (v as i32).abs() > still_limit));
                     unsafe {
-                        if CALIBRATION_SAMPLES < 100 {
+                        // Fresh run (button, CLI or GDB reset the counter): clear the sums
+                        if CALIBRATION_SAMPLES == 0 {
+                            cal_accel_x_sum = 0;
+                            cal_accel_y_sum = 0;
+                            cal_accel_z_sum = 0;
+                        }
+                        if moving {
+                            // All 100 samples must be still; flash red and go back
+                            info!("[CALIB] Aborted: device moving");
+                            transition_to(cal_return_state, &mut hw);
+                            hw.neopixel.write([RGB8::new(30, 0, 0)].into_iter()).ok();
+                            cal_abort_flash_ms = Some(UPTIME_MS);
+                        } else if CALIBRATION_SAMPLES < 100 {
                             cal_accel_x_sum += accel.x as i32;
                             cal_accel_y_sum += accel.y as i32;
                             cal_accel_z_sum += accel.z as i32;
@@ -231,9 +424,7 @@ fn main() -> ! {
                                 info!("[CALIB] Complete! Offsets: x={}, y={}, z={}",
                                       cal_accel_x_sum / 100, cal_accel_y_sum / 100, cal_accel_z_sum / 100);
                                 // Auto-transition back to Monitoring
-                                DEVICE_STATE = DeviceState::Monitoring;
-                                neopixel.write([RGB8::new(0, 0, 30)].into_iter()).ok();
-                                NEO_R = 0; NEO_G = 0; NEO_B = 30;
+                                transition_to(DeviceState::Monitoring, &mut hw);
                             }
                         }
                     }
@@ -241,6 +432,24 @@ fn main() -> ! {
             }
         }
 
+        // Aborted calibration: red for a moment, then the color transition_to left
+        if let Some(start) = cal_abort_flash_ms {
+            if unsafe { UPTIME_MS }.wrapping_sub(start) >= CAL_ABORT_FLASH_MS {
+                cal_abort_flash_ms = None;
+                let (r, g, b) = unsafe { (NEO_R, NEO_G, NEO_B) };
+                hw.neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
+            }
+        }
+
+        // A fatal error owns the LED: blink its code instead of gpio.on/off
+        if let Some(code) = unsafe { FAULT } {
+            if code.led_on(unsafe { UPTIME_MS }) {
+                hw.led.set_high();
+            } else {
+                hw.led.set_low();
+            }
+        }
+
         // Mode handling (CLI vs Streaming)
         let current_mode = unsafe { MODE };
         match current_mode {
@@ -249,20 +458,48 @@ fn main() -> ! {
                 if uart.read(&mut rx_byte).is_ok() {
                     let ch = rx_byte[0] as char;
 
-                    if ch == '\r' || ch == '\n' {
+                    if escape == Escape::Esc {
+                        escape = if ch == '[' { Escape::Csi } else { Escape::Idle };
+                    } else if escape == Escape::Csi {
+                        // Final byte: A = up, B = down; other keys (left/right...) are ignored
+                        escape = Escape::Idle;
+                        match ch {
+                            'A' => recall(history.older(), &mut cmd_buffer, &mut uart),
+                            'B' => recall(history.newer(), &mut cmd_buffer, &mut uart),
+                            _ => {}
+                        }
+                    } else if ch == '\x1b' {
+                        escape = Escape::Esc;
+                    } else if ch == '\t' {
+                        complete_command(&mut cmd_buffer, &mut uart);
+                    } else if ch == '\r' || ch == '\n' {
+                        line_too_long = false;
                         if !cmd_buffer.is_empty() {
                             let _ = uart.write_str("\r\n");
-                            process_command(&cmd_buffer, &mut led, &mut neopixel, &mut i2c, &mut uart);
+                            history.push(&cmd_buffer);
+                            let started = Instant::now();
+                            process_command(&cmd_buffer, &mut hw, &mut i2c, &mut uart);
+                            if unsafe { PROFILE_COMMANDS } {
+                                let mut buf: String<32> = String::new();
+                                write!(buf, "(took {} us)\r\n", started.elapsed().as_micros()).ok();
+                                let _ = uart.write_str(&buf);
+                            }
                             cmd_buffer.clear();
                             let _ = uart.write_str("> ");
                         }
                     } else if ch == '\x08' || ch == '\x7f' {
-                        if cmd_buffer.pop().is_some() {
+                        if cmd_buffer.pop().is_some() && unsafe { TERM_ECHO } {
                             let _ = uart.write_str("\x08 \x08");
                         }
+                    } else if line_too_long {
+                        // Rest of an overlong line, already reported
                     } else if ch.is_ascii_graphic() || ch == ' ' {
-                        let _ = uart.write(&[ch as u8]);
-                        let _ = cmd_buffer.push(ch);
+                        if cmd_buffer.push(ch).is_err() {
+                            line_too_long = true;
+                            report_line_too_long(&mut cmd_buffer, &mut uart);
+                        } else if unsafe { TERM_ECHO } {
+                            let _ = uart.write(&[ch as u8]);
+                        }
                     }
                 }
             }
@@ -271,7 +508,13 @@ fn main() -> ! {
                 if current_time_ms.wrapping_sub(last_stream_time_ms) >= 100 {
                     last_stream_time_ms = current_time_ms;
                     counter = counter.wrapping_add(1);
+                    seq = seq.wrapping_add(1);
+                    let format = unsafe { STREAM_FORMAT };
+                    if seq == 0 && format != StreamFormat::Binary {
+                        let _ = uart.write_str("[seq wrapped]\r\n");
+                    }
 
+                    // Worst-case JSON line (all fields at their widest) is 167 bytes
                     let mut msg: String<256> = String::new();
                     let (state, ax, ay, az, gx, gy, gz, r, g, b, cal) = unsafe {
                         (
@@ -289,12 +532,37 @@ fn main() -> ! {
                         )
                     };
 
-                    write!(
-                        msg,
-                        "[state={:?} accel=({},{},{}) gyro=({},{},{}) neo=({},{},{}) cal={} cnt={} t={}]\r\n",
-                        state, ax, ay, az, gx, gy, gz, r, g, b, cal, counter, current_time_ms
-                    )
-                    .ok();
+                    if format == StreamFormat::Binary {
+                        let frame = ImuFrame::new(current_time_ms, [ax, ay, az], [gx, gy, gz]);
+                        let _ = uart.write(&frame.to_bytes());
+                    } else {
+                        // Selected fields only, in fields::NAMES order
+                        let json = format == StreamFormat::Json;
+                        let (open, close) = if json { ('[', ']') } else { ('(', ')') };
+                        let selected = unsafe { STREAM_FIELDS };
+                        msg.push(if json { '{' } else { '[' }).ok();
+                        for (name, bit) in fields::NAMES {
+                            if selected & bit == 0 {
+                                continue;
+                            }
+                            if msg.len() > 1 {
+                                msg.push(if json { ',' } else { ' ' }).ok();
+                            }
+                            let _ = if json { write!(msg, "\"{}\":", name) } else { write!(msg, "{}=", name) };
+                            let _ = match bit {
+                                fields::STATE if json => write!(msg, "\"{:?}\"", state),
+                                fields::STATE => write!(msg, "{:?}", state),
+                                fields::ACCEL => write!(msg, "{}{},{},{}{}", open, ax, ay, az, close),
+                                fields::GYRO => write!(msg, "{}{},{},{}{}", open, gx, gy, gz, close),
+                                fields::NEO => write!(msg, "{}{},{},{}{}", open, r, g, b, close),
+                                fields::CAL => write!(msg, "{}", cal),
+                                fields::SEQ => write!(msg, "{}", seq),
+                                fields::CNT => write!(msg, "{}", counter),
+                                _ => write!(msg, "{}", current_time_ms),
+                            };
+                        }
+                        msg.push_str(if json { "}\r\n" } else { "]\r\n" }).ok();
+                    }
 
                     let _ = uart.write_str(&msg);
                 }
@@ -305,41 +573,148 @@ fn main() -> ! {
     }
 }
 
-fn process_command<W: Write, Dm: esp_hal::DriverMode>(
+/// The line outgrew the command buffer: ring the bell, drop it and redraw
+/// the prompt. The rest of the line is ignored until Enter.
+fn report_line_too_long<W: Write>(cmd_buffer: &mut String<CMD_BUFFER_SIZE>, uart: &mut W) {
+    cmd_buffer.clear();
+    let mut buf: String<64> = String::new();
+    write!(buf, "\x07\r\nERROR: command too long (max {} characters)\r\n> ", CMD_BUFFER_SIZE).ok();
+    let _ = uart.write_str(&buf);
+}
+
+/// Replace the line being edited with a history entry and redraw it
+fn recall<W: Write>(entry: Option<&str>, cmd_buffer: &mut String<CMD_BUFFER_SIZE>, uart: &mut W) {
+    if let Some(entry) = entry {
+        cmd_buffer.clear();
+        let _ = cmd_buffer.push_str(entry);
+        let _ = uart.write_str("\r\x1b[K> ");
+        let _ = uart.write_str(cmd_buffer);
+    }
+}
+
+/// Tab: complete the command name being typed against `COMMANDS`
+///
+/// One match fills in the rest; several are listed on a new line and the
+/// prompt is redrawn with the buffer unchanged. Arguments aren't completed.
+fn complete_command<W: Write>(cmd_buffer: &mut String<CMD_BUFFER_SIZE>, uart: &mut W) {
+    if cmd_buffer.contains(' ') {
+        return;
+    }
+    let prefix = cmd_buffer.as_str();
+    let mut matches = COMMANDS.iter().filter(|c| c.starts_with(prefix));
+    match (matches.next(), matches.next()) {
+        (None, _) => {}
+        (Some(cmd), None) => {
+            let rest = &cmd[prefix.len()..];
+            let _ = uart.write_str(rest);
+            let _ = cmd_buffer.push_str(rest);
+        }
+        (Some(first), Some(second)) => {
+            let _ = uart.write_str("\r\n");
+            for cmd in [first, second].into_iter().chain(matches) {
+                let _ = uart.write_str(cmd);
+                let _ = uart.write_str("  ");
+            }
+            let _ = uart.write_str("\r\n> ");
+            let _ = uart.write_str(cmd_buffer);
+        }
+    }
+}
+
+fn process_command<W: Write, Dm: esp_hal::DriverMode, N>(
     cmd: &str,
-    led: &mut Output,
-    neopixel: &mut SmartLedsAdapter<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>,
+    hw: &mut Hw<N>,
     i2c: &mut I2c<Dm>,
     uart: &mut W,
-) {
+) where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
     let cmd_trimmed = cmd.trim();
+    let imu_addr = unsafe { IMU_ADDR };
+
+    // `echo` takes free text, so handle it before splitting into a fixed-size arg list
+    if cmd_trimmed == "echo" || cmd_trimmed.starts_with("echo ") {
+        let _ = uart.write_str(cmd_trimmed[4..].trim_start());
+        let _ = uart.write_str("\r\n");
+        return;
+    }
+
     let parts: heapless::Vec<&str, 5> = cmd_trimmed.split_whitespace().collect();
 
     if parts.is_empty() {
         return;
     }
+    if !COMMANDS.contains(&parts[0]) {
+        let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
+        return;
+    }
 
     match parts[0] {
         "help" => {
             let _ = uart.write_str("Commands:\r\n");
             let _ = uart.write_str("  gpio.on/off         - LED control\r\n");
+            let _ = uart.write_str("  gpio.read <pin>     - Read an input pin (9 = button, 6 = MPU INT)\r\n");
             let _ = uart.write_str("  neo.color <r> <g> <b> - Set Neopixel\r\n");
             let _ = uart.write_str("  neo.off             - Neopixel off\r\n");
             let _ = uart.write_str("  imu.init            - Wake MPU6050\r\n");
             let _ = uart.write_str("  imu.whoami          - Read WHO_AM_I\r\n");
+            let _ = uart.write_str("  imu.addr [0x68|0x69] - Show or set the MPU6050 I2C address\r\n");
             let _ = uart.write_str("  imu.read            - Read accel/gyro\r\n");
+            let _ = uart.write_str("  imu.temp            - Read die temperature\r\n");
+            let _ = uart.write_str("  imu.range <2|4|8|16> - Set accel full-scale range (g)\r\n");
+            let _ = uart.write_str("  imu.dlpf [0-6]      - Show or set the on-chip low-pass filter (0 = 260 Hz .. 6 = 5 Hz)\r\n");
             let _ = uart.write_str("  state.get           - Get current state\r\n");
             let _ = uart.write_str("  state.set <state>   - Set state (sleep/monitor/calib)\r\n");
+            let _ = uart.write_str("  state.force <state> - Alias for state.set\r\n");
             let _ = uart.write_str("  stream.start/stop   - Toggle streaming\r\n");
+            let _ = uart.write_str("  stream.format <text|json|bin> - Streamed sample format\r\n");
+            let _ = uart.write_str("  stream.fields [list|all] - Text/JSON columns, e.g. accel,gyro,t\r\n");
+            let _ = uart.write_str("  stream.bin          - Stream binary frames (stream.format bin + start)\r\n");
+            let _ = uart.write_str("  ping                - Reply pong <uptime_ms>\r\n");
+            let _ = uart.write_str("  echo <text>         - Echo text back\r\n");
+            let _ = uart.write_str("  term.echo <on|off>  - Echo typed characters (off if the terminal echoes)\r\n");
+            let _ = uart.write_str("  sys.info            - CPU clock, reset reason, uptime\r\n");
+            let _ = uart.write_str("  sys.hang            - Spin forever; the watchdog resets the chip after ~3 s\r\n");
+            let _ = uart.write_str("  sys.reset           - Restart the chip (software reset)\r\n");
+            let _ = uart.write_str("  sys.profile <on|off> - Print each command's execution time\r\n");
         }
         "gpio.on" => {
-            led.set_high();
+            hw.led.set_high();
             let _ = uart.write_str("OK [LED ON]\r\n");
         }
         "gpio.off" => {
-            led.set_low();
+            hw.led.set_low();
             let _ = uart.write_str("OK [LED OFF]\r\n");
         }
+        "gpio.read" => match parts.get(1).map(|p| p.parse::<u8>()) {
+            Some(Ok(BUTTON_PIN)) => {
+                // Pulled up: the button reads LOW while pressed
+                let level = if hw.button.is_high() { "HIGH (released)" } else { "LOW (pressed)" };
+                let mut buf: String<48> = String::new();
+                write!(buf, "GPIO{} = {}\r\n", BUTTON_PIN, level).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(IMU_INT_PIN)) => {
+                // Held high from a new sample until it is read
+                let level = if hw.imu_int.is_high() { "HIGH (sample waiting)" } else { "LOW" };
+                let mut buf: String<48> = String::new();
+                write!(buf, "GPIO{} = {}\r\n", IMU_INT_PIN, level).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(pin)) if [LED_PIN, NEOPIXEL_PIN, UART_TX_PIN].contains(&pin) => {
+                // Its input path isn't enabled; reading it would just return garbage
+                let mut buf: String<80> = String::new();
+                write!(buf, "ERROR: GPIO{} is configured as an output, can't read it\r\n", pin).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(_)) => {
+                let _ = uart.write_str("ERROR: Unsupported pin (inputs: 9, 6)\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: gpio.read <pin>\r\n");
+            }
+        },
         "neo.color" => {
             if parts.len() < 4 {
                 let _ = uart.write_str("ERROR: Usage: neo.color <r> <g> <b>\r\n");
@@ -348,7 +723,7 @@ fn process_command<W: Write, Dm: esp_hal::DriverMode>(
                 parts[2].parse::<u8>(),
                 parts[3].parse::<u8>(),
             ) {
-                neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
+                hw.neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
                 unsafe {
                     NEO_R = r;
                     NEO_G = g;
@@ -360,7 +735,7 @@ fn process_command<W: Write, Dm: esp_hal::DriverMode>(
             }
         }
         "neo.off" => {
-            neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok();
+            hw.neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok();
             unsafe {
                 NEO_R = 0;
                 NEO_G = 0;
@@ -369,23 +744,74 @@ fn process_command<W: Write, Dm: esp_hal::DriverMode>(
             let _ = uart.write_str("OK [Neopixel OFF]\r\n");
         }
         "imu.init" => {
-            if mpu::wake_sensor(i2c).is_ok() {
+            if mpu::wake_sensor(i2c, imu_addr).is_ok() {
+                mpu::set_dlpf(i2c, imu_addr, unsafe { DLPF }).ok();
+                if mpu::probe(i2c, imu_addr).is_ok() {
+                    setup_data_ready(i2c, imu_addr, &hw.imu_int);
+                    if unsafe { FAULT }.is_some() {
+                        unsafe {
+                            FAULT = None;
+                        }
+                        hw.led.set_low();
+                    }
+                    mark_imu_present(hw);
+                }
                 let _ = uart.write_str("OK [MPU6050 woken]\r\n");
             } else {
                 let _ = uart.write_str("ERROR: Failed to wake MPU6050\r\n");
             }
         }
         "imu.whoami" => {
-            if let Ok(who_am_i) = mpu::read_who_am_i(i2c) {
-                let mut buf: String<64> = String::new();
-                write!(buf, "WHO_AM_I = 0x{:02X}\r\n", who_am_i).ok();
-                let _ = uart.write_str(&buf);
+            let mut buf: String<64> = String::new();
+            match mpu::read_who_am_i(i2c, imu_addr).map_err(ErrorCode::from) {
+                Ok(who_am_i) => write!(buf, "WHO_AM_I = 0x{:02X}\r\n", who_am_i).ok(),
+                Err(code) => write!(buf, "ERROR: {}\r\n", code.description()).ok(),
+            };
+            let _ = uart.write_str(&buf);
+        }
+        "imu.addr" => {
+            let new_addr = match parts.get(1).copied() {
+                None => {
+                    let mut buf: String<32> = String::new();
+                    write!(buf, "IMU address = 0x{:02X}\r\n", imu_addr).ok();
+                    let _ = uart.write_str(&buf);
+                    return;
+                }
+                Some("0x68") => Some(mpu::MPU_ADDR),
+                Some("0x69") => Some(mpu::MPU_ADDR_ALT),
+                Some(_) => None,
+            };
+            if let Some(addr) = new_addr {
+                // Only switch if a sensor answers there, so a typo can't orphan the IMU
+                if mpu::wake_sensor(i2c, addr).is_ok() {
+                    mpu::set_accel_range(i2c, addr, unsafe { ACCEL_RANGE }).ok();
+                    mpu::set_dlpf(i2c, addr, unsafe { DLPF }).ok();
+                    setup_data_ready(i2c, addr, &hw.imu_int);
+                    unsafe {
+                        IMU_ADDR = addr;
+                    }
+                    // A sensor answering again clears a fault from the old address
+                    if unsafe { FAULT }.is_some() {
+                        unsafe {
+                            FAULT = None;
+                        }
+                        hw.led.set_low();
+                    }
+                    mark_imu_present(hw);
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "OK [IMU address=0x{:02X}]\r\n", addr).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "ERROR: No MPU6050 at 0x{:02X}\r\n", addr).ok();
+                    let _ = uart.write_str(&buf);
+                }
             } else {
-                let _ = uart.write_str("ERROR: I2C read failed\r\n");
+                let _ = uart.write_str("ERROR: Usage: imu.addr [0x68|0x69]\r\n");
             }
         }
         "imu.read" => {
-            if let (Ok(accel), Ok(gyro)) = (mpu::read_accel(i2c), mpu::read_gyro(i2c)) {
+            if let (Ok(accel), Ok(gyro)) = (mpu::read_accel(i2c, imu_addr), mpu::read_gyro(i2c, imu_addr)) {
                 let mut buf: String<128> = String::new();
                 write!(
                     buf,
@@ -398,40 +824,89 @@ fn process_command<W: Write, Dm: esp_hal::DriverMode>(
                 let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
             }
         }
+        "imu.temp" => {
+            if let Ok(temp) = mpu::read_temp(i2c, imu_addr) {
+                let mut buf: String<32> = String::new();
+                write!(buf, "temp: {:.1}°C\r\n", temp).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+            }
+        }
+        "imu.range" => {
+            let range = match parts.get(1).copied() {
+                Some("2") => Some(AccelRange::G2),
+                Some("4") => Some(AccelRange::G4),
+                Some("8") => Some(AccelRange::G8),
+                Some("16") => Some(AccelRange::G16),
+                _ => None,
+            };
+            if let Some(range) = range {
+                if mpu::set_accel_range(i2c, imu_addr, range).is_ok() {
+                    unsafe {
+                        ACCEL_RANGE = range;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(
+                        buf,
+                        "OK [Accel range ±{}g, {} LSB/g]\r\n",
+                        2u32 << range as u32,
+                        mpu::accel_sensitivity(range) as u32
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: I2C write failed\r\n");
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Usage: imu.range <2|4|8|16>\r\n");
+            }
+        }
+        "imu.dlpf" => match parts.get(1).map(|a| a.parse::<u8>().ok().and_then(DlpfConfig::from_index)) {
+            None => {
+                let dlpf = unsafe { DLPF };
+                let mut buf: String<48> = String::new();
+                write!(buf, "dlpf = {} ({} Hz)\r\n", dlpf as u8, dlpf.bandwidth_hz()).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Some(dlpf)) => {
+                if mpu::set_dlpf(i2c, imu_addr, dlpf).is_ok() {
+                    unsafe {
+                        DLPF = dlpf;
+                    }
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "OK [DLPF {} = {} Hz]\r\n", dlpf as u8, dlpf.bandwidth_hz()).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: I2C write failed\r\n");
+                }
+            }
+            Some(None) => {
+                let _ = uart.write_str("ERROR: Usage: imu.dlpf <0-6>\r\n");
+            }
+        },
         "state.get" => {
             let state = unsafe { DEVICE_STATE };
             let mut buf: String<64> = String::new();
             write!(buf, "State = {:?}\r\n", state).ok();
             let _ = uart.write_str(&buf);
         }
-        "state.set" => {
+        "state.set" | "state.force" => {
+            let target = match parts.get(1) {
+                Some(&"sleep") => Some(DeviceState::Sleep),
+                Some(&"monitor") => Some(DeviceState::Monitoring),
+                Some(&"calib") => Some(DeviceState::Calibrating),
+                _ => None,
+            };
             if parts.len() < 2 {
                 let _ = uart.write_str("ERROR: Usage: state.set <sleep|monitor|calib>\r\n");
+            } else if let Some(state) = target {
+                transition_to(state, hw);
+                let mut buf: String<64> = String::new();
+                write!(buf, "OK [State = {:?}]\r\n", state).ok();
+                let _ = uart.write_str(&buf);
             } else {
-                match parts[1] {
-                    "sleep" => {
-                        unsafe {
-                            DEVICE_STATE = DeviceState::Sleep;
-                        }
-                        let _ = uart.write_str("OK [State = Sleep]\r\n");
-                    }
-                    "monitor" => {
-                        unsafe {
-                            DEVICE_STATE = DeviceState::Monitoring;
-                        }
-                        let _ = uart.write_str("OK [State = Monitoring]\r\n");
-                    }
-                    "calib" => {
-                        unsafe {
-                            DEVICE_STATE = DeviceState::Calibrating;
-                            CALIBRATION_SAMPLES = 0;
-                        }
-                        let _ = uart.write_str("OK [State = Calibrating]\r\n");
-                    }
-                    _ => {
-                        let _ = uart.write_str("ERROR: Unknown state\r\n");
-                    }
-                }
+                let _ = uart.write_str("ERROR: Unknown state\r\n");
             }
         }
         "stream.start" => {
@@ -446,12 +921,217 @@ fn process_command<W: Write, Dm: esp_hal::DriverMode>(
             }
             let _ = uart.write_str("[Switching to CLI mode...]\r\n");
         }
+        "stream.format" => {
+            let format = match parts.get(1).copied() {
+                Some("text") => Some(StreamFormat::Text),
+                Some("json") => Some(StreamFormat::Json),
+                Some("bin") => Some(StreamFormat::Binary),
+                _ => None,
+            };
+            if let Some(format) = format {
+                unsafe {
+                    STREAM_FORMAT = format;
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Stream format = {:?}]\r\n", format).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Usage: stream.format <text|json|bin>\r\n");
+            }
+        }
+        "stream.fields" => match parts.get(1).map(|list| fields::parse(list)) {
+            None => {
+                let selected = unsafe { STREAM_FIELDS };
+                let mut buf: String<64> = String::new();
+                buf.push_str("Stream fields:").ok();
+                for (name, bit) in fields::NAMES {
+                    if selected & bit != 0 {
+                        buf.push(' ').ok();
+                        buf.push_str(name).ok();
+                    }
+                }
+                buf.push_str("\r\n").ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(mask)) => {
+                unsafe {
+                    STREAM_FIELDS = mask;
+                }
+                let mut buf: String<96> = String::new();
+                write!(buf, "OK [Stream fields = {}]\r\n", parts[1]).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Err(name)) => {
+                let mut buf: String<224> = String::new();
+                write!(
+                    buf,
+                    "ERROR: Unknown field '{}' (state,accel,gyro,neo,cal,seq,cnt,t or all)\r\n",
+                    name
+                )
+                .ok();
+                let _ = uart.write_str(&buf);
+            }
+        },
+        "stream.bin" => {
+            unsafe {
+                STREAM_FORMAT = StreamFormat::Binary;
+                MODE = Mode::Streaming;
+            }
+            let _ = uart.write_str("[Switching to binary streaming...]\r\n");
+        }
+        "term.echo" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    TERM_ECHO = true;
+                }
+                let _ = uart.write_str("OK [Echo on]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    TERM_ECHO = false;
+                }
+                let _ = uart.write_str("OK [Echo off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: term.echo <on|off>\r\n");
+            }
+        },
+        "ping" => {
+            let mut buf: String<32> = String::new();
+            write!(buf, "pong {}\r\n", unsafe { UPTIME_MS }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "sys.info" => {
+            let mut buf: String<64> = String::new();
+            write!(buf, "cpu_freq = {} MHz\r\n", Clocks::get().cpu_clock.as_mhz()).ok();
+            let _ = uart.write_str(&buf);
+            buf.clear();
+            write!(buf, "reset_reason = {}\r\n", reset_reason_str(reset_reason(Cpu::ProCpu))).ok();
+            let _ = uart.write_str(&buf);
+            buf.clear();
+            write!(buf, "uptime_ms = {}\r\n", unsafe { UPTIME_MS }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "sys.hang" => {
+            let _ = uart.write_str("Hanging, expect a watchdog reset in ~3 s\r\n");
+            loop {
+                core::hint::spin_loop();
+            }
+        }
+        "sys.profile" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    PROFILE_COMMANDS = true;
+                }
+                let _ = uart.write_str("OK [Command profiling on]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    PROFILE_COMMANDS = false;
+                }
+                let _ = uart.write_str("OK [Command profiling off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: sys.profile <on|off>\r\n");
+            }
+        },
+        "sys.reset" => {
+            let _ = uart.write_str("Resetting...\r\n");
+            // Give the UART time to shift the line out before the reset cuts it off
+            Delay::new().delay_millis(RESET_FLUSH_MS);
+            software_reset();
+        }
         _ => {
             let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
         }
     }
 }
 
+/// Why the chip last reset, as shown by sys.info and the boot log
+fn reset_reason_str(reason: Option<SocResetReason>) -> &'static str {
+    match reason {
+        // The ROM reports brownout and super-watchdog chip resets as power-on too
+        Some(SocResetReason::ChipPowerOn) => "power-on",
+        Some(SocResetReason::SysBrownOut) => "brownout",
+        Some(SocResetReason::CoreSw | SocResetReason::Cpu0Sw) => "software",
+        Some(SocResetReason::CoreRtcWdt | SocResetReason::Cpu0RtcWdt | SocResetReason::SysRtcWdt) => "RTC watchdog",
+        Some(
+            SocResetReason::CoreMwdt0 | SocResetReason::CoreMwdt1 | SocResetReason::Cpu0Mwdt0 | SocResetReason::Cpu0Mwdt1,
+        ) => "TIMG watchdog",
+        Some(SocResetReason::SysSuperWdt) => "super watchdog",
+        Some(SocResetReason::CoreDeepSleep) => "deep sleep wake",
+        Some(SocResetReason::CoreUsbUart | SocResetReason::CoreUsbJtag | SocResetReason::Cpu0JtagCpu) => "USB/JTAG",
+        Some(_) => "other",
+        None => "unknown",
+    }
+}
+
+/// Apply a state change with all of its side effects (Neopixel color,
+/// calibration restart). Button and CLI both go through here.
+fn transition_to<N>(new_state: DeviceState, hw: &mut Hw<N>)
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let (r, g, b) = match new_state {
+        DeviceState::Sleep => (0, 0, 0),         // Off
+        DeviceState::Monitoring => (0, 0, 30),   // Blue
+        DeviceState::Calibrating => (30, 30, 0), // Yellow
+    };
+    hw.neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
+    unsafe {
+        NEO_R = r;
+        NEO_G = g;
+        NEO_B = b;
+        if new_state == DeviceState::Calibrating {
+            CALIBRATION_SAMPLES = 0;
+        }
+        let old_state = DEVICE_STATE;
+        info!("[STATE] {:?} → {:?}", old_state, new_state);
+        DEVICE_STATE = new_state;
+    }
+}
+
+/// Enable the data-ready interrupt and pick where Monitoring learns about
+/// new samples: the INT line on IMU_INT_PIN if it rises, else INT_STATUS
+fn setup_data_ready<Dm: esp_hal::DriverMode>(i2c: &mut I2c<Dm>, addr: u8, imu_int: &Input) {
+    mpu::configure_int_pin(i2c, addr, IntPolarity::ActiveHigh).ok();
+    mpu::enable_data_ready(i2c, addr).ok();
+    // Start from a released pin (any read clears the latch), then wait for
+    // the next sample to raise it
+    mpu::data_ready(i2c, addr).ok();
+    Delay::new().delay_millis(IMU_INT_DETECT_MS);
+    let wired = imu_int.is_high();
+    unsafe {
+        IMU_INT_GPIO = wired;
+    }
+    if wired {
+        info!("[IMU] Data ready on INT (GPIO{})", IMU_INT_PIN);
+    } else {
+        info!("[IMU] INT not connected to GPIO{}, polling INT_STATUS", IMU_INT_PIN);
+    }
+}
+
+/// The IMU answered again: resume Monitoring reads and repaint over the
+/// sensor fault pattern
+fn mark_imu_present<N>(hw: &mut Hw<N>)
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    unsafe {
+        if IMU_PRESENT {
+            return;
+        }
+        IMU_PRESENT = true;
+        let addr = IMU_ADDR;
+        info!("[IMU] MPU6050 detected at 0x{:02X}", addr);
+        if DEVICE_STATE == DeviceState::Monitoring {
+            transition_to(DeviceState::Monitoring, hw);
+        }
+    }
+}
+
 // GDB-callable functions
 #[no_mangle]
 pub extern "C" fn get_device_state() -> u8 {
@@ -2,111 +2,71 @@
 #![no_main]
 
 use core::fmt::Write;
+use errcode::ErrorCode;
 use esp_backtrace as _;
 use esp_hal::{
+    analog::adc::{Adc, AdcConfig, Attenuation},
     delay::Delay,
-    gpio::{Input, InputConfig, Level, Output, OutputConfig, Pull},
+    gpio::{DriveMode, Input, InputConfig, Level, Output, OutputConfig, Pull, WakeEvent},
+    handler,
     i2c::master::{Config as I2cConfig, I2c},
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self as ledc_timer, TimerIFace},
+        LSGlobalClkSource, Ledc, LowSpeed,
+    },
     main,
     rmt::Rmt,
-    time::Rate,
+    rtc_cntl::{
+        sleep::{GpioWakeupSource, TimerWakeupSource},
+        Rtc, RwdtStage,
+    },
+    time::{Duration, Instant, Rate},
+    timer::{timg::TimerGroup, PeriodicTimer},
     uart::{Config as UartConfig, Uart},
     Blocking,
 };
 use esp_hal_smartled::{buffer_size, color_order, SmartLedsAdapter, Ws2812Timing};
+use esp_storage::FlashStorage;
 use heapless::String;
 use lesson_05_posture_monitor as mpu;
-use log::info;
-use smart_leds::{SmartLedsWrite, RGB8};
-
-esp_bootloader_esp_idf::esp_app_desc!();
-
-// GPIO configuration
-const LED_PIN: u8 = 12;
-const BUTTON_PIN: u8 = 9;
-const NEOPIXEL_PIN: u8 = 8;
-const UART_TX_PIN: u8 = 23;
-const UART_RX_PIN: u8 = 15;
-const I2C_SDA_PIN: u8 = 2;
-const I2C_SCL_PIN: u8 = 11;
-
-const UART_BAUD: u32 = 115200;
-const I2C_FREQ: u32 = 100_000;
-const CMD_BUFFER_SIZE: usize = 128;
-
-// Posture thresholds (degrees)
-const TILT_WARNING_THRESHOLD: f32 = 30.0;
-const TILT_ALERT_THRESHOLD: f32 = 60.0;
-
-// LED blink frequencies
-const LED_BLINK_WARNING_HZ: u32 = 1;  // 1 Hz
-const LED_BLINK_ALERT_HZ: u32 = 5;    // 5 Hz
-
-// Device states
-#[derive(Debug, Clone, Copy, PartialEq)]
-#[repr(u8)]
-enum DeviceState {
-    Sleep = 0,
-    Monitoring = 1,
-    Calibrating = 2,
-}
-
-// Alert levels (sub-states of Monitoring)
-#[derive(Debug, Clone, Copy, PartialEq)]
-#[repr(u8)]
-enum AlertLevel {
-    Normal = 0,   // 0-30°
-    Warning = 1,  // 30-60°
-    Alert = 2,    // >60°
-}
+use log::{info, warn};
+use mpu::buzzer::{buzzer_pattern_on, buzzer_timer_config, Buzzer};
+use mpu::commands::{process_command, report_line_too_long, run_line};
+use mpu::config::*;
+use mpu::encoder::Quadrature;
+use mpu::fade::ColorFade;
+use mpu::fall::{FallDetector, FallEvent};
+use mpu::gesture::{Gesture, ShakeDetector};
+use mpu::hw::{report_fault, transition_to, Hw};
+use mpu::knob::{apply_encoder_step, knob_to_threshold};
+use mpu::persist::{self, Key};
+use mpu::score::ScoreWindow;
+use mpu::sensor::{accel_1g, accel_offsets, cal_deviation, corrected_tilt, program_hw_offsets, tilt_rate_dps, vector_magnitude};
+use mpu::settings::{load_calibration, load_colors, load_mount, save_calibration, save_mount};
+use mpu::state::*;
+use mpu::stats::Welford;
+use mpu::stream::{raw_flags, raw_frame, write_stream_line};
+use smart_leds::RGB8;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-#[repr(u8)]
-enum Mode {
-    CLI = 0,
-    Streaming = 1,
+#[handler]
+fn wake_timer_isr() {
+    critical_section::with(|cs| {
+        if let Some(timer) = WAKE_TIMER.borrow_ref_mut(cs).as_mut() {
+            timer.clear_interrupt();
+        }
+    });
+    unsafe {
+        WFI_WAKEUPS = WFI_WAKEUPS.wrapping_add(1);
+    }
 }
 
-// Global state (GDB-accessible)
-#[no_mangle]
-static mut DEVICE_STATE: DeviceState = DeviceState::Sleep;
-
-#[no_mangle]
-static mut ALERT_LEVEL: AlertLevel = AlertLevel::Normal;
-
-#[no_mangle]
-static mut MODE: Mode = Mode::CLI;
-
-#[no_mangle]
-static mut UPTIME_MS: u32 = 0;
-
-#[no_mangle]
-static mut TILT_ANGLE: f32 = 0.0;
-
-#[no_mangle]
-static mut IMU_ACCEL_X: i16 = 0;
-#[no_mangle]
-static mut IMU_ACCEL_Y: i16 = 0;
-#[no_mangle]
-static mut IMU_ACCEL_Z: i16 = 0;
-
-#[no_mangle]
-static mut CAL_OFFSET_X: i16 = 0;
-#[no_mangle]
-static mut CAL_OFFSET_Y: i16 = 0;
-#[no_mangle]
-static mut CAL_OFFSET_Z: i16 = 0;
-
-#[no_mangle]
-static mut CALIBRATION_SAMPLES: u16 = 0;
-
-#[no_mangle]
-static mut LED_STATE: bool = false;
-
 #[main]
 fn main() -> ! {
-    esp_println::logger::init_logger_from_env();
-    log::set_max_level(log::LevelFilter::Info);
+    // esp-println output plus an in-RAM copy for log.dump
+    mpu::logbuf::init(log::LevelFilter::Info, |record| {
+        esp_println::println!("{} - {}", record.level(), record.args());
+    });
 
     info!("\n=== Lesson 05: Posture Monitor Device ===\n");
 
@@ -121,6 +81,47 @@ fn main() -> ! {
         .with_tx(peripherals.GPIO23)
         .with_rx(peripherals.GPIO15);
 
+    // Count this boot in flash (own record, independent of any other persisted data)
+    let mut flash = FlashStorage::new(peripherals.FLASH);
+    let mut record = [0u8; persist::HEADER_LEN + persist::MAX_PAYLOAD];
+    let boots = persist::load(&mut flash, Key::BootCount, &mut record)
+        .and_then(|payload| payload.try_into().ok())
+        .map_or(0, u32::from_le_bytes)
+        .wrapping_add(1);
+    if persist::store(&mut flash, Key::BootCount, &boots.to_le_bytes()).is_err() {
+        report_fault(ErrorCode::Flash, &mut uart);
+    }
+    unsafe {
+        BOOT_COUNT = boots;
+    }
+    info!("[INIT] Boot #{}", boots);
+
+    // Restore the last saved calibration before the state machine starts
+    match load_calibration(&mut flash) {
+        Some([x, y, z]) => {
+            unsafe {
+                CAL_OFFSET_X = x;
+                CAL_OFFSET_Y = y;
+                CAL_OFFSET_Z = z;
+                CALIBRATED = true;
+            }
+            info!("[INIT] Calibration loaded: x={}, y={}, z={}", x, y, z);
+        }
+        None => info!("[INIT] No calibration found in flash, offsets zero"),
+    }
+    if let Some([x, y, z]) = load_mount(&mut flash) {
+        unsafe {
+            MOUNT_REF = Some([x, y, z]);
+        }
+        info!("[INIT] Mount reference loaded: x={}, y={}, z={}", x, y, z);
+    }
+    if let Some(colors) = load_colors(&mut flash) {
+        unsafe {
+            ALERT_COLORS = colors;
+        }
+        info!("[INIT] Alert colors loaded");
+    }
+
     // Initialize I2C
     info!("[INIT] I2C...");
     let i2c_config = I2cConfig::default().with_frequency(Rate::from_hz(I2C_FREQ));
@@ -130,59 +131,225 @@ fn main() -> ! {
         .with_scl(peripherals.GPIO11);
 
     // Initialize MPU6050
+    let imu_addr = unsafe { IMU_ADDR };
     info!("[INIT] MPU6050...");
-    if mpu::wake_sensor(&mut i2c).is_ok() {
+    if mpu::wake_sensor(&mut i2c, imu_addr).is_ok() {
         delay.delay_millis(100);
-        if let Ok(who_am_i) = mpu::read_who_am_i(&mut i2c) {
+    }
+    match mpu::probe(&mut i2c, imu_addr).map_err(ErrorCode::from) {
+        Ok(who_am_i) => {
             info!("[INIT] MPU WHO_AM_I = 0x{:02X}", who_am_i);
+            mpu::set_accel_range(&mut i2c, imu_addr, unsafe { ACCEL_RANGE }).ok();
+            mpu::enable_data_ready(&mut i2c, imu_addr).ok();
+            if let Ok(trim) = mpu::read_hardware_offsets(&mut i2c, imu_addr) {
+                unsafe {
+                    ACCEL_TRIM = trim;
+                }
+            }
+            // Same check as device.cal_check: a board remounted since the
+            // calibration was saved reads wrong angles until recalibrated
+            if unsafe { CALIBRATED } {
+                if let Ok(accel) = mpu::read_accel(&mut i2c, imu_addr) {
+                    let deviation = cal_deviation([accel.x, accel.y, accel.z]);
+                    if deviation > CAL_CHECK_MAX_DEG {
+                        warn!(
+                            "[INIT] Board is {:.1}° from its calibrated orientation (remounted?), run device.cal_zero",
+                            deviation
+                        );
+                    }
+                }
+            }
         }
+        Err(code) => report_fault(code, &mut uart),
+    }
+    // Second IMU is optional; joint.angle reports an error if it's absent
+    if mpu::wake_sensor(&mut i2c, unsafe { IMU2_ADDR }).is_ok() {
+        info!("[INIT] Second MPU at 0x{:02X}", unsafe { IMU2_ADDR });
     }
 
     // Initialize button
     info!("[INIT] Button...");
-    let button = Input::new(peripherals.GPIO9, InputConfig::default().with_pull(Pull::Up));
+    let mut button = Input::new(peripherals.GPIO9, InputConfig::default().with_pull(Pull::Up));
+    // Only takes effect in light sleep: a press (low level) wakes the chip
+    button.wakeup_enable(true, WakeEvent::LowLevel).ok();
 
     // Initialize LED
     info!("[INIT] LED...");
-    let mut led = Output::new(peripherals.GPIO12, Level::Low, OutputConfig::default());
+    let led_off = if LED_ACTIVE_LOW { Level::High } else { Level::Low };
+    let led = Output::new(peripherals.GPIO12, led_off, OutputConfig::default());
 
     // Initialize Neopixel
     info!("[INIT] Neopixel...");
     let rmt = Rmt::new(peripherals.RMT, Rate::from_mhz(80)).expect("Failed to init RMT");
-    let mut neopixel = SmartLedsAdapter::<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>::new_with_memsize(
+    let neopixel = SmartLedsAdapter::<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>::new_with_memsize(
         rmt.channel0,
         peripherals.GPIO8,
         2,
     )
     .expect("Failed to create SmartLedsAdapter");
 
+    // Initialize watchdog (esp_hal::init() leaves it disabled)
+    info!("[INIT] Watchdog (RWDT, {} ms)...", WATCHDOG_TIMEOUT_MS);
+    let mut rtc = Rtc::new(peripherals.LPWR);
+    rtc.rwdt.set_timeout(RwdtStage::Stage0, Duration::from_millis(WATCHDOG_TIMEOUT_MS));
+    rtc.rwdt.enable();
+
+    // Initialize buzzer: LEDC low-speed timer0 at BUZZER_FREQ_HZ, channel0 silent
+    info!("[INIT] Buzzer (LEDC, GPIO{})...", BUZZER_PIN);
+    let mut ledc = Ledc::new(peripherals.LEDC);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut buzzer_timer = ledc.timer::<LowSpeed>(ledc_timer::Number::Timer0);
+    buzzer_timer
+        .configure(buzzer_timer_config(BUZZER_FREQ_HZ))
+        .expect("Failed to configure LEDC timer");
+    let mut buzzer_channel = ledc.channel(channel::Number::Channel0, peripherals.GPIO10);
+    buzzer_channel
+        .configure(channel::config::Config {
+            timer: &buzzer_timer,
+            duty_pct: 0,
+            drive_mode: DriveMode::PushPull,
+        })
+        .expect("Failed to configure LEDC channel");
+
+    // Initialize rotary encoder (common pin to GND, A/B pulled up)
+    info!("[INIT] Encoder (GPIO{}/GPIO{})...", ENCODER_A_PIN, ENCODER_B_PIN);
+    let encoder_a = Input::new(peripherals.GPIO4, InputConfig::default().with_pull(Pull::Up));
+    let encoder_b = Input::new(peripherals.GPIO5, InputConfig::default().with_pull(Pull::Up));
+    let mut encoder = Quadrature::new(encoder_a.is_high(), encoder_b.is_high());
+
+    let mut hw = Hw {
+        led,
+        neopixel,
+        neo_color: RGB8::new(0, 0, 0),
+        flash,
+        rtc,
+        buzzer: Buzzer::new(&ledc, buzzer_channel),
+        button,
+        encoder_a,
+        encoder_b,
+    };
+
+    // Initialize potentiometer ADC
+    info!("[INIT] Knob ADC (GPIO{})...", KNOB_ADC_PIN);
+    let mut adc_config = AdcConfig::new();
+    let mut knob_pin = adc_config.enable_pin(peripherals.GPIO3, Attenuation::_11dB);
+    let mut adc = Adc::new(peripherals.ADC1, adc_config);
+
+    // Initialize cpu.wfi wake-up timer (interrupt only enabled around `wfi`)
+    info!("[INIT] WFI wake timer...");
+    let timg0 = TimerGroup::new(peripherals.TIMG0);
+    let mut wake_timer = PeriodicTimer::new(timg0.timer0);
+    wake_timer.set_interrupt_handler(wake_timer_isr);
+    wake_timer.start(Duration::from_millis(WFI_WAKE_PERIOD_MS)).ok();
+    critical_section::with(|cs| WAKE_TIMER.borrow_ref_mut(cs).replace(wake_timer));
+
     info!("[INIT] All peripherals ready\n");
-    info!("[DEVICE] Starting in Sleep mode. Press button to start monitoring.\n");
 
     let _ = uart.write_str("\r\n=== Posture Monitor Device ===\r\n");
+
+    if AUTO_CAL_ON_BOOT {
+        // Sensor warm-up; blink yellow so the user knows to keep the device still
+        info!("[DEVICE] Auto-calibration in {} ms, keep the device still\n", AUTO_CAL_WARMUP_MS);
+        let _ = uart.write_str("Auto-calibration: keep the device still...\r\n");
+        let mut elapsed_ms = 0;
+        while elapsed_ms < AUTO_CAL_WARMUP_MS {
+            let on = (elapsed_ms / AUTO_CAL_BLINK_MS) % 2 == 0;
+            let color = if on { RGB8::new(30, 30, 0) } else { RGB8::new(0, 0, 0) };
+            hw.set_neo(color);
+            hw.feed_watchdog();
+            delay.delay_millis(AUTO_CAL_BLINK_MS);
+            elapsed_ms += AUTO_CAL_BLINK_MS;
+        }
+        // Calibration moves on to Monitoring by itself once the offsets verify
+        transition_to(DeviceState::Calibrating, &mut hw);
+    } else {
+        info!("[DEVICE] Starting in Sleep mode. Press button to start monitoring.\n");
+    }
+
     let _ = uart.write_str("Type 'help' for commands\r\n\r\n> ");
 
     let mut cmd_buffer: String<CMD_BUFFER_SIZE> = String::new();
+    // Set when a line outgrew cmd_buffer; the rest of it is ignored until Enter
+    let mut line_too_long = false;
     let mut counter: u32 = 0;
+    let mut seq: u32 = 0; // per emitted line, for host-side gap detection
     let mut last_stream_time_ms: u32 = 0;
-    let mut button_last_state = button.is_high();
+    let mut button_last_state = hw.button.is_high();
     let mut button_press_time: u32 = 0;
     let mut led_last_toggle_ms: u32 = 0;
+    let mut knob_last_read_ms: u32 = 0;
+    let mut last_sample_ms: u32 = 0;
+    let mut strobe_on = false;
+    let mut strobe_last_toggle_ms: u32 = 0;
+    let mut temp_last_read_ms: u32 = 0;
+    let mut raw_seq: u8 = 0;
+    // Guided demo progress (DEMO_STEP is set by the `demo` command)
+    let mut demo_last_step: u8 = 0;
+    let mut demo_step_started_ms: u32 = 0;
+    let mut demo_last_action_ms: u32 = 0;
+    let mut demo_tick: u32 = 0;
+    let mut demo_prev_state = DeviceState::Sleep;
+    // Last notification per alert level (posture.cooldown)
+    let mut alert_notified_ms: [Option<u32>; 3] = [None; 3];
+    // Whether the current level was notified; the buzzer stays quiet otherwise
+    let mut alert_announced = true;
+    // Stationary detection: start of the current still period and its angle
+    let mut still_since_ms: Option<u32> = None;
+    let mut still_ref_tilt: f32 = 0.0;
+    let mut autocal_hinted = false;
+    // Sedentary nudges given in the current still period, and the running one
+    let mut sedentary_nudges: u32 = 0;
+    let mut sedentary_nudge_ms: Option<u32> = None;
+    // Free-fall/impact detector and the event color being flashed (color, start, length)
+    let mut fall_detector = FallDetector::new(FREEFALL_G, FREEFALL_SAMPLES, IMPACT_G);
+    let mut event_flash: Option<(RGB8, u32, u32)> = None;
+    // Shake detector and the start of its white acknowledgement blinks
+    let mut shake_detector = ShakeDetector::new(SHAKE_THRESHOLD_G, SHAKE_SIGN_CHANGES, SHAKE_WINDOW_MS);
+    let mut shake_flash_ms: Option<u32> = None;
+    // Alert levels for the posture score (restarted with each session)
+    let mut score_window: ScoreWindow<SCORE_WINDOW_SAMPLES> = ScoreWindow::new();
+    let mut score_sample_ms: u32 = 0;
+    let mut score_update_ms: u32 = 0;
+    // Filters restart from the raw angle on the first sample of each Monitoring
+    // stint: calibration or time asleep leaves their old angle meaningless
+    let mut filters_primed = false;
+    // Session: from entering Monitoring until the next Sleep (summary printed at the end)
+    let mut last_state = unsafe { DEVICE_STATE };
+    let mut session_active = false;
+    let mut session_start_ms: u32 = 0;
+    let mut session_tilt = Welford::new();
+    let mut session_tilt_max: f32 = 0.0;
+    let mut session_alert_base = [0u32; 3];
+    let mut fade_start_ms: Option<u32> = None;
+    // Alert color crossfade; alert_fading stays set until the target is painted
+    let mut alert_fade = ColorFade::new(alert_color(AlertLevel::Normal), ALERT_FADE_MS);
+    let mut alert_fading = false;
+    // stream.aggregate avg: samples accumulated since the last streamed line
+    let mut stream_tilt_avg = Welford::new();
+    let mut stream_accel_avg = [Welford::new(); 3];
 
     // Calibration accumulators
     let mut cal_accel_x_sum: i32 = 0;
     let mut cal_accel_y_sum: i32 = 0;
     let mut cal_accel_z_sum: i32 = 0;
+    let mut cal_attempt: u8 = 0;
+    // State to go back to if calibration is aborted, and the red flash shown then
+    let mut cal_return_state = DeviceState::Monitoring;
+    let mut cal_abort_flash_ms: Option<u32> = None;
+    // Last character received on the CLI; holds off light sleep
+    let mut last_rx_ms: u32 = 0;
 
     loop {
+        hw.feed_watchdog();
+        let imu_addr = unsafe { IMU_ADDR };
         unsafe {
-            UPTIME_MS = UPTIME_MS.wrapping_add(10);
+            UPTIME_MS = Instant::now().duration_since_epoch().as_millis() as u32;
         }
 
         let current_time_ms = unsafe { UPTIME_MS };
 
         // Button handling (short vs long press)
-        let button_current = button.is_high();
+        let button_current = hw.button.is_high();
         if button_last_state && !button_current {
             // Button just pressed
             button_press_time = current_time_ms;
@@ -192,144 +359,734 @@ fn main() -> ! {
 
             if press_duration >= 3000 {
                 // Long press (3s) - toggle Sleep
-                unsafe {
-                    DEVICE_STATE = if DEVICE_STATE == DeviceState::Sleep {
-                        info!("[STATE] Long press: Sleep → Monitoring");
-                        neopixel.write([RGB8::new(0, 30, 0)].into_iter()).ok(); // Green
-                        DeviceState::Monitoring
-                    } else {
-                        info!("[STATE] Long press: → Sleep");
-                        neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok(); // Off
-                        led.set_low();
-                        LED_STATE = false;
-                        DeviceState::Sleep
-                    };
+                info!("[BUTTON] Long press");
+                if unsafe { DEVICE_STATE } == DeviceState::Sleep {
+                    transition_to(DeviceState::Monitoring, &mut hw);
+                } else {
+                    transition_to(DeviceState::Sleep, &mut hw);
                 }
             } else if press_duration >= 50 {
                 // Short press - calibrate zero
+                if unsafe { DEVICE_STATE } == DeviceState::Monitoring {
+                    info!("[BUTTON] Short press: calibrating zero orientation");
+                    transition_to(DeviceState::Calibrating, &mut hw);
+                }
+            }
+        }
+        button_last_state = button_current;
+
+        // Potentiometer → warning threshold
+        if unsafe { KNOB_ENABLED } && current_time_ms.wrapping_sub(knob_last_read_ms) >= KNOB_READ_PERIOD_MS {
+            knob_last_read_ms = current_time_ms;
+            if let Ok(Some(deg)) = nb::block!(adc.read_oneshot(&mut knob_pin)).map(knob_to_threshold) {
+                unsafe {
+                    TILT_WARNING_THRESHOLD = deg;
+                }
+            }
+        }
+
+        // Rotary encoder → selected setting (polled every tick)
+        let step = encoder.update(hw.encoder_a.is_high(), hw.encoder_b.is_high());
+        if step != 0 {
+            apply_encoder_step(step);
+        }
+
+        // Over-temperature guard (not while asleep or frozen)
+        if unsafe { DEVICE_STATE != DeviceState::Sleep && !TELEMETRY_FROZEN }
+            && current_time_ms.wrapping_sub(temp_last_read_ms) >= TEMP_CHECK_PERIOD_MS
+        {
+            temp_last_read_ms = current_time_ms;
+            if let Ok(temp) = mpu::read_temp(&mut i2c, imu_addr) {
                 unsafe {
-                    if DEVICE_STATE == DeviceState::Monitoring {
-                        info!("[STATE] Short press: Calibrating zero orientation");
-                        DEVICE_STATE = DeviceState::Calibrating;
-                        CALIBRATION_SAMPLES = 0;
-                        cal_accel_x_sum = 0;
-                        cal_accel_y_sum = 0;
-                        cal_accel_z_sum = 0;
-                        neopixel.write([RGB8::new(30, 30, 0)].into_iter()).ok(); // Yellow
+                    TEMP_C = temp;
+                    let limit = TEMP_LIMIT_C;
+                    if !OVER_TEMP && temp > limit {
+                        OVER_TEMP = true;
+                        info!("[TEMP] Over limit: {:.1}°C > {:.1}°C", temp, limit);
+                        let mut buf: String<80> = String::new();
+                        write!(buf, "\r\nWARNING: Over temperature {:.1}C (limit {:.1}C)\r\n", temp, limit).ok();
+                        let _ = uart.write_str(&buf);
+                        if TEMP_SLEEP_ON_LIMIT {
+                            transition_to(DeviceState::Sleep, &mut hw);
+                        }
+                        hw.set_neo(OVERTEMP_COLOR);
+                    } else if OVER_TEMP && temp < limit - TEMP_HYSTERESIS_C {
+                        OVER_TEMP = false;
+                        info!("[TEMP] Back below limit: {:.1}°C", temp);
+                        if DEVICE_STATE == DeviceState::Monitoring {
+                            hw.set_neo(alert_color(ALERT_LEVEL));
+                        }
                     }
                 }
             }
         }
-        button_last_state = button_current;
 
-        // State machine
+        // Neopixel strobe; an impact strobe times out back to the state's color
+        if let Some(start) = unsafe { NEO_STROBE_AUTO_MS } {
+            if unsafe { NEO_STROBE_HZ } == 0 {
+                unsafe { NEO_STROBE_AUTO_MS = None };
+            } else if current_time_ms.wrapping_sub(start) >= IMPACT_STROBE_MS {
+                unsafe {
+                    NEO_STROBE_HZ = 0;
+                    NEO_STROBE_AUTO_MS = None;
+                }
+                match unsafe { DEVICE_STATE } {
+                    DeviceState::Monitoring => hw.set_neo(alert_color(unsafe { ALERT_LEVEL })),
+                    _ => hw.set_neo(RGB8::new(0, 0, 0)),
+                }
+            }
+        }
+        let strobe_hz = unsafe { NEO_STROBE_HZ };
+        if strobe_hz > 0 {
+            let half_period_ms = 500 / strobe_hz as u32;
+            if current_time_ms.wrapping_sub(strobe_last_toggle_ms) >= half_period_ms {
+                strobe_on = !strobe_on;
+                let color = if strobe_on { NEO_STROBE_COLOR } else { RGB8::new(0, 0, 0) };
+                hw.set_neo(color);
+                strobe_last_toggle_ms = current_time_ms;
+            }
+        }
+
+        // Guided demo: one step at a time, driven by the loop tick (non-blocking)
+        let demo_step = unsafe { DEMO_STEP };
+        if demo_step != 0 {
+            if demo_step != demo_last_step {
+                if demo_last_step == 0 {
+                    demo_prev_state = unsafe { DEVICE_STATE };
+                }
+                demo_last_step = demo_step;
+                demo_step_started_ms = current_time_ms;
+                demo_last_action_ms = current_time_ms;
+                demo_tick = 0;
+                let title = match demo_step {
+                    1 => "Neopixel colors",
+                    2 => "LED blink",
+                    3 => "Telemetry",
+                    4 => "Simulated tilt: warning",
+                    5 => "Simulated tilt: alert",
+                    _ => "",
+                };
+                if demo_step <= DEMO_STEPS {
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "\r\n[demo {}/{}] {}\r\n", demo_step, DEMO_STEPS, title).ok();
+                    let _ = uart.write_str(&buf);
+                }
+                match demo_step {
+                    4 => {
+                        transition_to(DeviceState::Monitoring, &mut hw);
+                        inject_tilt((unsafe { TILT_WARNING_THRESHOLD } + unsafe { TILT_ALERT_THRESHOLD }) / 2.0);
+                    }
+                    5 => inject_tilt(unsafe { TILT_ALERT_THRESHOLD } + 10.0),
+                    _ => {}
+                }
+            }
+
+            let elapsed_ms = current_time_ms.wrapping_sub(demo_step_started_ms);
+            let since_action_ms = current_time_ms.wrapping_sub(demo_last_action_ms);
+            match demo_step {
+                1 if since_action_ms >= 500 || demo_tick == 0 => {
+                    hw.set_neo(DEMO_COLORS[demo_tick as usize % DEMO_COLORS.len()]);
+                    demo_tick += 1;
+                    demo_last_action_ms = current_time_ms;
+                }
+                2 if since_action_ms >= 100 => {
+                    hw.set_led(demo_tick % 2 == 0);
+                    demo_tick += 1;
+                    demo_last_action_ms = current_time_ms;
+                }
+                3 if since_action_ms >= 500 => {
+                    let mut buf: String<128> = String::new();
+                    let (state, alert, tilt, ax, ay, az) =
+                        unsafe { (DEVICE_STATE, ALERT_LEVEL, TILT_ANGLE, IMU_ACCEL_X, IMU_ACCEL_Y, IMU_ACCEL_Z) };
+                    write!(
+                        buf,
+                        "[state={:?} alert={:?} tilt={:.1}° accel=({},{},{}) t={}]\r\n",
+                        state, alert, tilt, ax, ay, az, current_time_ms
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                    demo_last_action_ms = current_time_ms;
+                }
+                _ => {}
+            }
+
+            if demo_step == DEMO_STOP || (demo_step == DEMO_STEPS && elapsed_ms >= DEMO_STEP_MS) {
+                // Finished or interrupted: undo everything the demo changed
+                clear_tilt_override();
+                transition_to(demo_prev_state, &mut hw);
+                unsafe {
+                    DEMO_STEP = 0;
+                }
+                demo_last_step = 0;
+                let _ = uart.write_str(if demo_step == DEMO_STOP { "\r\n[demo stopped]\r\n> " } else { "\r\n[demo done]\r\n> " });
+            } else if elapsed_ms >= DEMO_STEP_MS {
+                if demo_step == 2 {
+                    hw.set_led(false);
+                }
+                unsafe {
+                    DEMO_STEP = demo_step + 1;
+                }
+            }
+        }
+
+        // State machine (skipped entirely while telemetry is frozen)
+        let frozen = unsafe { TELEMETRY_FROZEN };
         let current_state = unsafe { DEVICE_STATE };
+        // Session start/end, whatever caused the transition (button, CLI, GDB, over-temperature)
+        if current_state != last_state {
+            if current_state == DeviceState::Monitoring && !session_active {
+                session_active = true;
+                session_start_ms = current_time_ms;
+                session_tilt = Welford::new();
+                session_tilt_max = 0.0;
+                session_alert_base = unsafe { ALERT_COUNTS };
+                score_window.clear();
+                score_sample_ms = current_time_ms;
+                score_update_ms = current_time_ms;
+                unsafe {
+                    POSTURE_SCORE = None;
+                    POSTURE_SCORE_SPAN_S = 0;
+                }
+            } else if current_state == DeviceState::Sleep && session_active {
+                session_active = false;
+                let counts = unsafe { ALERT_COUNTS };
+                let duration_s = current_time_ms.wrapping_sub(session_start_ms) / 1000;
+                let (warnings, alerts) = (
+                    counts[AlertLevel::Warning as usize].saturating_sub(session_alert_base[AlertLevel::Warning as usize]),
+                    counts[AlertLevel::Alert as usize].saturating_sub(session_alert_base[AlertLevel::Alert as usize]),
+                );
+                info!(
+                    "[SESSION] End after {} s: tilt avg={:.1}° max={:.1}°, {} warnings, {} alerts",
+                    duration_s,
+                    session_tilt.mean(),
+                    session_tilt_max,
+                    warnings,
+                    alerts
+                );
+                if unsafe { MODE } != Mode::Raw {
+                    let mut buf: String<192> = String::new();
+                    write!(
+                        buf,
+                        "[session end duration={}s samples={} tilt_avg={:.1}° tilt_max={:.1}° warnings={} alerts={} t={}]\r\n",
+                        duration_s,
+                        session_tilt.count(),
+                        session_tilt.mean(),
+                        session_tilt_max,
+                        warnings,
+                        alerts,
+                        current_time_ms
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                }
+                // An over-temperature Sleep keeps OVERTEMP_COLOR lit instead of fading
+                if !unsafe { OVER_TEMP } {
+                    fade_start_ms = Some(current_time_ms);
+                }
+            }
+            if current_state == DeviceState::Calibrating {
+                cal_return_state = last_state;
+            }
+            last_state = current_state;
+        }
+
+        // Shutdown fade (transition_to already turned the Neopixel off; fade back down
+        // from the last alert color). Waking up cancels it.
+        if let Some(start) = fade_start_ms {
+            let t = current_time_ms.wrapping_sub(start);
+            if current_state != DeviceState::Sleep {
+                fade_start_ms = None;
+            } else if t >= SHUTDOWN_FADE_MS {
+                fade_start_ms = None;
+                hw.set_neo(RGB8::new(0, 0, 0));
+            } else {
+                let from = alert_color(unsafe { ALERT_LEVEL });
+                let scale = |c: u8| (c as u32 * (SHUTDOWN_FADE_MS - t) / SHUTDOWN_FADE_MS) as u8;
+                hw.set_neo(RGB8::new(scale(from.r), scale(from.g), scale(from.b)));
+            }
+        }
+
+        // Time spent outside Monitoring doesn't count as sitting still
+        if current_state != DeviceState::Monitoring {
+            still_since_ms = None;
+            sedentary_nudge_ms = None;
+            fall_detector.reset();
+            event_flash = None;
+            shake_detector.reset();
+            shake_flash_ms = None;
+            filters_primed = false;
+            // transition_to paints Normal directly when Monitoring starts again
+            alert_fade = ColorFade::new(alert_color(AlertLevel::Normal), ALERT_FADE_MS);
+            alert_fading = false;
+        }
         match current_state {
+            _ if frozen => {}
             DeviceState::Sleep => {
                 // Minimal activity
             }
             DeviceState::Monitoring => {
-                // Read IMU
-                if let Ok(accel) = mpu::read_accel(&mut i2c) {
-                    unsafe {
-                        IMU_ACCEL_X = accel.x;
-                        IMU_ACCEL_Y = accel.y;
-                        IMU_ACCEL_Z = accel.z;
-
-                        // Apply calibration offsets
-                        let ax = (accel.x - CAL_OFFSET_X) as f32;
-                        let ay = (accel.y - CAL_OFFSET_Y) as f32;
-                        let az = (accel.z - CAL_OFFSET_Z) as f32;
-
-                        // Calculate tilt angle (pitch from X-Z plane)
-                        // Using atan2(sqrt(x² + y²), z) for total tilt from vertical
-                        let xy_magnitude = libm::sqrtf(ax * ax + ay * ay);
-                        TILT_ANGLE = libm::atan2f(xy_magnitude, az) * 180.0 / 3.14159265;
-
-                        // Determine alert level
-                        let prev_alert = ALERT_LEVEL;
-                        ALERT_LEVEL = if TILT_ANGLE < TILT_WARNING_THRESHOLD {
-                            AlertLevel::Normal
-                        } else if TILT_ANGLE < TILT_ALERT_THRESHOLD {
-                            AlertLevel::Warning
-                        } else {
-                            AlertLevel::Alert
-                        };
-
-                        // Update Neopixel and LED based on alert level
-                        if ALERT_LEVEL != prev_alert {
-                            match ALERT_LEVEL {
-                                AlertLevel::Normal => {
-                                    neopixel.write([RGB8::new(0, 30, 0)].into_iter()).ok(); // Green
-                                    led.set_low();
-                                    LED_STATE = false;
-                                    info!("[ALERT] Normal (tilt={:.1}°)", TILT_ANGLE);
+                let mut autocal_due = false;
+                // Read IMU every SAMPLE_PERIOD_MS (the loop itself ticks every 10 ms),
+                // and only when the sensor has a fresh sample (INT_STATUS.DATA_RDY) or a
+                // tilt is injected
+                if current_time_ms.wrapping_sub(last_sample_ms) >= unsafe { SAMPLE_PERIOD_MS }
+                    && (unsafe { TILT_OVERRIDE } || matches!(mpu::data_ready(&mut i2c, imu_addr), Ok(true)))
+                {
+                    // Capped so resuming from Sleep doesn't look like one huge step
+                    let dt = current_time_ms.wrapping_sub(last_sample_ms).min(SAMPLE_PERIOD_MAX_MS) as f32 / 1000.0;
+                    last_sample_ms = current_time_ms;
+                    let accel = mpu::read_accel(&mut i2c, imu_addr).ok();
+                    if let Some(accel) = accel {
+                        let gyro_raw = mpu::read_gyro(&mut i2c, imu_addr).ok();
+                        unsafe {
+                            let gyro_bias = GYRO_BIAS;
+                            let gyro = gyro_raw.map(|g| {
+                                let [x, y, z] = gyro_bias.correct([g.x, g.y, g.z]);
+                                mpu::Gyro { x, y, z }
+                            });
+                            IMU_ACCEL_X = accel.x;
+                            IMU_ACCEL_Y = accel.y;
+                            IMU_ACCEL_Z = accel.z;
+
+                            // Apply calibration offsets (no-op when the MPU applies them)
+                            let [ox, oy, oz] = accel_offsets();
+                            let ax = accel.x as i32 - ox;
+                            let ay = accel.y as i32 - oy;
+                            let az = accel.z as i32 - oz;
+
+                            // Smoothed copies for the angle; restart with the filters
+                            let mut ema = ACCEL_EMA;
+                            if !filters_primed {
+                                ema.reset();
+                            }
+                            let [sx, sy, sz] = ema.update([ax as f32, ay as f32, az as f32]);
+                            ACCEL_EMA = ema;
+
+                            // Calculate tilt angle: atan2(sqrt(x² + y²), z) = total tilt from vertical,
+                            // or the angle to the cal.mount reference when one is set
+                            // (libm or fixed-point CORDIC, selected by the `float` feature)
+                            let raw_tilt = corrected_tilt([sx as i32, sy as i32, sz as i32]);
+                            let rate = gyro.map_or(0.0, |g| tilt_rate_dps(ax, ay, g.x, g.y));
+                            TILT_RAW = raw_tilt;
+
+                            // Roll/pitch from the same smoothed accel (no cal.mount reference)
+                            let (accel_roll, accel_pitch) = mpu::tilt::roll_pitch(sx as i32, sy as i32, sz as i32);
+                            let mut roll = ROLL;
+                            let mut pitch = PITCH;
+                            if !filters_primed {
+                                roll.reset(accel_roll);
+                                pitch.reset(accel_pitch);
+                            }
+                            roll.alpha = COMPLEMENTARY.alpha;
+                            pitch.alpha = COMPLEMENTARY.alpha;
+                            let roll_rate = gyro.map_or(0.0, |g| g.x as f32 / GYRO_LSB_PER_DPS);
+                            let pitch_rate = gyro.map_or(0.0, |g| g.y as f32 / GYRO_LSB_PER_DPS);
+                            ROLL_ANGLE = roll.update(accel_roll, roll_rate, dt);
+                            PITCH_ANGLE = pitch.update(accel_pitch, pitch_rate, dt);
+                            ROLL = roll;
+                            PITCH = pitch;
+
+                            let mut comp = COMPLEMENTARY;
+                            let mut kalman = KALMAN;
+                            if !filters_primed {
+                                comp.reset(raw_tilt);
+                                kalman.reset(raw_tilt);
+                                filters_primed = true;
+                            }
+                            let comp_tilt = comp.update(raw_tilt, rate, dt);
+                            COMPLEMENTARY = comp;
+                            if let Some(g) = gyro {
+                                let mut yaw = YAW;
+                                YAW_ANGLE = yaw.update(g.z, dt);
+                                YAW = yaw;
+                            }
+                            TILT_ANGLE = match TILT_FILTER {
+                                TiltFilter::Raw => raw_tilt,
+                                TiltFilter::Complementary => comp_tilt,
+                                TiltFilter::Kalman => {
+                                    let tilt = kalman.update(raw_tilt, rate, dt);
+                                    KALMAN = kalman;
+                                    tilt
+                                }
+                            };
+                            if COMPARE && MODE == Mode::CLI {
+                                let mut buf: String<96> = String::new();
+                                write!(buf, "cmp t={} raw={:.2} comp={:.2}", current_time_ms, raw_tilt, comp_tilt).ok();
+                                if TILT_FILTER == TiltFilter::Kalman {
+                                    let kalman_tilt = TILT_ANGLE;
+                                    write!(buf, " kalman={:.2}", kalman_tilt).ok();
                                 }
-                                AlertLevel::Warning => {
-                                    neopixel.write([RGB8::new(30, 30, 0)].into_iter()).ok(); // Yellow
-                                    info!("[ALERT] Warning (tilt={:.1}°)", TILT_ANGLE);
+                                buf.push_str("\r\n").ok();
+                                let _ = uart.write_str(&buf);
+                            }
+                            if STREAM_AVG {
+                                stream_accel_avg[0].update(accel.x as f32);
+                                stream_accel_avg[1].update(accel.y as f32);
+                                stream_accel_avg[2].update(accel.z as f32);
+                            }
+
+                            // Motion gate: the angle only reflects posture when the device is still
+                            let accel_mag = vector_magnitude(accel.x, accel.y, accel.z);
+                            let rotating = gyro.is_some_and(|g| vector_magnitude(g.x, g.y, g.z) > MOTION_GYRO_MAG);
+                            let one_g = accel_1g();
+                            let accel_dev_g = (accel_mag - one_g).abs() as f32 / one_g as f32;
+                            MOVING = accel_dev_g > MOTION_ACCEL_DEV_G || rotating;
+
+                            // Drops: free fall reads near 0 g, hitting the floor spikes
+                            let accel_g = accel_mag as f32 / one_g as f32;
+                            match fall_detector.update(accel_g) {
+                                Some(FallEvent::FreeFall) => {
+                                    info!("[EVENT] Free fall (|a|={:.2} g, t={})", accel_g, current_time_ms);
+                                    event_flash = Some((FREEFALL_COLOR, current_time_ms, FREEFALL_FLASH_MS));
                                 }
-                                AlertLevel::Alert => {
-                                    neopixel.write([RGB8::new(30, 0, 0)].into_iter()).ok(); // Red
-                                    info!("[ALERT] Alert! (tilt={:.1}°)", TILT_ANGLE);
+                                Some(FallEvent::Impact) => {
+                                    info!("[EVENT] Impact (|a|={:.2} g, t={})", accel_g, current_time_ms);
+                                    event_flash = Some((IMPACT_COLOR, current_time_ms, IMPACT_FLASH_MS));
+                                    // Leave a manual neo.strobe alone; restart an impact one
+                                    let auto_strobe = NEO_STROBE_AUTO_MS;
+                                    if NEO_STROBE_HZ == 0 || auto_strobe.is_some() {
+                                        NEO_STROBE_HZ = NEO_STROBE_MAX_HZ;
+                                        NEO_STROBE_AUTO_MS = Some(current_time_ms);
+                                    }
+                                }
+                                None => {}
+                            }
+
+                            // Shake: raw accel is fine, the high-pass drops gravity and offsets
+                            let accel_g3 = [accel.x, accel.y, accel.z].map(|v| v as f32 / one_g as f32);
+                            if shake_detector.update(current_time_ms, accel_g3) {
+                                info!("[GESTURE] Shake (t={})", current_time_ms);
+                                GESTURE = Gesture::Shake;
+                            }
+
+                            // Stationary: near 1 g, barely rotating, and the angle isn't creeping
+                            let still = accel_dev_g <= STILL_ACCEL_DEV_G
+                                && gyro.is_some_and(|g| vector_magnitude(g.x, g.y, g.z) <= STILL_GYRO_MAG);
+                            if !still || (still_since_ms.is_some() && (raw_tilt - still_ref_tilt).abs() > STILL_TILT_DRIFT_DEG) {
+                                still_since_ms = None;
+                            } else if still_since_ms.is_none() {
+                                still_since_ms = Some(current_time_ms);
+                                still_ref_tilt = raw_tilt;
+                            }
+                            let still_ms = still_since_ms.map_or(0, |t| current_time_ms.wrapping_sub(t));
+                            if still_since_ms.is_none() {
+                                sedentary_nudges = 0;
+                            }
+                            // At rest the true rate is zero, so the raw reading is the bias
+                            if still_ms >= GYRO_BIAS_STILL_MS {
+                                if let Some(g) = gyro_raw {
+                                    let mut bias = GYRO_BIAS;
+                                    bias.update([g.x, g.y, g.z]);
+                                    GYRO_BIAS = bias;
+                                }
+                            }
+                            let sedentary_ms = SEDENTARY_TIMEOUT_MIN.saturating_mul(60_000);
+                            if sedentary_ms > 0 && still_ms >= sedentary_ms.saturating_mul(sedentary_nudges + 1) {
+                                sedentary_nudges += 1;
+                                sedentary_nudge_ms = Some(current_time_ms);
+                                info!("[SEDENTARY] No movement for {} min", still_ms / 60_000);
+                            }
+                            if !CALIBRATED && still_ms >= AUTOCAL_STILL_MS {
+                                if AUTOCAL {
+                                    info!("[CALIB] Still for {} ms and uncalibrated, calibrating", still_ms);
+                                    still_since_ms = None;
+                                    autocal_due = true;
+                                } else if !autocal_hinted {
+                                    autocal_hinted = true;
+                                    let _ = uart.write_str("\r\nDevice is still but not calibrated: run device.cal_zero (or posture.autocal on)\r\n> ");
                                 }
                             }
                         }
+                    }
 
-                        // Handle LED blinking for Warning/Alert
-                        match ALERT_LEVEL {
-                            AlertLevel::Normal => {
-                                led.set_low();
-                                LED_STATE = false;
+                    // An injected tilt (inject_tilt, the demo) replaces the sensor angle, and
+                    // still drives the alert logic when there is no IMU sample to read
+                    let injected = unsafe { TILT_OVERRIDE };
+                    if accel.is_some() || injected {
+                        unsafe {
+                            if injected {
+                                TILT_ANGLE = TILT_OVERRIDE_DEG;
                             }
-                            AlertLevel::Warning => {
-                                // Blink at 1 Hz (500ms period)
-                                if current_time_ms.wrapping_sub(led_last_toggle_ms) >= 500 {
-                                    LED_STATE = !LED_STATE;
-                                    if LED_STATE {
-                                        led.set_high();
-                                    } else {
-                                        led.set_low();
+                            session_tilt.update(TILT_ANGLE);
+                            session_tilt_max = session_tilt_max.max(TILT_ANGLE);
+                            if STREAM_AVG {
+                                stream_tilt_avg.update(TILT_ANGLE);
+                            }
+
+                            // Determine alert level (may drop while moving, but not rise)
+                            let prev_alert = ALERT_LEVEL;
+                            let level = if TILT_ANGLE < TILT_WARNING_THRESHOLD {
+                                AlertLevel::Normal
+                            } else if TILT_ANGLE < TILT_ALERT_THRESHOLD {
+                                AlertLevel::Warning
+                            } else {
+                                AlertLevel::Alert
+                            };
+                            ALERT_LEVEL = if MOVING && level as u8 > prev_alert as u8 { prev_alert } else { level };
+
+                            // Raw stream: one binary frame per sample, no text framing
+                            if MODE == Mode::Raw {
+                                let frame = raw_frame(TILT_ANGLE, raw_flags(), raw_seq);
+                                let mut sent = 0;
+                                while sent < frame.len() {
+                                    match uart.write(&frame[sent..]) {
+                                        Ok(n) => sent += n,
+                                        Err(_) => break,
                                     }
-                                    led_last_toggle_ms = current_time_ms;
                                 }
+                                raw_seq = raw_seq.wrapping_add(1);
                             }
-                            AlertLevel::Alert => {
-                                // Blink at 5 Hz (100ms period)
-                                if current_time_ms.wrapping_sub(led_last_toggle_ms) >= 100 {
-                                    LED_STATE = !LED_STATE;
-                                    if LED_STATE {
-                                        led.set_high();
-                                    } else {
-                                        led.set_low();
+
+                            // Neopixel and LED follow every level change; only the
+                            // notification (log line, buzzer) is held back by posture.cooldown
+                            if ALERT_LEVEL != prev_alert {
+                                ALERT_COUNTS[ALERT_LEVEL as usize] += 1;
+                                alert_fade.set_target(alert_color(ALERT_LEVEL), current_time_ms);
+                                if ALERT_LEVEL == AlertLevel::Normal {
+                                    hw.set_led(false);
+                                    LED_STATE = false;
+                                }
+                                let notify = ALERT_LEVEL == AlertLevel::Normal
+                                    || alert_notified_ms[ALERT_LEVEL as usize]
+                                        .map_or(true, |t| current_time_ms.wrapping_sub(t) >= ALERT_COOLDOWN_MS);
+                                alert_announced = notify;
+                                if notify {
+                                    alert_notified_ms[ALERT_LEVEL as usize] = Some(current_time_ms);
+                                    let mut limit = ALERT_LOG_LIMIT[ALERT_LEVEL as usize];
+                                    if let Some(suppressed) = limit.check(current_time_ms) {
+                                        let tilt = TILT_ANGLE;
+                                        match ALERT_LEVEL {
+                                            AlertLevel::Normal => {
+                                                info!("[ALERT] Normal (tilt={:.1}°){}", tilt, suppressed)
+                                            }
+                                            AlertLevel::Warning => {
+                                                info!("[ALERT] Warning (tilt={:.1}°){}", tilt, suppressed)
+                                            }
+                                            AlertLevel::Alert => {
+                                                info!("[ALERT] Alert! (tilt={:.1}°){}", tilt, suppressed)
+                                            }
+                                        }
                                     }
-                                    led_last_toggle_ms = current_time_ms;
+                                    ALERT_LOG_LIMIT[ALERT_LEVEL as usize] = limit;
                                 }
                             }
                         }
                     }
                 }
+
+                // Alert color crossfade, then one last write of the exact target
+                if alert_fade.is_active(current_time_ms) {
+                    hw.set_neo(alert_fade.tick(current_time_ms));
+                    alert_fading = true;
+                } else if alert_fading {
+                    hw.set_neo(alert_fade.target());
+                    alert_fading = false;
+                }
+
+                // Handle LED blinking for Warning/Alert
+                unsafe {
+                    match ALERT_LEVEL {
+                        AlertLevel::Normal => {
+                            hw.set_led(false);
+                            LED_STATE = false;
+                        }
+                        AlertLevel::Warning => {
+                            // Blink at 1 Hz (500ms period)
+                            if current_time_ms.wrapping_sub(led_last_toggle_ms) >= 500 {
+                                LED_STATE = !LED_STATE;
+                                hw.set_led(LED_STATE);
+                                led_last_toggle_ms = current_time_ms;
+                            }
+                        }
+                        AlertLevel::Alert => {
+                            // Blink at 5 Hz (100ms period)
+                            if current_time_ms.wrapping_sub(led_last_toggle_ms) >= 100 {
+                                LED_STATE = !LED_STATE;
+                                hw.set_led(LED_STATE);
+                                led_last_toggle_ms = current_time_ms;
+                            }
+                        }
+                    }
+                }
+
+                // Posture score: sample the alert level at a fixed rate, whatever the IMU period
+                if current_time_ms.wrapping_sub(score_sample_ms) >= SCORE_SAMPLE_MS {
+                    score_sample_ms = current_time_ms;
+                    score_window.push(unsafe { ALERT_LEVEL } as u8);
+                }
+                if current_time_ms.wrapping_sub(score_update_ms) >= SCORE_UPDATE_MS && !score_window.is_empty() {
+                    score_update_ms = current_time_ms;
+                    unsafe {
+                        POSTURE_SCORE = Some([0, 1, 2].map(|level| score_window.percent(level).unwrap_or(0)));
+                        POSTURE_SCORE_SPAN_S = score_window.len() as u32 * SCORE_SAMPLE_MS / 1000;
+                    }
+                }
+
+                // Sedentary nudge: triangle-wave blue pulses, then back to the alert color
+                if let Some(start) = sedentary_nudge_ms {
+                    let t = current_time_ms.wrapping_sub(start);
+                    if t >= SEDENTARY_PULSE_MS * SEDENTARY_PULSES {
+                        sedentary_nudge_ms = None;
+                        hw.set_neo(alert_color(unsafe { ALERT_LEVEL }));
+                    } else {
+                        let half = SEDENTARY_PULSE_MS / 2;
+                        let phase = t % SEDENTARY_PULSE_MS;
+                        let ramp = if phase < half { phase } else { SEDENTARY_PULSE_MS - phase };
+                        let blue = (ramp * SEDENTARY_BLUE_MAX / half) as u8;
+                        hw.set_neo(RGB8::new(0, 0, blue));
+                    }
+                }
+
+                // Free-fall/impact flash, over everything else until it ends
+                if let Some((color, start, length)) = event_flash {
+                    if current_time_ms.wrapping_sub(start) >= length {
+                        event_flash = None;
+                        hw.set_neo(alert_color(unsafe { ALERT_LEVEL }));
+                    } else {
+                        hw.set_neo(color);
+                    }
+                }
+
+                // Shake (detected above or set from GDB): back to uncalibrated. The saved
+                // calibration stays in flash, so cal.load (or a reboot) brings it back.
+                if unsafe { GESTURE } == Gesture::Shake {
+                    unsafe {
+                        GESTURE = Gesture::None;
+                        CAL_OFFSET_X = 0;
+                        CAL_OFFSET_Y = 0;
+                        CAL_OFFSET_Z = 0;
+                        CALIBRATED = false;
+                        if CAL_HW {
+                            program_hw_offsets(&mut i2c, imu_addr, false).ok();
+                        }
+                    }
+                    filters_primed = false;
+                    autocal_hinted = false;
+                    shake_flash_ms = Some(current_time_ms);
+                    info!("[CALIB] Cleared by shake gesture");
+                    if unsafe { MODE } == Mode::CLI {
+                        let _ = uart.write_str("\r\nShake: calibration cleared (cal.load restores the saved one)\r\n> ");
+                    }
+                }
+
+                // Shake acknowledgement: white blinks, over everything else until they end
+                if let Some(start) = shake_flash_ms {
+                    let t = current_time_ms.wrapping_sub(start);
+                    if t >= SHAKE_FLASH_PERIOD_MS * SHAKE_FLASHES {
+                        shake_flash_ms = None;
+                        hw.set_neo(alert_color(unsafe { ALERT_LEVEL }));
+                    } else if t % SHAKE_FLASH_PERIOD_MS < SHAKE_FLASH_PERIOD_MS / 2 {
+                        hw.set_neo(SHAKE_FLASH_COLOR);
+                    } else {
+                        hw.set_neo(RGB8::new(0, 0, 0));
+                    }
+                }
+
+                // After the sample's alert handling, so nothing repaints over the calibration color
+                if autocal_due {
+                    transition_to(DeviceState::Calibrating, &mut hw);
+                }
             }
             DeviceState::Calibrating => {
+                // Measure against factory trim, not the previous hardware offsets
+                if unsafe { CAL_HW && CALIBRATION_SAMPLES == 0 } {
+                    program_hw_offsets(&mut i2c, imu_addr, false).ok();
+                }
                 // Collect calibration samples
-                if let Ok(accel) = mpu::read_accel(&mut i2c) {
+                if let Ok(accel) = mpu::read_accel(&mut i2c, imu_addr) {
+                    let still_limit = (CAL_STILL_GYRO_DPS * GYRO_LSB_PER_DPS) as i32;
+                    let moving = mpu::read_gyro(&mut i2c, imu_addr)
+                        .is_ok_and(|g| [g.x, g.y, g.z].iter().any(|&v| (v as i32).abs() > still_limit));
                     unsafe {
-                        if CALIBRATION_SAMPLES < 100 {
+                        // Fresh run (button, CLI or GDB reset the counter): clear the sums
+                        if CALIBRATION_SAMPLES == 0 {
+                            cal_accel_x_sum = 0;
+                            cal_accel_y_sum = 0;
+                            cal_accel_z_sum = 0;
+                        }
+                        if moving {
+                            // Every one of the 100 samples must be still; start over from scratch next time
+                            info!("[CALIB] Aborted: device moving");
+                            let _ = uart.write_str("ERROR: Calibration aborted, device moving\r\n");
+                            cal_attempt = 0;
+                            if CAL_HW {
+                                program_hw_offsets(&mut i2c, imu_addr, true).ok();
+                            }
+                            transition_to(cal_return_state, &mut hw);
+                            hw.set_neo(RGB8::new(30, 0, 0));
+                            cal_abort_flash_ms = Some(current_time_ms);
+                        } else if CALIBRATION_SAMPLES < 100 {
                             cal_accel_x_sum += accel.x as i32;
                             cal_accel_y_sum += accel.y as i32;
                             cal_accel_z_sum += accel.z as i32;
                             CALIBRATION_SAMPLES += 1;
 
                             if CALIBRATION_SAMPLES >= 100 {
-                                CAL_OFFSET_X = (cal_accel_x_sum / 100) as i16;
-                                CAL_OFFSET_Y = (cal_accel_y_sum / 100) as i16;
-                                CAL_OFFSET_Z = (cal_accel_z_sum / 100) as i16 - 16384; // Gravity offset
-                                info!(
-                                    "[CALIB] Complete! Offsets: x={}, y={}, z={}",
-                                    CAL_OFFSET_X, CAL_OFFSET_Y, CAL_OFFSET_Z
-                                );
-                                DEVICE_STATE = DeviceState::Monitoring;
-                                neopixel.write([RGB8::new(0, 30, 0)].into_iter()).ok(); // Green
+                                let offset_x = (cal_accel_x_sum / 100) as i16;
+                                let offset_y = (cal_accel_y_sum / 100) as i16;
+                                let offset_z = (cal_accel_z_sum / 100 - accel_1g()) as i16; // Gravity offset
+
+                                // Verify: a fresh sample with the new offsets should read ~0° tilt.
+                                // If not, the device moved during collection.
+                                let verify_tilt = mpu::read_accel(&mut i2c, imu_addr).ok().map(|a| {
+                                    mpu::tilt::tilt_angle(
+                                        a.x as i32 - offset_x as i32,
+                                        a.y as i32 - offset_y as i32,
+                                        a.z as i32 - offset_z as i32,
+                                    )
+                                });
+
+                                let retries = CAL_RETRIES;
+                                match verify_tilt {
+                                    Some(tilt) if tilt <= CAL_VERIFY_TOLERANCE_DEG => {
+                                        CAL_OFFSET_X = offset_x;
+                                        CAL_OFFSET_Y = offset_y;
+                                        CAL_OFFSET_Z = offset_z;
+                                        CALIBRATED = true;
+                                        if CAL_HW {
+                                            program_hw_offsets(&mut i2c, imu_addr, true).ok();
+                                        }
+                                        info!(
+                                            "[CALIB] Complete! Offsets: x={}, y={}, z={}",
+                                            offset_x, offset_y, offset_z
+                                        );
+                                        if save_calibration(&mut hw.flash).is_err() {
+                                            report_fault(ErrorCode::Flash, &mut uart);
+                                        }
+                                        // The new offsets zero the current position on Z, which
+                                        // moves the frame a mount reference was captured in
+                                        let mount_ref = MOUNT_REF;
+                                        if mount_ref.is_some() {
+                                            MOUNT_REF = None;
+                                            info!("[CALIB] Mount reference cleared, tilt is from vertical again");
+                                            if save_mount(&mut hw.flash).is_err() {
+                                                report_fault(ErrorCode::Flash, &mut uart);
+                                            }
+                                        }
+                                        cal_attempt = 0;
+                                        transition_to(DeviceState::Monitoring, &mut hw);
+                                    }
+                                    _ if cal_attempt < retries => {
+                                        cal_attempt += 1;
+                                        info!(
+                                            "[CALIB] Verify failed (device moved?), retry {}/{}",
+                                            cal_attempt, retries
+                                        );
+                                        CALIBRATION_SAMPLES = 0;
+                                    }
+                                    _ => {
+                                        // Out of retries: keep the previous offsets and stop in Sleep
+                                        // with the Neopixel left red so the failure is visible
+                                        info!("[CALIB] Failed after {} attempts, offsets unchanged", cal_attempt + 1);
+                                        let _ = uart.write_str("ERROR: Calibration failed, keep device still and retry\r\n");
+                                        cal_attempt = 0;
+                                        if CAL_HW {
+                                            program_hw_offsets(&mut i2c, imu_addr, true).ok();
+                                        }
+                                        transition_to(DeviceState::Sleep, &mut hw);
+                                        hw.set_neo(RGB8::new(30, 0, 0));
+                                    }
+                                }
                             }
                         }
                     }
@@ -337,27 +1094,95 @@ fn main() -> ! {
             }
         }
 
+        // Aborted calibration: red for a moment, then the state's own color
+        if let Some(start) = cal_abort_flash_ms {
+            if current_time_ms.wrapping_sub(start) >= CAL_ABORT_FLASH_MS {
+                cal_abort_flash_ms = None;
+                match unsafe { DEVICE_STATE } {
+                    DeviceState::Monitoring => hw.set_neo(alert_color(unsafe { ALERT_LEVEL })),
+                    DeviceState::Sleep => hw.set_neo(RGB8::new(0, 0, 0)),
+                    DeviceState::Calibrating => {} // Restarted meanwhile, already yellow
+                }
+            }
+        }
+
+        // A fatal error owns the LED: overrides alert blinking and gpio.on/off
+        if let Some(code) = unsafe { FAULT } {
+            hw.set_led(code.led_on(current_time_ms));
+        }
+
+        // Beep pattern for the alert level; silent outside Monitoring, when muted
+        // or when posture.cooldown held back this level's notification
+        let beeping = unsafe { DEVICE_STATE == DeviceState::Monitoring && !BUZZER_MUTED }
+            && alert_announced
+            && buzzer_pattern_on(unsafe { ALERT_LEVEL }, current_time_ms);
+        hw.buzzer.set_sounding(beeping);
+
         // CLI vs Streaming mode
         let current_mode = unsafe { MODE };
+        if current_mode != Mode::Streaming {
+            unsafe {
+                CSV_HEADER_SENT = false;
+            }
+        }
         match current_mode {
             Mode::CLI => {
                 let mut rx_byte = [0u8; 1];
                 if uart.read(&mut rx_byte).is_ok() {
                     let ch = rx_byte[0] as char;
+                    last_rx_ms = current_time_ms;
 
-                    if ch == '\r' || ch == '\n' {
+                    if unsafe { DEMO_STEP } != 0 {
+                        // Any key stops the demo; the key itself is discarded
+                        unsafe {
+                            DEMO_STEP = DEMO_STOP;
+                        }
+                    } else if ch == '\r' || ch == '\n' {
+                        line_too_long = false;
                         if !cmd_buffer.is_empty() {
                             let _ = uart.write_str("\r\n");
-                            process_command(&cmd_buffer, &mut led, &mut neopixel, &mut i2c, &mut uart);
+                            let ok = run_line(&cmd_buffer, &mut hw, &mut i2c, &mut uart);
+                            // While recording, keep lines that worked (macro.* itself is never recorded)
+                            if ok && !mpu::macros::runs_macro_command(&cmd_buffer) {
+                                let recorded = critical_section::with(|cs| {
+                                    let mut macros = MACROS.borrow_ref_mut(cs);
+                                    macros.recording().is_some().then(|| macros.record(&cmd_buffer))
+                                });
+                                if let Some(Err(_)) = recorded {
+                                    let _ = uart.write_str("ERROR: Macro is full, line not recorded\r\n");
+                                }
+                            }
                             cmd_buffer.clear();
                             let _ = uart.write_str("> ");
                         }
                     } else if ch == '\x08' || ch == '\x7f' {
-                        if cmd_buffer.pop().is_some() {
+                        if cmd_buffer.pop().is_some() && unsafe { TERM_ECHO } {
                             let _ = uart.write_str("\x08 \x08");
                         }
+                    } else if line_too_long {
+                        // Rest of an overlong line, already reported
+                    } else if ch.is_ascii_graphic() || ch == ' ' {
+                        if cmd_buffer.push(ch).is_err() {
+                            line_too_long = true;
+                            report_line_too_long(&mut cmd_buffer, &mut uart);
+                        } else if unsafe { TERM_ECHO } {
+                            let _ = uart.write(&[ch as u8]);
+                        }
+                    }
+                }
+            }
+            Mode::Raw => {
+                // No echo or prompt (the host is reading binary); only `stream.raw off` is accepted
+                let mut rx_byte = [0u8; 1];
+                if uart.read(&mut rx_byte).is_ok() {
+                    let ch = rx_byte[0] as char;
+                    if ch == '\r' || ch == '\n' {
+                        if cmd_buffer.trim() == "stream.raw off" {
+                            process_command(&cmd_buffer, &mut hw, &mut i2c, &mut uart);
+                            let _ = uart.write_str("> ");
+                        }
+                        cmd_buffer.clear();
                     } else if ch.is_ascii_graphic() || ch == ' ' {
-                        let _ = uart.write(&[ch as u8]);
                         let _ = cmd_buffer.push(ch);
                     }
                 }
@@ -366,9 +1191,14 @@ fn main() -> ! {
                 if current_time_ms.wrapping_sub(last_stream_time_ms) >= 100 {
                     last_stream_time_ms = current_time_ms;
                     counter = counter.wrapping_add(1);
+                    seq = seq.wrapping_add(1);
+                    let csv = unsafe { STREAM_CSV };
+                    if seq == 0 && !csv {
+                        write_stream_line(&mut uart, "[seq wrapped]");
+                    }
 
                     let mut msg: String<256> = String::new();
-                    let (state, alert, tilt, ax, ay, az, led_st) = unsafe {
+                    let (state, alert, mut tilt, mut ax, mut ay, mut az, led_st, moving, score) = unsafe {
                         (
                             DEVICE_STATE,
                             ALERT_LEVEL,
@@ -377,128 +1207,95 @@ fn main() -> ! {
                             IMU_ACCEL_Y,
                             IMU_ACCEL_Z,
                             LED_STATE,
+                            MOVING,
+                            POSTURE_SCORE.map(|p| p[0]),
                         )
                     };
 
-                    write!(
-                        msg,
-                        "[dev=PostureMonitor state={:?} alert={:?} tilt={:.1}° accel=({},{},{}) led={} cnt={} t={}]\r\n",
-                        state, alert, tilt, ax, ay, az, if led_st { "on" } else { "off" }, counter, current_time_ms
-                    )
-                    .ok();
+                    // Mean of the samples since the last line (falls back to the
+                    // latest values if none were taken, e.g. outside Monitoring)
+                    if unsafe { STREAM_AVG } && stream_tilt_avg.count() > 0 {
+                        tilt = stream_tilt_avg.mean();
+                        ax = stream_accel_avg[0].mean() as i16;
+                        ay = stream_accel_avg[1].mean() as i16;
+                        az = stream_accel_avg[2].mean() as i16;
+                    }
+                    stream_tilt_avg = Welford::new();
+                    stream_accel_avg = [Welford::new(); 3];
 
-                    let _ = uart.write_str(&msg);
+                    if csv {
+                        if !unsafe { CSV_HEADER_SENT } {
+                            write_stream_line(&mut uart, "seq,t_ms,state,alert,tilt,ax,ay,az,led,score");
+                            unsafe {
+                                CSV_HEADER_SENT = true;
+                            }
+                        }
+                        write!(
+                            msg,
+                            "{},{},{:?},{:?},{:.1},{},{},{},{}",
+                            seq,
+                            current_time_ms,
+                            state,
+                            alert,
+                            tilt,
+                            ax,
+                            ay,
+                            az,
+                            led_st as u8
+                        )
+                        .ok();
+                        // Empty field until the first score of the session
+                        msg.push(',').ok();
+                        if let Some(score) = score {
+                            write!(msg, "{}", score).ok();
+                        }
+                    } else {
+                        write!(
+                            msg,
+                            "[dev=PostureMonitor state={:?} alert={:?} tilt={:.1}° accel=({},{},{}) led={}{}",
+                            state,
+                            alert,
+                            tilt,
+                            ax,
+                            ay,
+                            az,
+                            if led_st { "on" } else { "off" },
+                            if moving { " MOVING" } else { "" },
+                        )
+                        .ok();
+                        if let Some(score) = score {
+                            write!(msg, " score={}%", score).ok();
+                        }
+                        write!(msg, " seq={} cnt={} t={}]", seq, counter, current_time_ms).ok();
+                    }
+
+                    write_stream_line(&mut uart, &msg);
                 }
             }
         }
 
-        delay.delay_millis(10);
-    }
-}
-
-fn process_command<W: Write, Dm: esp_hal::DriverMode>(
-    cmd: &str,
-    led: &mut Output,
-    neopixel: &mut SmartLedsAdapter<{ buffer_size(1) }, Blocking, color_order::Rgb, Ws2812Timing>,
-    i2c: &mut I2c<Dm>,
-    uart: &mut W,
-) {
-    let cmd_trimmed = cmd.trim();
-    let parts: heapless::Vec<&str, 5> = cmd_trimmed.split_whitespace().collect();
-
-    if parts.is_empty() {
-        return;
-    }
-
-    match parts[0] {
-        "help" => {
-            let _ = uart.write_str("Posture Monitor Commands:\r\n");
-            let _ = uart.write_str("  device.start        - Start monitoring\r\n");
-            let _ = uart.write_str("  device.cal_zero     - Calibrate zero orientation\r\n");
-            let _ = uart.write_str("  device.sleep        - Enter sleep mode\r\n");
-            let _ = uart.write_str("  device.status       - Show device status\r\n");
-            let _ = uart.write_str("  gpio.on/off         - LED control\r\n");
-            let _ = uart.write_str("  neo.color <r> <g> <b> - Set Neopixel\r\n");
-            let _ = uart.write_str("  imu.read            - Read accel data\r\n");
-            let _ = uart.write_str("  stream.start/stop   - Toggle streaming\r\n");
-        }
-        "device.start" => {
-            unsafe {
-                DEVICE_STATE = DeviceState::Monitoring;
-            }
-            let _ = uart.write_str("OK [Posture Monitor started]\r\n");
-        }
-        "device.cal_zero" => {
-            unsafe {
-                DEVICE_STATE = DeviceState::Calibrating;
-                CALIBRATION_SAMPLES = 0;
-            }
-            let _ = uart.write_str("OK [Calibrating zero orientation...]\r\n");
-        }
-        "device.sleep" => {
+        // Sleep state: light-sleep instead of spinning, once the shutdown fade is
+        // done, the button is released and nobody is typing. Wakes on a button
+        // press or after LIGHT_SLEEP_TIMER_MS; the loop then runs as usual, so a
+        // long press still leaves Sleep and anything else sleeps again.
+        let light_sleep = unsafe { LIGHT_SLEEP && DEVICE_STATE == DeviceState::Sleep && MODE == Mode::CLI }
+            && !frozen
+            && fade_start_ms.is_none()
+            && hw.button.is_high()
+            && cmd_buffer.is_empty()
+            && current_time_ms.wrapping_sub(last_rx_ms) >= LIGHT_SLEEP_RX_IDLE_MS;
+        if light_sleep {
+            // Light sleep gates the UART clock: let the last reply finish first
+            let _ = uart.flush();
+            let timer = TimerWakeupSource::new(core::time::Duration::from_millis(LIGHT_SLEEP_TIMER_MS));
+            let gpio = GpioWakeupSource::new();
+            hw.feed_watchdog();
+            hw.rtc.sleep_light(&[&timer, &gpio]);
             unsafe {
-                DEVICE_STATE = DeviceState::Sleep;
+                LIGHT_SLEEP_COUNT += 1;
             }
-            neopixel.write([RGB8::new(0, 0, 0)].into_iter()).ok();
-            led.set_low();
-            let _ = uart.write_str("OK [Sleep mode]\r\n");
-        }
-        "device.status" => {
-            let (state, alert, tilt) = unsafe { (DEVICE_STATE, ALERT_LEVEL, TILT_ANGLE) };
-            let mut buf: String<128> = String::new();
-            write!(
-                buf,
-                "Device: Posture Monitor\r\nState: {:?}\r\nAlert: {:?} (tilt={:.1}°)\r\n",
-                state, alert, tilt
-            )
-            .ok();
-            let _ = uart.write_str(&buf);
-        }
-        "gpio.on" => {
-            led.set_high();
-            let _ = uart.write_str("OK [LED ON]\r\n");
-        }
-        "gpio.off" => {
-            led.set_low();
-            let _ = uart.write_str("OK [LED OFF]\r\n");
-        }
-        "neo.color" => {
-            if parts.len() < 4 {
-                let _ = uart.write_str("ERROR: Usage: neo.color <r> <g> <b>\r\n");
-            } else if let (Ok(r), Ok(g), Ok(b)) = (
-                parts[1].parse::<u8>(),
-                parts[2].parse::<u8>(),
-                parts[3].parse::<u8>(),
-            ) {
-                neopixel.write([RGB8::new(r, g, b)].into_iter()).ok();
-                let mut buf: String<64> = String::new();
-                write!(buf, "OK [Neopixel RGB=({},{},{})]\r\n", r, g, b).ok();
-                let _ = uart.write_str(&buf);
-            }
-        }
-        "imu.read" => {
-            if let Ok(accel) = mpu::read_accel(i2c) {
-                let mut buf: String<128> = String::new();
-                write!(buf, "accel: x={} y={} z={}\r\n", accel.x, accel.y, accel.z).ok();
-                let _ = uart.write_str(&buf);
-            } else {
-                let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
-            }
-        }
-        "stream.start" => {
-            unsafe {
-                MODE = Mode::Streaming;
-            }
-            let _ = uart.write_str("[Switching to streaming mode...]\r\n");
-        }
-        "stream.stop" => {
-            unsafe {
-                MODE = Mode::CLI;
-            }
-            let _ = uart.write_str("[Switching to CLI mode...]\r\n");
-        }
-        _ => {
-            let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
+        } else {
+            delay.delay_millis(10);
         }
     }
 }
@@ -0,0 +1,63 @@
+//! Alert buzzer
+//!
+//! The tone is a fixed-frequency square wave; the main loop switches it on
+//! and off following the alert level's `BeepPattern` (`beep.rs`).
+
+use esp_hal::{
+    ledc::{
+        channel::{Channel, ChannelIFace},
+        timer::{self as ledc_timer, TimerIFace},
+        Ledc, LowSpeed,
+    },
+    time::Rate,
+};
+
+use crate::state::{AlertLevel, BUZZER_PATTERNS};
+
+/// Passive piezo on an LEDC channel (timer 0, 10-bit duty)
+pub struct Buzzer<'a> {
+    /// To retune the channel's timer (buzzer.test); the channel keeps using it
+    ledc: &'a Ledc<'static>,
+    channel: Channel<'a, LowSpeed>,
+    sounding: bool,
+}
+
+impl<'a> Buzzer<'a> {
+    /// Wrap a channel already configured on timer 0 (silent, 0% duty)
+    pub fn new(ledc: &'a Ledc<'static>, channel: Channel<'a, LowSpeed>) -> Self {
+        Self {
+            ledc,
+            channel,
+            sounding: false,
+        }
+    }
+
+    /// Program the tone frequency; false if LEDC can't divide down to it
+    pub fn set_frequency(&mut self, hz: u32) -> bool {
+        let mut timer = self.ledc.timer::<LowSpeed>(ledc_timer::Number::Timer0);
+        timer.configure(buzzer_timer_config(hz)).is_ok()
+    }
+
+    /// Start (50% duty) or stop (0% duty) the square wave
+    pub fn set_sounding(&mut self, on: bool) {
+        if on != self.sounding {
+            self.channel.set_duty(if on { 50 } else { 0 }).ok();
+            self.sounding = on;
+        }
+    }
+}
+
+/// LEDC timer settings for a buzzer tone of `hz`
+pub fn buzzer_timer_config(hz: u32) -> ledc_timer::config::Config<ledc_timer::LSClockSource> {
+    ledc_timer::config::Config {
+        duty: ledc_timer::config::Duty::Duty10Bit,
+        clock_source: ledc_timer::LSClockSource::APBClk,
+        frequency: Rate::from_hz(hz),
+    }
+}
+
+/// Whether the beep pattern for `level` is sounding at `t_ms`
+pub fn buzzer_pattern_on(level: AlertLevel, t_ms: u32) -> bool {
+    let pattern = unsafe { BUZZER_PATTERNS }[level as usize];
+    pattern.is_on(t_ms)
+}
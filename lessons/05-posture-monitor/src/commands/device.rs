@@ -0,0 +1,365 @@
+//! `device.*` and `cal.*`: state changes, calibration and device info
+
+use core::fmt::Write;
+use esp_hal::{delay::Delay, i2c::master::I2c};
+use heapless::String;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::config::*;
+use crate::hw::{transition_to, Hw};
+use crate::sensor::{accel_offsets, cal_deviation, corrected_tilt, program_hw_offsets};
+use crate::settings::{load_calibration, save_calibration, save_mount};
+use crate::state::*;
+use crate::ESP_APP_DESC;
+
+pub fn run<W: Write, Dm: esp_hal::DriverMode, N>(
+    parts: &[&str],
+    hw: &mut Hw<'_, N>,
+    i2c: &mut I2c<Dm>,
+    uart: &mut W,
+) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let imu_addr = unsafe { IMU_ADDR };
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "device.start" => {
+            transition_to(DeviceState::Monitoring, hw);
+            let _ = uart.write_str("OK [Posture Monitor started]\r\n");
+        }
+        "device.cal_zero" => {
+            transition_to(DeviceState::Calibrating, hw);
+            let _ = uart.write_str("OK [Calibrating zero orientation...]\r\n");
+        }
+        "device.cal_check" => {
+            if !unsafe { CALIBRATED } {
+                let _ = uart.write_str("ERROR: Not calibrated\r\n");
+                ok = false;
+            } else if let Ok(accel) = mpu6050::read_accel(i2c, imu_addr) {
+                let deviation = cal_deviation([accel.x, accel.y, accel.z]);
+                let mut buf: String<128> = String::new();
+                if deviation <= CAL_CHECK_MAX_DEG {
+                    write!(buf, "OK [Orientation matches calibration ({:.1}°)]\r\n", deviation).ok();
+                } else {
+                    write!(
+                        buf,
+                        "WARNING: Board is {:.1}° from calibrated orientation, run device.cal_zero\r\n",
+                        deviation
+                    )
+                    .ok();
+                }
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                ok = false;
+            }
+        }
+        "cal.verify" => {
+            if mpu6050::probe(i2c, imu_addr).is_err() {
+                let mut buf: String<48> = String::new();
+                write!(buf, "ERROR: No IMU at 0x{:02X}\r\n", imu_addr).ok();
+                let _ = uart.write_str(&buf);
+                ok = false;
+            } else {
+                // Average the vector, not per-sample tilts: noise would only ever add to those
+                let delay = Delay::new();
+                let [ox, oy, oz] = accel_offsets();
+                let mut sum = [0i32; 3];
+                let mut read_ok = true;
+                for _ in 0..CAL_VERIFY_SAMPLES {
+                    let Ok(accel) = mpu6050::read_accel(i2c, imu_addr) else {
+                        read_ok = false;
+                        break;
+                    };
+                    sum[0] += accel.x as i32 - ox;
+                    sum[1] += accel.y as i32 - oy;
+                    sum[2] += accel.z as i32 - oz;
+                    delay.delay_millis(CAL_VERIFY_SPACING_MS);
+                }
+                if read_ok {
+                    let tilt = corrected_tilt(sum.map(|v| v / CAL_VERIFY_SAMPLES));
+                    let mut buf: String<128> = String::new();
+                    if tilt <= CAL_VERIFY_TOLERANCE_DEG {
+                        write!(buf, "PASS [tilt {:.1}° (max {:.0}°)]\r\n", tilt, CAL_VERIFY_TOLERANCE_DEG).ok();
+                    } else {
+                        write!(
+                            buf,
+                            "FAIL [tilt {:.1}° (max {:.0}°): not at the calibrated zero, run device.cal_zero]\r\n",
+                            tilt, CAL_VERIFY_TOLERANCE_DEG
+                        )
+                        .ok();
+                    }
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "cal.save" => {
+            if save_calibration(&mut hw.flash).is_ok() {
+                let mut buf: String<64> = String::new();
+                let (x, y, z) = unsafe { (CAL_OFFSET_X, CAL_OFFSET_Y, CAL_OFFSET_Z) };
+                write!(buf, "OK [Calibration saved: x={} y={} z={}]\r\n", x, y, z).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Flash write failed\r\n");
+                ok = false;
+            }
+        }
+        "cal.load" => {
+            if let Some([x, y, z]) = load_calibration(&mut hw.flash) {
+                unsafe {
+                    CAL_OFFSET_X = x;
+                    CAL_OFFSET_Y = y;
+                    CAL_OFFSET_Z = z;
+                    CALIBRATED = true;
+                    if CAL_HW {
+                        program_hw_offsets(i2c, imu_addr, true).ok();
+                    }
+                }
+                let mut buf: String<64> = String::new();
+                write!(buf, "OK [Calibration loaded: x={} y={} z={}]\r\n", x, y, z).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: No saved calibration for this accel range\r\n");
+                ok = false;
+            }
+        }
+        "cal.mount" => match parts.get(1).copied() {
+            None => {
+                // Average the resting vector in the same frame the tilt is computed in
+                let delay = Delay::new();
+                let still_limit = (CAL_STILL_GYRO_DPS * GYRO_LSB_PER_DPS) as i32;
+                let [ox, oy, oz] = accel_offsets();
+                let mut sum = [0i32; 3];
+                let mut count = 0;
+                let mut moving = false;
+                for _ in 0..MOUNT_SAMPLES {
+                    hw.feed_watchdog();
+                    if let Ok(accel) = mpu6050::read_accel(i2c, imu_addr) {
+                        sum[0] += accel.x as i32 - ox;
+                        sum[1] += accel.y as i32 - oy;
+                        sum[2] += accel.z as i32 - oz;
+                        count += 1;
+                    }
+                    moving |= mpu6050::read_gyro(i2c, imu_addr)
+                        .is_ok_and(|g| [g.x, g.y, g.z].iter().any(|&v| (v as i32).abs() > still_limit));
+                    delay.delay_millis(MOUNT_SAMPLE_SPACING_MS);
+                }
+                if count == 0 {
+                    let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                    ok = false;
+                } else if moving {
+                    let _ = uart.write_str("ERROR: Device moving, keep it still in its mounted position\r\n");
+                    ok = false;
+                } else {
+                    let reference = sum.map(|s| (s / count) as i16);
+                    unsafe {
+                        MOUNT_REF = Some(reference);
+                    }
+                    let mut buf: String<80> = String::new();
+                    write!(
+                        buf,
+                        "OK [Mount reference: x={} y={} z={}]\r\n",
+                        reference[0], reference[1], reference[2]
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                    if save_mount(&mut hw.flash).is_err() {
+                        let _ = uart.write_str("ERROR: Flash write failed\r\n");
+                        ok = false;
+                    }
+                }
+            }
+            Some("clear") => {
+                unsafe {
+                    MOUNT_REF = None;
+                }
+                let _ = uart.write_str("OK [Mount reference cleared, tilt from vertical]\r\n");
+                if save_mount(&mut hw.flash).is_err() {
+                    let _ = uart.write_str("ERROR: Flash write failed\r\n");
+                    ok = false;
+                }
+            }
+            Some(_) => {
+                let _ = uart.write_str("ERROR: Usage: cal.mount [clear]\r\n");
+                ok = false;
+            }
+        },
+        "device.cal_retries" => {
+            if parts.len() < 2 {
+                let _ = uart.write_str("ERROR: Usage: device.cal_retries <n>\r\n");
+                ok = false;
+            } else if let Ok(n) = parts[1].parse::<u8>() {
+                if n <= CAL_RETRIES_MAX {
+                    unsafe {
+                        CAL_RETRIES = n;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "OK [Calibration retries = {}]\r\n", n).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Retries must be 0-10\r\n");
+                    ok = false;
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid retry count\r\n");
+                ok = false;
+            }
+        }
+        "device.cal_hw" => {
+            let enable = match parts.get(1).copied() {
+                None => {
+                    let trim = unsafe { ACCEL_TRIM };
+                    let mut buf: String<96> = String::new();
+                    write!(
+                        buf,
+                        "Offset correction = {} (factory trim x={} y={} z={})\r\n",
+                        if unsafe { CAL_HW } { "hardware" } else { "software" },
+                        trim[0],
+                        trim[1],
+                        trim[2]
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                    return true;
+                }
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => None,
+            };
+            match enable {
+                // The offset registers are at 0x06 only on the MPU6050 (WHO_AM_I 0x68)
+                Some(true) if mpu6050::read_who_am_i(i2c, imu_addr) != Ok(0x68) => {
+                    let _ = uart.write_str("ERROR: Hardware offsets need an MPU6050\r\n");
+                    ok = false;
+                }
+                Some(on) => {
+                    if program_hw_offsets(i2c, imu_addr, on).is_ok() {
+                        unsafe {
+                            CAL_HW = on;
+                        }
+                        let _ = uart.write_str(if on {
+                            "OK [Offsets applied by MPU registers]\r\n"
+                        } else {
+                            "OK [Offsets applied in software]\r\n"
+                        });
+                    } else {
+                        let _ = uart.write_str("ERROR: Failed to write MPU offset registers\r\n");
+                        ok = false;
+                    }
+                }
+                None => {
+                    let _ = uart.write_str("ERROR: Usage: device.cal_hw [on|off]\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "device.sleep" => {
+            transition_to(DeviceState::Sleep, hw);
+            let _ = uart.write_str("OK [Sleep mode]\r\n");
+        }
+        "device.light_sleep" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    LIGHT_SLEEP = true;
+                }
+                let _ = uart.write_str("OK [Light sleep in Sleep state, wake with the button]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    LIGHT_SLEEP = false;
+                }
+                let _ = uart.write_str("OK [Light sleep off, Sleep keeps the CPU running]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: device.light_sleep <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "device.boots" => {
+            let mut buf: String<32> = String::new();
+            write!(buf, "Boots: {}\r\n", unsafe { BOOT_COUNT }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "device.appdesc" => {
+            // ESP_APP_DESC is the static that esp_app_desc!() places in the image
+            // header; the bootloader reads the same bytes
+            let desc = &ESP_APP_DESC;
+            let mut buf: String<256> = String::new();
+            write!(
+                buf,
+                "Project: {}\r\nVersion: {}\r\nIDF: {}\r\nBuilt: {} {}\r\nMagic: 0x{:08X} secure_version={}\r\n",
+                desc.project_name(),
+                desc.version(),
+                desc.idf_ver(),
+                desc.date(),
+                desc.time(),
+                desc.magic_word(),
+                desc.secure_version()
+            )
+            .ok();
+            let _ = uart.write_str(&buf);
+        }
+        "device.status" => {
+            let (state, alert, tilt, warn, alert_th, knob, frozen) = unsafe {
+                (
+                    DEVICE_STATE,
+                    ALERT_LEVEL,
+                    TILT_ANGLE,
+                    TILT_WARNING_THRESHOLD,
+                    TILT_ALERT_THRESHOLD,
+                    KNOB_ENABLED,
+                    TELEMETRY_FROZEN,
+                )
+            };
+            let (temp, temp_limit, temp_sleep, over_temp) =
+                unsafe { (TEMP_C, TEMP_LIMIT_C, TEMP_SLEEP_ON_LIMIT, OVER_TEMP) };
+            let mut buf: String<448> = String::new();
+            write!(
+                buf,
+                "Device: Posture Monitor\r\nState: {:?}\r\nAlert: {:?} (tilt={:.1}°)\r\nThresholds: warn={:.1}° alert={:.1}° (knob {})\r\n",
+                state, alert, tilt, warn, alert_th, if knob { "on" } else { "off" }
+            )
+            .ok();
+            write!(
+                buf,
+                "Temp: {:.1}°C (limit {:.1}°C, {}){}\r\n",
+                temp,
+                temp_limit,
+                if temp_sleep { "sleep" } else { "warn" },
+                if over_temp { " OVER" } else { "" }
+            )
+            .ok();
+            write!(
+                buf,
+                "LED: GPIO{} {}\r\n",
+                LED_PIN,
+                if LED_ACTIVE_LOW { "active-low" } else { "active-high" }
+            )
+            .ok();
+            match unsafe { MOUNT_REF } {
+                Some([x, y, z]) => write!(buf, "Tilt reference: mount x={} y={} z={}\r\n", x, y, z).ok(),
+                None => write!(buf, "Tilt reference: vertical (+Z)\r\n").ok(),
+            };
+            if let Some(code) = unsafe { FAULT } {
+                write!(buf, "Fault: {} (LED blinks {}x)\r\n", code.description(), code.blinks()).ok();
+            }
+            if frozen {
+                buf.push_str("Telemetry: FROZEN (debug.freeze off to resume)\r\n").ok();
+            }
+            if unsafe { TILT_OVERRIDE } {
+                buf.push_str("Tilt: INJECTED (call clear_tilt_override() to resume)\r\n").ok();
+            }
+            let _ = uart.write_str(&buf);
+        }
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
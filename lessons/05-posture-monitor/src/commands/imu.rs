@@ -0,0 +1,462 @@
+//! `imu.*`, `imu2.*` and friends: raw sensor access, registers and the estimators
+
+use core::fmt::Write;
+use errcode::ErrorCode;
+use esp_hal::{delay::Delay, i2c::master::I2c, time::Instant};
+use heapless::String;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::config::*;
+use crate::filter::{GyroBias, YawIntegrator};
+use crate::hw::Hw;
+use crate::sensor::program_hw_offsets;
+use crate::state::*;
+use crate::DlpfConfig;
+
+pub fn run<W: Write, Dm: esp_hal::DriverMode, N>(
+    parts: &[&str],
+    hw: &mut Hw<'_, N>,
+    i2c: &mut I2c<Dm>,
+    uart: &mut W,
+) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let imu_addr = unsafe { IMU_ADDR };
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "imu.addr" => {
+            let new_addr = match parts.get(1).copied() {
+                None => {
+                    let mut buf: String<32> = String::new();
+                    write!(buf, "IMU address = 0x{:02X}\r\n", imu_addr).ok();
+                    let _ = uart.write_str(&buf);
+                    return true;
+                }
+                Some("0x68") => Some(mpu6050::MPU_ADDR),
+                Some("0x69") => Some(mpu6050::MPU_ADDR_ALT),
+                Some(_) => None,
+            };
+            if let Some(addr) = new_addr {
+                // Only switch if a sensor answers there, so a typo can't orphan the IMU
+                if mpu6050::wake_sensor(i2c, addr).is_ok() {
+                    mpu6050::set_accel_range(i2c, addr, unsafe { ACCEL_RANGE }).ok();
+                    mpu6050::set_dlpf(i2c, addr, unsafe { DLPF }).ok();
+                    mpu6050::enable_data_ready(i2c, addr).ok();
+                    unsafe {
+                        if matches!(FAULT, Some(ErrorCode::Sensor | ErrorCode::Bus)) {
+                            FAULT = None;
+                            hw.set_led(false);
+                        }
+                        // Hardware offsets belong to the old sensor: restore its
+                        // trim and fall back to software correction
+                        if CAL_HW {
+                            program_hw_offsets(i2c, imu_addr, false).ok();
+                            CAL_HW = false;
+                        }
+                        ACCEL_TRIM = mpu6050::read_hardware_offsets(i2c, addr).unwrap_or([0; 3]);
+                        IMU_ADDR = addr;
+                    }
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "OK [IMU address=0x{:02X}]\r\n", addr).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "ERROR: No MPU6050 at 0x{:02X}\r\n", addr).ok();
+                    let _ = uart.write_str(&buf);
+                    ok = false;
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Usage: imu.addr [0x68|0x69]\r\n");
+                ok = false;
+            }
+        }
+        "imu.read" => {
+            if let Ok(accel) = mpu6050::read_accel(i2c, imu_addr) {
+                let mut buf: String<128> = String::new();
+                write!(buf, "accel: x={} y={} z={}\r\n", accel.x, accel.y, accel.z).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                ok = false;
+            }
+        }
+        "imu.dump" => {
+            // 10 registers as "RR:VV " pairs
+            let mut buf: String<80> = String::new();
+            let mut regs = [0u8; 4];
+            let mut read_ok = true;
+            for &(first, count) in &IMU_DUMP_BLOCKS {
+                if mpu6050::read_regs(i2c, imu_addr, first, &mut regs[..count]).is_err() {
+                    read_ok = false;
+                    break;
+                }
+                for (reg, value) in (first..).zip(&regs[..count]) {
+                    write!(buf, "{:02X}:{:02X} ", reg, value).ok();
+                }
+            }
+            if read_ok {
+                buf.pop();
+                buf.push_str("\r\n").ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                ok = false;
+            }
+        }
+        "imu.alpha" => match parts.get(1).map(|a| a.parse::<f32>()) {
+            None => {
+                let mut buf: String<48> = String::new();
+                write!(buf, "alpha = {}\r\n", unsafe { COMPLEMENTARY }.alpha).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(alpha)) if (0.0..=1.0).contains(&alpha) => {
+                // Keep the current angle; only the blend changes from the next sample
+                let mut comp = unsafe { COMPLEMENTARY };
+                comp.alpha = alpha;
+                unsafe {
+                    COMPLEMENTARY = comp;
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Complementary alpha={}]\r\n", alpha).ok();
+                let _ = uart.write_str(&buf);
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: imu.alpha <0.0-1.0>\r\n");
+                ok = false;
+            }
+        },
+        "imu.yaw" => {
+            let mut buf: String<48> = String::new();
+            write!(buf, "yaw = {:.1} deg\r\n", unsafe { YAW_ANGLE }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "imu.rpy" => {
+            let mut buf: String<64> = String::new();
+            write!(buf, "roll = {:.1} deg, pitch = {:.1} deg\r\n", unsafe { ROLL_ANGLE }, unsafe { PITCH_ANGLE }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "yaw.reset" => {
+            unsafe {
+                YAW = YawIntegrator::new();
+                YAW_ANGLE = 0.0;
+            }
+            let _ = uart.write_str("OK [Yaw = 0]\r\n");
+        }
+        "imu.gyrobias" => {
+            let bias = unsafe { GYRO_BIAS };
+            let [x, y, z] = bias.bias().map(|v| v / GYRO_LSB_PER_DPS);
+            let mut buf: String<96> = String::new();
+            write!(buf, "gyro bias x={:.3} y={:.3} z={:.3} dps ({} samples)\r\n", x, y, z, bias.samples()).ok();
+            let _ = uart.write_str(&buf);
+        }
+        "gyrobias.reset" => {
+            unsafe {
+                GYRO_BIAS = GyroBias::new();
+            }
+            let _ = uart.write_str("OK [Gyro bias = 0]\r\n");
+        }
+        "imu.smooth" => match parts.get(1).map(|a| a.parse::<f32>()) {
+            None => {
+                let mut buf: String<48> = String::new();
+                write!(buf, "smooth = {}\r\n", unsafe { ACCEL_EMA }.alpha()).ok();
+                let _ = uart.write_str(&buf);
+            }
+            // 0 would freeze the angle at its current value
+            Some(Ok(alpha)) if alpha > 0.0 && alpha <= 1.0 => {
+                let mut ema = unsafe { ACCEL_EMA };
+                ema.set_alpha(alpha);
+                unsafe {
+                    ACCEL_EMA = ema;
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Accel smoothing alpha={}]\r\n", alpha).ok();
+                let _ = uart.write_str(&buf);
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: imu.smooth <0.0-1.0> (> 0)\r\n");
+                ok = false;
+            }
+        },
+        "imu.dlpf" => match parts.get(1).map(|a| a.parse::<u8>().ok().and_then(DlpfConfig::from_index)) {
+            None => {
+                let dlpf = unsafe { DLPF };
+                let mut buf: String<48> = String::new();
+                write!(buf, "dlpf = {} ({} Hz)\r\n", dlpf as u8, dlpf.bandwidth_hz()).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Some(dlpf)) => {
+                if mpu6050::set_dlpf(i2c, imu_addr, dlpf).is_ok() {
+                    unsafe {
+                        DLPF = dlpf;
+                    }
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "OK [DLPF {} = {} Hz]\r\n", dlpf as u8, dlpf.bandwidth_hz()).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: I2C write failed\r\n");
+                    ok = false;
+                }
+            }
+            Some(None) => {
+                let _ = uart.write_str("ERROR: Usage: imu.dlpf <0-6>\r\n");
+                ok = false;
+            }
+        },
+        "imu.capture" => {
+            let count = parts.get(1).and_then(|p| p.parse::<usize>().ok());
+            let hex = match parts.get(2).copied() {
+                None | Some("csv") => Some(false),
+                Some("hex") => Some(true),
+                Some(_) => None,
+            };
+            match (count, hex) {
+                (Some(count), Some(hex)) if (1..=CAPTURE_MAX_SAMPLES).contains(&count) => {
+                    // Capture first, print afterwards: no UART latency between samples
+                    let started = Instant::now();
+                    let mut captured = 0;
+                    while captured < count {
+                        hw.feed_watchdog();
+                        let Ok((accel, gyro)) = mpu6050::read_motion(i2c, imu_addr) else {
+                            break;
+                        };
+                        unsafe {
+                            CAPTURE_T_US[captured] = started.elapsed().as_micros() as u32;
+                            CAPTURE_DATA[captured] = [accel.x, accel.y, accel.z, gyro.x, gyro.y, gyro.z];
+                        }
+                        captured += 1;
+                    }
+
+                    let mut buf: String<96> = String::new();
+                    if hex {
+                        // Per line: t_us as u32 then ax..gz as i16, all big-endian hex
+                        let _ = uart.write_str("# t_us(u32) ax ay az gx gy gz (i16), big-endian hex\r\n");
+                    } else {
+                        let _ = uart.write_str("i,t_us,ax,ay,az,gx,gy,gz\r\n");
+                    }
+                    // 512 lines at 115200 baud take longer than the watchdog timeout
+                    for i in 0..captured {
+                        hw.feed_watchdog();
+                        let (t_us, d) = unsafe { (CAPTURE_T_US[i], CAPTURE_DATA[i]) };
+                        buf.clear();
+                        if hex {
+                            write!(buf, "{:08X}", t_us).ok();
+                            for v in d {
+                                write!(buf, "{:04X}", v as u16).ok();
+                            }
+                            buf.push_str("\r\n").ok();
+                        } else {
+                            write!(buf, "{},{},{},{},{},{},{},{}\r\n", i, t_us, d[0], d[1], d[2], d[3], d[4], d[5]).ok();
+                        }
+                        let _ = uart.write_str(&buf);
+                    }
+
+                    if captured < count {
+                        buf.clear();
+                        write!(buf, "ERROR: I2C read failed after {} of {} samples\r\n", captured, count).ok();
+                        let _ = uart.write_str(&buf);
+                        ok = false;
+                    } else {
+                        buf.clear();
+                        write!(buf, "OK [{} samples]\r\n", captured).ok();
+                        let _ = uart.write_str(&buf);
+                    }
+                }
+                _ => {
+                    let _ = uart.write_str("ERROR: Usage: imu.capture <1-512> [csv|hex]\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "imu.fifo" => match parts.get(1).copied() {
+            None => match mpu6050::fifo_count(i2c, imu_addr) {
+                Ok(count) => {
+                    let mut buf: String<96> = String::new();
+                    write!(
+                        buf,
+                        "fifo: {} ({} bytes, {} samples{})\r\n",
+                        if unsafe { IMU_FIFO } { "on" } else { "off" },
+                        count,
+                        count as usize / mpu6050::FIFO_FRAME_LEN,
+                        if count as usize >= mpu6050::FIFO_DEPTH { ", full: oldest overwritten" } else { "" }
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                }
+                Err(_) => {
+                    let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                    ok = false;
+                }
+            },
+            Some(arg @ ("on" | "off")) => {
+                let on = arg == "on";
+                let result = if on {
+                    mpu6050::enable_fifo(i2c, imu_addr)
+                } else {
+                    mpu6050::disable_fifo(i2c, imu_addr)
+                };
+                if result.is_ok() {
+                    unsafe {
+                        IMU_FIFO = on;
+                    }
+                    let _ = uart.write_str(if on { "OK [FIFO on, emptied]\r\n" } else { "OK [FIFO off]\r\n" });
+                } else {
+                    let _ = uart.write_str("ERROR: I2C write failed\r\n");
+                    ok = false;
+                }
+            }
+            Some("read") => {
+                // Full means samples were overwritten mid-frame, so the bytes are misaligned.
+                // Checked by count: the monitor's data_ready polling clears FIFO_OFLOW_INT.
+                let full = mpu6050::fifo_count(i2c, imu_addr).is_ok_and(|count| count as usize >= mpu6050::FIFO_DEPTH);
+                let mut fifo = [0u8; mpu6050::FIFO_DEPTH];
+                match mpu6050::read_fifo(i2c, imu_addr, &mut fifo) {
+                    Ok(_) if full => {
+                        mpu6050::enable_fifo(i2c, imu_addr).ok();
+                        let _ = uart.write_str("ERROR: FIFO overflowed, emptied and restarted (read it more often)\r\n");
+                        ok = false;
+                    }
+                    Ok(len) => {
+                        let _ = uart.write_str("i,ax,ay,az,gx,gy,gz\r\n");
+                        let mut buf: String<64> = String::new();
+                        for (i, frame) in fifo[..len].chunks_exact(mpu6050::FIFO_FRAME_LEN).enumerate() {
+                            hw.feed_watchdog();
+                            let (a, g) = mpu6050::fifo_sample(frame);
+                            buf.clear();
+                            write!(buf, "{},{},{},{},{},{},{}\r\n", i, a.x, a.y, a.z, g.x, g.y, g.z).ok();
+                            let _ = uart.write_str(&buf);
+                        }
+                        buf.clear();
+                        write!(buf, "OK [{} samples]\r\n", len / mpu6050::FIFO_FRAME_LEN).ok();
+                        let _ = uart.write_str(&buf);
+                    }
+                    Err(_) => {
+                        let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                        ok = false;
+                    }
+                }
+            }
+            Some(_) => {
+                let _ = uart.write_str("ERROR: Usage: imu.fifo [on|off|read]\r\n");
+                ok = false;
+            }
+        },
+        "imu2.addr" => {
+            let imu2_addr = unsafe { IMU2_ADDR };
+            let new_addr = match parts.get(1).copied() {
+                None => {
+                    let mut buf: String<32> = String::new();
+                    write!(buf, "IMU2 address = 0x{:02X}\r\n", imu2_addr).ok();
+                    let _ = uart.write_str(&buf);
+                    return true;
+                }
+                Some("0x68") => Some(mpu6050::MPU_ADDR),
+                Some("0x69") => Some(mpu6050::MPU_ADDR_ALT),
+                Some(_) => None,
+            };
+            match new_addr {
+                Some(addr) if addr == imu_addr => {
+                    let _ = uart.write_str("ERROR: Address already used by the primary IMU\r\n");
+                    ok = false;
+                }
+                Some(addr) => {
+                    if mpu6050::wake_sensor(i2c, addr).is_ok() {
+                        unsafe {
+                            IMU2_ADDR = addr;
+                        }
+                        let mut buf: String<48> = String::new();
+                        write!(buf, "OK [IMU2 address=0x{:02X}]\r\n", addr).ok();
+                        let _ = uart.write_str(&buf);
+                    } else {
+                        let mut buf: String<48> = String::new();
+                        write!(buf, "ERROR: No MPU6050 at 0x{:02X}\r\n", addr).ok();
+                        let _ = uart.write_str(&buf);
+                        ok = false;
+                    }
+                }
+                None => {
+                    let _ = uart.write_str("ERROR: Usage: imu2.addr [0x68|0x69]\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "imu2.read" => {
+            if let Ok(accel) = mpu6050::read_accel(i2c, unsafe { IMU2_ADDR }) {
+                let mut buf: String<128> = String::new();
+                write!(buf, "accel2: x={} y={} z={}\r\n", accel.x, accel.y, accel.z).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Failed to read IMU2\r\n");
+                ok = false;
+            }
+        }
+        "joint.angle" => {
+            // Angle between the two gravity vectors, e.g. across a hinge.
+            // Raw readings: the primary's calibration offsets are for its own zero.
+            match (mpu6050::read_accel(i2c, imu_addr), mpu6050::read_accel(i2c, unsafe { IMU2_ADDR })) {
+                (Ok(a), Ok(b)) => {
+                    let angle = crate::tilt::angle_between(
+                        [a.x as i32, a.y as i32, a.z as i32],
+                        [b.x as i32, b.y as i32, b.z as i32],
+                    );
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "Joint angle: {:.1}°\r\n", angle).ok();
+                    let _ = uart.write_str(&buf);
+                }
+                (Err(_), _) => {
+                    let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                    ok = false;
+                }
+                (_, Err(_)) => {
+                    let _ = uart.write_str("ERROR: Failed to read IMU2\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "imu.noise" => {
+            let samples = match parts.get(1).map(|s| s.parse::<u32>()) {
+                None => Some(NOISE_DEFAULT_SAMPLES),
+                Some(Ok(n)) if (2..=NOISE_MAX_SAMPLES).contains(&n) => Some(n),
+                Some(_) => None,
+            };
+            if let Some(samples) = samples {
+                let delay = Delay::new();
+                let mut stats = [crate::stats::Welford::new(); 3];
+                for _ in 0..samples {
+                    hw.feed_watchdog();
+                    if let Ok(accel) = mpu6050::read_accel(i2c, imu_addr) {
+                        stats[0].update(accel.x as f32);
+                        stats[1].update(accel.y as f32);
+                        stats[2].update(accel.z as f32);
+                    }
+                    delay.delay_millis(NOISE_SAMPLE_SPACING_MS);
+                }
+                if stats[0].count() < 2 {
+                    let _ = uart.write_str("ERROR: Failed to read IMU\r\n");
+                    ok = false;
+                } else {
+                    let mut buf: String<128> = String::new();
+                    write!(
+                        buf,
+                        "noise (std dev, counts): x={:.2} y={:.2} z={:.2} (n={})\r\n",
+                        stats[0].std_dev(),
+                        stats[1].std_dev(),
+                        stats[2].std_dev(),
+                        stats[0].count()
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Usage: imu.noise [samples 2-2000]\r\n");
+                ok = false;
+            }
+        }
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
@@ -0,0 +1,217 @@
+//! UART command handlers
+//!
+//! `process_command` runs one command: it splits the line into words and
+//! hands them to the module for the command's group, the part of the name
+//! before the dot (`imu.read` → `imu.rs`). Every handler writes its own
+//! reply, `OK [...]` or `ERROR: ...`, and returns false on an error so
+//! `;` chains and macros stop there. The help text is in `help.rs`.
+
+mod device;
+mod imu;
+mod output;
+mod posture;
+mod stream;
+mod system;
+
+use core::fmt::Write;
+use esp_hal::{i2c::master::I2c, time::Instant};
+use heapless::String;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::config::CMD_BUFFER_SIZE;
+use crate::hw::Hw;
+use crate::macros::MacroError;
+use crate::state::{MACROS, PROFILE_COMMANDS};
+
+/// The line outgrew the command buffer: ring the bell, drop it and redraw
+/// the prompt. The rest of the line is ignored until Enter.
+pub fn report_line_too_long<W: Write>(cmd_buffer: &mut String<CMD_BUFFER_SIZE>, uart: &mut W) {
+    cmd_buffer.clear();
+    let mut buf: String<64> = String::new();
+    write!(buf, "\x07\r\nERROR: command too long (max {} characters)\r\n> ", CMD_BUFFER_SIZE).ok();
+    let _ = uart.write_str(&buf);
+}
+
+/// Run one CLI line: `cmd1; cmd2; ...` runs in order and stops at the first
+/// error. Returns false if a command failed.
+pub fn run_line<W: Write, Dm: esp_hal::DriverMode, N>(
+    line: &str,
+    hw: &mut Hw<'_, N>,
+    i2c: &mut I2c<Dm>,
+    uart: &mut W,
+) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let chained = line.contains(';');
+    for (i, segment) in line.split(';').enumerate() {
+        if segment.trim().is_empty() {
+            continue;
+        }
+        let started = Instant::now();
+        let ok = process_command(segment, hw, i2c, uart);
+        if unsafe { PROFILE_COMMANDS } {
+            let mut buf: String<32> = String::new();
+            write!(buf, "(took {} us)\r\n", started.elapsed().as_micros()).ok();
+            let _ = uart.write_str(&buf);
+        }
+        if !ok {
+            if chained {
+                let mut buf: String<160> = String::new();
+                write!(buf, "ERROR: Stopped at segment {} ({})\r\n", i + 1, segment.trim()).ok();
+                let _ = uart.write_str(&buf);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+/// Run one command (no `;`); false if it replied with an error
+pub fn process_command<W: Write, Dm: esp_hal::DriverMode, N>(
+    cmd: &str,
+    hw: &mut Hw<'_, N>,
+    i2c: &mut I2c<Dm>,
+    uart: &mut W,
+) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let cmd_trimmed = cmd.trim();
+
+    // `echo` takes free text, so handle it before splitting into a fixed-size arg list
+    if cmd_trimmed == "echo" || cmd_trimmed.starts_with("echo ") {
+        let _ = uart.write_str(cmd_trimmed[4..].trim_start());
+        let _ = uart.write_str("\r\n");
+        return true;
+    }
+
+    let mut words = cmd_trimmed.split_whitespace();
+    let parts: heapless::Vec<&str, 6> = words.by_ref().take(6).collect();
+
+    if parts.is_empty() {
+        return true;
+    }
+    if words.next().is_some() {
+        let _ = uart.write_str("ERROR: Too many arguments\r\n");
+        return false;
+    }
+
+
+    match parts[0].split_once('.').map_or(parts[0], |(group, _)| group) {
+        "device" | "cal" => device::run(&parts, hw, i2c, uart),
+        "gpio" | "neo" | "buzzer" => output::run(&parts, hw, uart),
+        "posture" | "knob" => posture::run(&parts, hw, uart),
+        "imu" | "imu2" | "joint" | "yaw" | "gyrobias" => imu::run(&parts, hw, i2c, uart),
+        "stream" => stream::run(&parts, uart),
+        "macro" => run_macro(&parts, hw, i2c, uart),
+        "help" | "debug" | "config" | "log" | "temp" | "cpu" | "sys" | "demo" | "term" | "ping" => {
+            system::run(&parts, uart)
+        }
+        _ => unknown(uart),
+    }
+}
+
+/// `macro.*`: here rather than in a module of its own because `macro.run`
+/// replays the recorded lines through `run_line`
+fn run_macro<W: Write, Dm: esp_hal::DriverMode, N>(
+    parts: &[&str],
+    hw: &mut Hw<'_, N>,
+    i2c: &mut I2c<Dm>,
+    uart: &mut W,
+) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    let mut ok = true;
+
+    match parts[0] {
+        "macro.record" => match parts.get(1) {
+            Some(name) => match critical_section::with(|cs| MACROS.borrow_ref_mut(cs).start(name)) {
+                Ok(()) => {
+                    let _ = uart.write_str("OK [Recording, enter commands then macro.stop]\r\n");
+                }
+                Err(e) => {
+                    let _ = uart.write_str(match e {
+                        MacroError::NameTooLong => "ERROR: Macro name too long (max 12)\r\n",
+                        MacroError::Full => "ERROR: No free macro slot\r\n",
+                        _ => "ERROR: Already recording, macro.stop first\r\n",
+                    });
+                    ok = false;
+                }
+            },
+            None => {
+                let _ = uart.write_str("ERROR: Usage: macro.record <name>\r\n");
+                ok = false;
+            }
+        },
+        "macro.stop" => match critical_section::with(|cs| MACROS.borrow_ref_mut(cs).stop()) {
+            Ok(0) => {
+                let _ = uart.write_str("OK [Nothing recorded, macro discarded]\r\n");
+            }
+            Ok(n) => {
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Recorded {} lines]\r\n", n).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Err(_) => {
+                let _ = uart.write_str("ERROR: Not recording\r\n");
+                ok = false;
+            }
+        },
+        "macro.run" => {
+            let name = parts.get(1).copied().unwrap_or("");
+            // Copied out: replayed commands may touch the store (macro.list).
+            // Recorded lines never contain macro.*, so replays can't nest.
+            let found = critical_section::with(|cs| MACROS.borrow_ref(cs).get(name).cloned());
+            if let Some(m) = found {
+                for (i, line) in m.lines.iter().enumerate() {
+                    if !run_line(line, hw, i2c, uart) {
+                        let mut buf: String<192> = String::new();
+                        write!(buf, "ERROR: Stopped at macro line {} ({})\r\n", i + 1, line).ok();
+                        let _ = uart.write_str(&buf);
+                        ok = false;
+                        break;
+                    }
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Usage: macro.run <name> (see macro.list)\r\n");
+                ok = false;
+            }
+        }
+        "macro.list" => {
+            critical_section::with(|cs| {
+                let macros = MACROS.borrow_ref(cs);
+                if macros.iter().next().is_none() {
+                    let _ = uart.write_str("No macros recorded\r\n");
+                }
+                for m in macros.iter() {
+                    let mut buf: String<192> = String::new();
+                    write!(buf, "{} ({} lines)", m.name, m.lines.len()).ok();
+                    if macros.recording() == Some(m.name.as_str()) {
+                        buf.push_str(" [recording]").ok();
+                    }
+                    buf.push_str("\r\n").ok();
+                    let _ = uart.write_str(&buf);
+                    for line in &m.lines {
+                        buf.clear();
+                        write!(buf, "  {}\r\n", line).ok();
+                        let _ = uart.write_str(&buf);
+                    }
+                }
+            });
+        }
+        _ => ok = unknown(uart),
+    }
+
+    ok
+}
+
+/// Reply for a name no handler knows
+fn unknown<W: Write>(uart: &mut W) -> bool {
+    let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
+    false
+}
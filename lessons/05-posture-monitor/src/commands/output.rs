@@ -0,0 +1,211 @@
+//! `gpio.*`, `neo.*` and `buzzer.*`: the LED, Neopixel, buzzer and input pins
+
+use core::fmt::Write;
+use esp_hal::delay::Delay;
+use heapless::String;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::beep::BeepPattern;
+use crate::config::*;
+use crate::hw::Hw;
+use crate::state::*;
+
+pub fn run<W: Write, N>(parts: &[&str], hw: &mut Hw<'_, N>, uart: &mut W) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "gpio.on" => {
+            hw.set_led(true);
+            let _ = uart.write_str("OK [LED ON]\r\n");
+        }
+        "gpio.off" => {
+            hw.set_led(false);
+            let _ = uart.write_str("OK [LED OFF]\r\n");
+        }
+        "gpio.read" => match parts.get(1).map(|p| p.parse::<u8>()) {
+            Some(Ok(pin)) => match hw.input(pin) {
+                Some(input) => {
+                    // All pulled up: the button and encoder contacts read LOW when closed
+                    let mut buf: String<32> = String::new();
+                    write!(buf, "GPIO{} = {}\r\n", pin, if input.is_high() { "HIGH" } else { "LOW" }).ok();
+                    let _ = uart.write_str(&buf);
+                }
+                None if [LED_PIN, NEOPIXEL_PIN, UART_TX_PIN, BUZZER_PIN].contains(&pin) => {
+                    // Its input path isn't enabled; reading it would just return garbage
+                    let mut buf: String<80> = String::new();
+                    write!(buf, "ERROR: GPIO{} is configured as an output, can't read it\r\n", pin).ok();
+                    let _ = uart.write_str(&buf);
+                    ok = false;
+                }
+                None => {
+                    let _ = uart.write_str("ERROR: Unsupported pin (inputs: 4, 5, 9)\r\n");
+                    ok = false;
+                }
+            },
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: gpio.read <pin>\r\n");
+                ok = false;
+            }
+        },
+        "neo.color" => {
+            if parts.len() < 4 {
+                let _ = uart.write_str("ERROR: Usage: neo.color <r> <g> <b>\r\n");
+                ok = false;
+            } else if let (Ok(r), Ok(g), Ok(b)) = (
+                parts[1].parse::<u8>(),
+                parts[2].parse::<u8>(),
+                parts[3].parse::<u8>(),
+            ) {
+                unsafe {
+                    NEO_STROBE_HZ = 0;
+                }
+                hw.set_neo(RGB8::new(r, g, b));
+                let mut buf: String<64> = String::new();
+                write!(buf, "OK [Neopixel RGB=({},{},{})]\r\n", r, g, b).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Invalid RGB values\r\n");
+                ok = false;
+            }
+        }
+        "neo.off" => {
+            unsafe {
+                NEO_STROBE_HZ = 0;
+            }
+            hw.set_neo(RGB8::new(0, 0, 0));
+            let _ = uart.write_str("OK [Neopixel OFF]\r\n");
+        }
+        "neo.bright" => match parts.get(1).map(|a| a.parse::<u8>()) {
+            None => {
+                let mut buf: String<32> = String::new();
+                write!(buf, "brightness = {}\r\n", unsafe { NEO_BRIGHTNESS }).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Ok(brightness)) => {
+                unsafe {
+                    NEO_BRIGHTNESS = brightness;
+                }
+                // Repaint so the change shows without waiting for the next color
+                let color = hw.neo_color;
+                hw.set_neo(color);
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Neopixel brightness={}]\r\n", brightness).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some(Err(_)) => {
+                let _ = uart.write_str("ERROR: Usage: neo.bright <0-255>\r\n");
+                ok = false;
+            }
+        },
+        "buzzer.test" => match parts.get(1).map(|a| a.parse::<u32>()) {
+            Some(Ok(hz)) if (BUZZER_TEST_MIN_HZ..=BUZZER_TEST_MAX_HZ).contains(&hz) => {
+                if hw.buzzer.set_frequency(hz) {
+                    hw.buzzer.set_sounding(true);
+                    Delay::new().delay_millis(BUZZER_TEST_MS);
+                    hw.buzzer.set_sounding(false);
+                    hw.buzzer.set_frequency(BUZZER_FREQ_HZ);
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "OK [Buzzer {} Hz for {} ms]\r\n", hz, BUZZER_TEST_MS).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: LEDC can't generate that frequency\r\n");
+                    ok = false;
+                }
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: buzzer.test <100-10000>\r\n");
+                ok = false;
+            }
+        },
+        "buzzer.mute" => {
+            let muted = match parts.get(1).copied() {
+                None => Some(!unsafe { BUZZER_MUTED }),
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => None,
+            };
+            if let Some(muted) = muted {
+                unsafe {
+                    BUZZER_MUTED = muted;
+                }
+                let _ = uart.write_str(if muted { "OK [Buzzer muted]\r\n" } else { "OK [Buzzer unmuted]\r\n" });
+            } else {
+                let _ = uart.write_str("ERROR: Usage: buzzer.mute [on|off]\r\n");
+                ok = false;
+            }
+        }
+        "buzzer.pattern" => {
+            let level = match parts.get(1).copied() {
+                Some("warning") => Some(AlertLevel::Warning),
+                Some("alert") => Some(AlertLevel::Alert),
+                _ => None,
+            };
+            let mut args = [0u16; 4];
+            let args_ok = parts.len() == 6
+                && args.iter_mut().zip(&parts[2..]).all(|(arg, part)| part.parse().map(|v| *arg = v).is_ok());
+            match (level, parts.len()) {
+                (None, 1) => {
+                    let patterns = unsafe { BUZZER_PATTERNS };
+                    for (name, level) in [("warning", AlertLevel::Warning), ("alert", AlertLevel::Alert)] {
+                        let p = patterns[level as usize];
+                        let mut buf: String<80> = String::new();
+                        write!(buf, "{}: {} x {} ms, gap {} ms, every {} ms\r\n", name, p.count, p.on_ms, p.gap_ms, p.period_ms).ok();
+                        let _ = uart.write_str(&buf);
+                    }
+                }
+                (Some(level), 6) => {
+                    let [count, on_ms, gap_ms, period_ms] = args;
+                    let pattern = u8::try_from(count)
+                        .ok()
+                        .filter(|_| args_ok)
+                        .map(|count| BeepPattern::new(count, on_ms, gap_ms, period_ms))
+                        .filter(BeepPattern::is_valid);
+                    if let Some(pattern) = pattern {
+                        unsafe {
+                            BUZZER_PATTERNS[level as usize] = pattern;
+                        }
+                        let _ = uart.write_str("OK [Buzzer pattern set]\r\n");
+                    } else {
+                        let _ = uart.write_str("ERROR: Beeps must be > 0 ms and fit in the period\r\n");
+                        ok = false;
+                    }
+                }
+                _ => {
+                    let _ = uart.write_str("ERROR: Usage: buzzer.pattern [warning|alert <count> <on_ms> <gap_ms> <period_ms>]\r\n");
+                    ok = false;
+                }
+            }
+        }
+        "neo.strobe" => {
+            if parts.len() < 2 {
+                let _ = uart.write_str("ERROR: Usage: neo.strobe <hz>\r\n");
+                ok = false;
+            } else if let Ok(hz) = parts[1].parse::<u8>() {
+                if hz == 0 {
+                    let _ = uart.write_str("ERROR: Frequency must be >= 1 (use neo.off to stop)\r\n");
+                    ok = false;
+                } else {
+                    let hz = hz.min(NEO_STROBE_MAX_HZ);
+                    unsafe {
+                        NEO_STROBE_HZ = hz;
+                        NEO_STROBE_AUTO_MS = None;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "OK [Strobe {} Hz, neo.off to stop]\r\n", hz).ok();
+                    let _ = uart.write_str(&buf);
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid frequency\r\n");
+                ok = false;
+            }
+        }
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
@@ -0,0 +1,350 @@
+//! `posture.*` and `knob.target`: thresholds, colors, filters and the score
+
+use core::fmt::Write;
+use heapless::String;
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::config::*;
+use crate::hw::Hw;
+use crate::settings::save_colors;
+use crate::state::*;
+
+pub fn run<W: Write, N>(parts: &[&str], hw: &mut Hw<'_, N>, uart: &mut W) -> bool
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "posture.color" => {
+            let level = match parts.get(1) {
+                Some(&"normal") => Some(AlertLevel::Normal),
+                Some(&"warning") => Some(AlertLevel::Warning),
+                Some(&"alert") => Some(AlertLevel::Alert),
+                _ => None,
+            };
+            if parts.len() < 5 || level.is_none() {
+                let _ = uart.write_str("ERROR: Usage: posture.color <normal|warning|alert> <r> <g> <b>\r\n");
+                ok = false;
+            } else if let (Some(level), Ok(r), Ok(g), Ok(b)) = (
+                level,
+                parts[2].parse::<u8>(),
+                parts[3].parse::<u8>(),
+                parts[4].parse::<u8>(),
+            ) {
+                unsafe {
+                    ALERT_COLORS[level as usize] = RGB8::new(r, g, b);
+                    // Repaint right away if the monitor is showing this level
+                    if DEVICE_STATE == DeviceState::Monitoring && ALERT_LEVEL == level {
+                        hw.set_neo(alert_color(level));
+                    }
+                }
+                let mut buf: String<64> = String::new();
+                write!(buf, "OK [{:?} color RGB=({},{},{})]\r\n", level, r, g, b).ok();
+                let _ = uart.write_str(&buf);
+                if save_colors(&mut hw.flash).is_err() {
+                    let _ = uart.write_str("ERROR: Flash write failed\r\n");
+                    ok = false;
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid RGB values\r\n");
+                ok = false;
+            }
+        }
+        "posture.colors" => {
+            let mut buf: String<128> = String::new();
+            for level in [AlertLevel::Normal, AlertLevel::Warning, AlertLevel::Alert] {
+                let c = alert_color(level);
+                write!(buf, "{:?}: ({},{},{})\r\n", level, c.r, c.g, c.b).ok();
+            }
+            let _ = uart.write_str(&buf);
+        }
+        "posture.alert_count" => {
+            if parts.get(1) == Some(&"reset") {
+                unsafe {
+                    ALERT_COUNTS = [0; 3];
+                }
+                let _ = uart.write_str("OK [Alert counts reset]\r\n");
+            } else {
+                let counts = unsafe { ALERT_COUNTS };
+                let mut buf: String<64> = String::new();
+                write!(buf, "normal={} warning={} alert={}\r\n", counts[0], counts[1], counts[2]).ok();
+                let _ = uart.write_str(&buf);
+            }
+        }
+        "posture.score" => match unsafe { (POSTURE_SCORE, POSTURE_SCORE_SPAN_S) } {
+            (Some([normal, warning, alert]), span_s) => {
+                let mut buf: String<96> = String::new();
+                write!(
+                    buf,
+                    "Posture score: {}% good over the last {} s (warning {}%, alert {}%)\r\n",
+                    normal, span_s, warning, alert
+                )
+                .ok();
+                let _ = uart.write_str(&buf);
+            }
+            (None, _) => {
+                let _ = uart.write_str("ERROR: No score yet, start monitoring (device.start)\r\n");
+                ok = false;
+            }
+        },
+        "posture.cooldown" => {
+            if let Some(Ok(secs)) = parts.get(1).map(|p| p.parse::<u32>()) {
+                unsafe {
+                    ALERT_COOLDOWN_MS = secs.saturating_mul(1000);
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Alert cooldown={}s]\r\n", secs).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Usage: posture.cooldown <seconds>\r\n");
+                ok = false;
+            }
+        }
+        "posture.sedentary_timeout" => match parts.get(1).map(|p| p.parse::<u32>()) {
+            Some(Ok(min)) if min <= 24 * 60 => {
+                unsafe {
+                    SEDENTARY_TIMEOUT_MIN = min;
+                }
+                let mut buf: String<48> = String::new();
+                if min == 0 {
+                    buf.push_str("OK [Sedentary nudge off]\r\n").ok();
+                } else {
+                    write!(buf, "OK [Sedentary nudge after {} min]\r\n", min).ok();
+                }
+                let _ = uart.write_str(&buf);
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: posture.sedentary_timeout <0-1440 min>\r\n");
+                ok = false;
+            }
+        },
+        "posture.compare" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    COMPARE = true;
+                }
+                let _ = uart.write_str("OK [Comparing estimators, 'posture.compare off' to stop]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    COMPARE = false;
+                }
+                let _ = uart.write_str("OK [Compare off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: posture.compare <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "posture.filter" => match parts.get(1).copied() {
+            Some("none") => {
+                unsafe {
+                    TILT_FILTER = TiltFilter::Raw;
+                }
+                let _ = uart.write_str("OK [Filter: none (raw accel angle)]\r\n");
+            }
+            Some("comp") => {
+                let alpha = unsafe { COMPLEMENTARY }.alpha;
+                unsafe {
+                    TILT_FILTER = TiltFilter::Complementary;
+                }
+                let mut buf: String<64> = String::new();
+                write!(buf, "OK [Filter: complementary alpha={}]\r\n", alpha).ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some("kalman") => {
+                let noise = match (parts.get(2), parts.get(3), parts.get(4)) {
+                    (None, _, _) => Some(None),
+                    (Some(q), Some(b), Some(r)) => match (q.parse::<f32>(), b.parse::<f32>(), r.parse::<f32>()) {
+                        (Ok(q), Ok(b), Ok(r)) if q > 0.0 && b > 0.0 && r > 0.0 => Some(Some((q, b, r))),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(noise) = noise {
+                    let mut kalman = unsafe { KALMAN };
+                    if let Some((q, b, r)) = noise {
+                        kalman.q_angle = q;
+                        kalman.q_bias = b;
+                        kalman.r_measure = r;
+                    }
+                    // Start from the current angle rather than 0°
+                    kalman.reset(unsafe { TILT_ANGLE });
+                    unsafe {
+                        KALMAN = kalman;
+                        TILT_FILTER = TiltFilter::Kalman;
+                    }
+                    let mut buf: String<96> = String::new();
+                    write!(
+                        buf,
+                        "OK [Filter: kalman q_angle={} q_bias={} r={}]\r\n",
+                        kalman.q_angle, kalman.q_bias, kalman.r_measure
+                    )
+                    .ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Noise values must be three positive numbers\r\n");
+                    ok = false;
+                }
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: posture.filter <none|comp|kalman> [q_angle q_bias r]\r\n");
+                ok = false;
+            }
+        },
+        "knob.target" => {
+            let target = match parts.get(1).copied() {
+                Some("none") => Some(KnobTarget::None),
+                Some("warn") => Some(KnobTarget::Warning),
+                Some("alert") => Some(KnobTarget::Alert),
+                Some("period") => Some(KnobTarget::SamplePeriod),
+                _ => None,
+            };
+            if let Some(target) = target {
+                unsafe {
+                    KNOB_TARGET = target;
+                }
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Encoder adjusts {:?}]\r\n", target).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Usage: knob.target <none|warn|alert|period>\r\n");
+                ok = false;
+            }
+        }
+        "posture.autocal" => match parts.get(1).copied() {
+            None => {
+                let mut buf: String<96> = String::new();
+                write!(
+                    buf,
+                    "Autocal: {} (after {} s still), {}\r\n",
+                    if unsafe { AUTOCAL } { "on" } else { "off" },
+                    unsafe { AUTOCAL_STILL_MS } / 1000,
+                    if unsafe { CALIBRATED } { "calibrated" } else { "not calibrated" }
+                )
+                .ok();
+                let _ = uart.write_str(&buf);
+            }
+            Some("on") => match parts.get(2).map(|p| p.parse::<u32>()) {
+                Some(Ok(s)) if !(1..=600).contains(&s) => {
+                    let _ = uart.write_str("ERROR: Still period must be 1-600 s\r\n");
+                    ok = false;
+                }
+                Some(Err(_)) => {
+                    let _ = uart.write_str("ERROR: Invalid still period\r\n");
+                    ok = false;
+                }
+                secs => {
+                    unsafe {
+                        if let Some(Ok(s)) = secs {
+                            AUTOCAL_STILL_MS = s * 1000;
+                        }
+                        AUTOCAL = true;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "OK [Autocal on, after {} s still]\r\n", unsafe { AUTOCAL_STILL_MS } / 1000).ok();
+                    let _ = uart.write_str(&buf);
+                }
+            },
+            Some("off") => {
+                unsafe {
+                    AUTOCAL = false;
+                }
+                let _ = uart.write_str("OK [Autocal off]\r\n");
+            }
+            Some(_) => {
+                let _ = uart.write_str("ERROR: Usage: posture.autocal [on [s]|off]\r\n");
+                ok = false;
+            }
+        },
+        "posture.warn" | "posture.alert" => {
+            let is_warn = parts[0] == "posture.warn";
+            match parts.get(1).map(|p| p.parse::<f32>()) {
+                Some(Ok(deg)) => {
+                    let (warn, alert) = unsafe {
+                        if is_warn {
+                            (deg, TILT_ALERT_THRESHOLD)
+                        } else {
+                            (TILT_WARNING_THRESHOLD, deg)
+                        }
+                    };
+                    if is_warn && unsafe { KNOB_ENABLED } {
+                        let _ = uart.write_str("ERROR: The knob sets the warning threshold, run posture.knob off first\r\n");
+                        ok = false;
+                    } else {
+                        ok = set_thresholds(warn, alert, uart);
+                    }
+                }
+                Some(Err(_)) => {
+                    let _ = uart.write_str("ERROR: Invalid angle\r\n");
+                    ok = false;
+                }
+                None => {
+                    let mut buf: String<48> = String::new();
+                    write!(buf, "ERROR: Usage: {} <deg>\r\n", parts[0]).ok();
+                    let _ = uart.write_str(&buf);
+                    ok = false;
+                }
+            }
+        }
+        "posture.thresholds" => match parts.len() {
+            1 => {
+                let (warn, alert, knob) = unsafe { (TILT_WARNING_THRESHOLD, TILT_ALERT_THRESHOLD, KNOB_ENABLED) };
+                let mut buf: String<80> = String::new();
+                write!(
+                    buf,
+                    "Thresholds: warn={:.1}° alert={:.1}°{}\r\n",
+                    warn,
+                    alert,
+                    if knob { " (warn from knob)" } else { "" }
+                )
+                .ok();
+                let _ = uart.write_str(&buf);
+            }
+            3 => match (parts[1].parse::<f32>(), parts[2].parse::<f32>()) {
+                (Ok(_), Ok(_)) if unsafe { KNOB_ENABLED } => {
+                    let _ = uart.write_str("ERROR: The knob sets the warning threshold, run posture.knob off first\r\n");
+                    ok = false;
+                }
+                (Ok(warn), Ok(alert)) => ok = set_thresholds(warn, alert, uart),
+                _ => {
+                    let _ = uart.write_str("ERROR: Invalid angle\r\n");
+                    ok = false;
+                }
+            },
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: posture.thresholds [<warn> <alert>]\r\n");
+                ok = false;
+            }
+        },
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
+
+/// Apply a warning/alert pair if it passes `thresholds_valid`; false after
+/// replying with the reason it doesn't
+fn set_thresholds<W: Write>(warn: f32, alert: f32, uart: &mut W) -> bool {
+    if !(warn > 0.0 && warn < THRESHOLD_MAX_DEG && alert > 0.0 && alert < THRESHOLD_MAX_DEG) {
+        let _ = uart.write_str("ERROR: Threshold must be between 0 and 90°\r\n");
+        return false;
+    }
+    if !thresholds_valid(warn, alert) {
+        let mut buf: String<96> = String::new();
+        write!(buf, "ERROR: Warning must be below alert (warn={:.1}° alert={:.1}°)\r\n", warn, alert).ok();
+        let _ = uart.write_str(&buf);
+        return false;
+    }
+    unsafe {
+        TILT_WARNING_THRESHOLD = warn;
+        TILT_ALERT_THRESHOLD = alert;
+    }
+    let mut buf: String<64> = String::new();
+    write!(buf, "OK [Thresholds: warn={:.1}° alert={:.1}°]\r\n", warn, alert).ok();
+    let _ = uart.write_str(&buf);
+    true
+}
@@ -0,0 +1,93 @@
+//! `stream.*`: streaming mode and its line format
+
+use core::fmt::Write;
+
+use crate::state::*;
+
+pub fn run<W: Write>(parts: &[&str], uart: &mut W) -> bool {
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "stream.start" => {
+            unsafe {
+                STREAM_CSV = false;
+                MODE = Mode::Streaming;
+            }
+            let _ = uart.write_str("[Switching to streaming mode...]\r\n");
+        }
+        "stream.csv" => {
+            unsafe {
+                STREAM_CSV = true;
+                CSV_HEADER_SENT = false;
+                MODE = Mode::Streaming;
+            }
+            let _ = uart.write_str("[Switching to CSV streaming...]\r\n");
+        }
+        "stream.stop" => {
+            unsafe {
+                STREAM_CSV = false;
+                CSV_HEADER_SENT = false;
+                MODE = Mode::CLI;
+            }
+            let _ = uart.write_str("[Switching to CLI mode...]\r\n");
+        }
+        "stream.raw" => match parts.get(1) {
+            Some(&"on") => {
+                let _ = uart.write_str("OK [Raw binary stream, type 'stream.raw off' + Enter to stop]\r\n");
+                unsafe {
+                    MODE = Mode::Raw;
+                }
+            }
+            Some(&"off") => {
+                unsafe {
+                    MODE = Mode::CLI;
+                }
+                let _ = uart.write_str("\r\nOK [Raw stream stopped]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: stream.raw <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "stream.aggregate" => match parts.get(1) {
+            Some(&"last") => {
+                unsafe {
+                    STREAM_AVG = false;
+                }
+                let _ = uart.write_str("OK [Streaming latest sample]\r\n");
+            }
+            Some(&"avg") => {
+                unsafe {
+                    STREAM_AVG = true;
+                }
+                let _ = uart.write_str("OK [Streaming mean since last line]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: stream.aggregate <last|avg>\r\n");
+                ok = false;
+            }
+        },
+        "stream.crc" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    STREAM_CRC = true;
+                }
+                let _ = uart.write_str("OK [Stream lines end with *XX CRC-8]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    STREAM_CRC = false;
+                }
+                let _ = uart.write_str("OK [Stream CRC off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: stream.crc <on|off>\r\n");
+                ok = false;
+            }
+        },
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
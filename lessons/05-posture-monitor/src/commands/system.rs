@@ -0,0 +1,269 @@
+//! `help` and the debugging/system commands: logs, profiling, config dump,
+//! temperature limit, WFI, reset
+
+use core::fmt::Write;
+use esp_hal::{delay::Delay, system::software_reset};
+use heapless::String;
+
+use crate::config::*;
+use crate::help;
+use crate::state::*;
+
+pub fn run<W: Write>(parts: &[&str], uart: &mut W) -> bool {
+    // Cleared by any arm that replies with ERROR
+    let mut ok = true;
+
+    match parts[0] {
+        "help" => match parts.get(1) {
+            None => {
+                let _ = uart.write_str("Posture Monitor Commands:\r\n");
+                for entry in help::COMMANDS {
+                    let _ = uart.write_str("  ");
+                    let _ = uart.write_str(entry.summary);
+                    let _ = uart.write_str("\r\n");
+                }
+            }
+            Some(name) => match help::find(name) {
+                Some(entry) => {
+                    let _ = uart.write_str("  ");
+                    let _ = uart.write_str(entry.summary);
+                    let _ = uart.write_str("\r\n");
+                    let _ = uart.write_str(entry.detail);
+                    let _ = uart.write_str("\r\n");
+                }
+                None => {
+                    let _ = uart.write_str("ERROR: Unknown command. Type 'help'\r\n");
+                    ok = false;
+                }
+            },
+        },
+        "debug.freeze" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    TELEMETRY_FROZEN = true;
+                }
+                let _ = uart.write_str("OK [Telemetry frozen]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    TELEMETRY_FROZEN = false;
+                }
+                let _ = uart.write_str("OK [Telemetry live]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: debug.freeze <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "config.dump_rust" => {
+            // Same form as the declarations in state.rs, so each one can be pasted over its default
+            let _ = uart.write_str("// Runtime config (paste over the defaults in src/state.rs)\r\n");
+            let (warn, alert) = unsafe { (TILT_WARNING_THRESHOLD, TILT_ALERT_THRESHOLD) };
+            dump_static(uart, "TILT_WARNING_THRESHOLD", "f32", format_args!("{:?}", warn));
+            dump_static(uart, "TILT_ALERT_THRESHOLD", "f32", format_args!("{:?}", alert));
+            dump_static(uart, "KNOB_ENABLED", "bool", format_args!("{}", unsafe { KNOB_ENABLED }));
+            dump_static(uart, "KNOB_TARGET", "KnobTarget", format_args!("KnobTarget::{:?}", unsafe { KNOB_TARGET }));
+            dump_static(uart, "SAMPLE_PERIOD_MS", "u32", format_args!("{}", unsafe { SAMPLE_PERIOD_MS }));
+            dump_static(uart, "TERM_ECHO", "bool", format_args!("{}", unsafe { TERM_ECHO }));
+            dump_static(uart, "CAL_RETRIES", "u8", format_args!("{}", unsafe { CAL_RETRIES }));
+            dump_static(uart, "NEO_BRIGHTNESS", "u8", format_args!("{}", unsafe { NEO_BRIGHTNESS }));
+            dump_static(uart, "BUZZER_MUTED", "bool", format_args!("{}", unsafe { BUZZER_MUTED }));
+            dump_static(uart, "BUZZER_PATTERNS", "[BeepPattern; 3]", format_args!("{:?}", unsafe { BUZZER_PATTERNS }));
+            dump_static(uart, "LIGHT_SLEEP", "bool", format_args!("{}", unsafe { LIGHT_SLEEP }));
+            dump_static(uart, "IMU_ADDR", "u8", format_args!("{:#04x}", unsafe { IMU_ADDR }));
+            dump_static(uart, "IMU2_ADDR", "u8", format_args!("{:#04x}", unsafe { IMU2_ADDR }));
+            dump_static(uart, "ACCEL_RANGE", "AccelRange", format_args!("AccelRange::{:?}", unsafe { ACCEL_RANGE }));
+            dump_static(uart, "DLPF", "DlpfConfig", format_args!("DlpfConfig::{:?}", unsafe { DLPF }));
+            dump_static(uart, "CAL_OFFSET_X", "i16", format_args!("{}", unsafe { CAL_OFFSET_X }));
+            dump_static(uart, "CAL_OFFSET_Y", "i16", format_args!("{}", unsafe { CAL_OFFSET_Y }));
+            dump_static(uart, "CAL_OFFSET_Z", "i16", format_args!("{}", unsafe { CAL_OFFSET_Z }));
+            dump_static(uart, "CAL_HW", "bool", format_args!("{}", unsafe { CAL_HW }));
+            dump_static(uart, "MOUNT_REF", "Option<[i16; 3]>", format_args!("{:?}", unsafe { MOUNT_REF }));
+            let [normal, warning, alert] = unsafe { ALERT_COLORS };
+            dump_static(
+                uart,
+                "ALERT_COLORS",
+                "[RGB8; 3]",
+                format_args!(
+                    "[RGB8::new({}, {}, {}), RGB8::new({}, {}, {}), RGB8::new({}, {}, {})]",
+                    normal.r, normal.g, normal.b, warning.r, warning.g, warning.b, alert.r, alert.g, alert.b
+                ),
+            );
+            dump_static(uart, "TEMP_LIMIT_C", "f32", format_args!("{:?}", unsafe { TEMP_LIMIT_C }));
+            dump_static(uart, "TEMP_SLEEP_ON_LIMIT", "bool", format_args!("{}", unsafe { TEMP_SLEEP_ON_LIMIT }));
+            dump_static(uart, "ALERT_COOLDOWN_MS", "u32", format_args!("{}", unsafe { ALERT_COOLDOWN_MS }));
+            dump_static(uart, "TILT_FILTER", "TiltFilter", format_args!("TiltFilter::{:?}", unsafe { TILT_FILTER }));
+            // imu.alpha sets all three complementary filters
+            let alpha = unsafe { COMPLEMENTARY.alpha };
+            for name in ["COMPLEMENTARY", "ROLL", "PITCH"] {
+                dump_static(uart, name, "ComplementaryFilter", format_args!("ComplementaryFilter::new({:?})", alpha));
+            }
+            let smooth = unsafe { ACCEL_EMA }.alpha();
+            dump_static(uart, "ACCEL_EMA", "Ema3", format_args!("Ema3::new({:?})", smooth));
+            dump_static(uart, "AUTOCAL", "bool", format_args!("{}", unsafe { AUTOCAL }));
+            dump_static(uart, "AUTOCAL_STILL_MS", "u32", format_args!("{}", unsafe { AUTOCAL_STILL_MS }));
+            dump_static(uart, "SEDENTARY_TIMEOUT_MIN", "u32", format_args!("{}", unsafe { SEDENTARY_TIMEOUT_MIN }));
+            dump_static(uart, "STREAM_AVG", "bool", format_args!("{}", unsafe { STREAM_AVG }));
+            dump_static(uart, "STREAM_CSV", "bool", format_args!("{}", unsafe { STREAM_CSV }));
+            dump_static(uart, "STREAM_CRC", "bool", format_args!("{}", unsafe { STREAM_CRC }));
+        }
+        "log.dump" => {
+            let mut buf: String<128> = String::new();
+            write!(
+                buf,
+                "Log: {} entries (storing {})\r\n",
+                crate::logbuf::len(),
+                crate::logbuf::store_level()
+            )
+            .ok();
+            let _ = uart.write_str(&buf);
+            crate::logbuf::for_each(|entry| {
+                buf.clear();
+                write!(buf, "[{}][{}] {}\r\n", entry.level, entry.tag, entry.msg).ok();
+                let _ = uart.write_str(&buf);
+            });
+        }
+        "log.filter" => {
+            if let Some(level) = parts.get(1).and_then(|p| p.parse::<log::LevelFilter>().ok()) {
+                crate::logbuf::set_store_level(level);
+                let mut buf: String<48> = String::new();
+                write!(buf, "OK [Storing {} and above]\r\n", level).ok();
+                let _ = uart.write_str(&buf);
+            } else {
+                let _ = uart.write_str("ERROR: Usage: log.filter <off|error|warn|info|debug|trace>\r\n");
+                ok = false;
+            }
+        }
+        "log.clear" => {
+            crate::logbuf::clear();
+            let _ = uart.write_str("OK [Log cleared]\r\n");
+        }
+        "temp.limit" => {
+            if parts.len() < 2 {
+                let _ = uart.write_str("ERROR: Usage: temp.limit <c> [warn|sleep]\r\n");
+                ok = false;
+            } else if let Ok(limit) = parts[1].parse::<f32>() {
+                let sleep = match parts.get(2) {
+                    None => Some(unsafe { TEMP_SLEEP_ON_LIMIT }),
+                    Some(&"warn") => Some(false),
+                    Some(&"sleep") => Some(true),
+                    Some(_) => None,
+                };
+                if let Some(sleep) = sleep {
+                    unsafe {
+                        TEMP_LIMIT_C = limit;
+                        TEMP_SLEEP_ON_LIMIT = sleep;
+                        // Re-evaluate against the new limit on the next check
+                        OVER_TEMP = false;
+                    }
+                    let mut buf: String<64> = String::new();
+                    write!(buf, "OK [Temp limit={:.1}C, {}]\r\n", limit, if sleep { "sleep" } else { "warn" }).ok();
+                    let _ = uart.write_str(&buf);
+                } else {
+                    let _ = uart.write_str("ERROR: Action must be warn or sleep\r\n");
+                    ok = false;
+                }
+            } else {
+                let _ = uart.write_str("ERROR: Invalid temperature\r\n");
+                ok = false;
+            }
+        }
+        "cpu.wfi" => {
+            // Not the device Sleep state: the core itself stops until an interrupt
+            // is pending. The wake timer is the only interrupt we enable, so it
+            // parks for at most one tick, far inside the watchdog timeout.
+            let wakeups_before = unsafe { WFI_WAKEUPS };
+            critical_section::with(|cs| {
+                if let Some(timer) = WAKE_TIMER.borrow_ref_mut(cs).as_mut() {
+                    timer.clear_interrupt();
+                    timer.listen();
+                }
+            });
+            unsafe {
+                core::arch::asm!("wfi");
+            }
+            critical_section::with(|cs| {
+                if let Some(timer) = WAKE_TIMER.borrow_ref_mut(cs).as_mut() {
+                    timer.unlisten();
+                }
+            });
+            let mut buf: String<64> = String::new();
+            write!(
+                buf,
+                "OK [Woke from WFI, {} timer interrupt(s)]\r\n",
+                unsafe { WFI_WAKEUPS }.wrapping_sub(wakeups_before)
+            )
+            .ok();
+            let _ = uart.write_str(&buf);
+        }
+        "sys.hang" => {
+            let _ = uart.write_str("Hanging, expect a watchdog reset in ~3 s\r\n");
+            loop {
+                core::hint::spin_loop();
+            }
+        }
+        "sys.reset" => {
+            let _ = uart.write_str("Resetting...\r\n");
+            // Give the UART time to shift the line out before the reset cuts it off
+            Delay::new().delay_millis(RESET_FLUSH_MS);
+            software_reset();
+        }
+        "debug.profile" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    PROFILE_COMMANDS = true;
+                }
+                let _ = uart.write_str("OK [Command profiling on]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    PROFILE_COMMANDS = false;
+                }
+                let _ = uart.write_str("OK [Command profiling off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: debug.profile <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "demo" => {
+            let _ = uart.write_str("OK [Demo starting, press any key to stop]\r\n");
+            unsafe {
+                DEMO_STEP = 1;
+            }
+        }
+        "term.echo" => match parts.get(1) {
+            Some(&"on") => {
+                unsafe {
+                    TERM_ECHO = true;
+                }
+                let _ = uart.write_str("OK [Echo on]\r\n");
+            }
+            Some(&"off") => {
+                unsafe {
+                    TERM_ECHO = false;
+                }
+                let _ = uart.write_str("OK [Echo off]\r\n");
+            }
+            _ => {
+                let _ = uart.write_str("ERROR: Usage: term.echo <on|off>\r\n");
+                ok = false;
+            }
+        },
+        "ping" => {
+            let mut buf: String<32> = String::new();
+            write!(buf, "pong {}\r\n", unsafe { UPTIME_MS }).ok();
+            let _ = uart.write_str(&buf);
+        }
+        _ => ok = super::unknown(uart),
+    }
+
+    ok
+}
+
+/// Print one setting the way state.rs declares it
+fn dump_static<W: Write>(uart: &mut W, name: &str, ty: &str, value: core::fmt::Arguments) {
+    let mut buf: String<320> = String::new();
+    write!(buf, "#[no_mangle]\r\npub static mut {}: {} = {};\r\n", name, ty, value).ok();
+    let _ = uart.write_str(&buf);
+}
@@ -0,0 +1,203 @@
+//! Pin assignments and tuning constants
+//!
+//! Everything here is fixed at build time; the runtime-adjustable settings
+//! are the globals in `state.rs`.
+
+use smart_leds::RGB8;
+
+// GPIO configuration
+pub const LED_PIN: u8 = 12;
+pub const LED_ACTIVE_LOW: bool = false; // true if the LED is wired from 3V3 to the pin (sink)
+pub const BUTTON_PIN: u8 = 9;
+pub const NEOPIXEL_PIN: u8 = 8;
+pub const UART_TX_PIN: u8 = 23;
+pub const UART_RX_PIN: u8 = 15;
+pub const I2C_SDA_PIN: u8 = 2;
+pub const I2C_SCL_PIN: u8 = 11;
+pub const KNOB_ADC_PIN: u8 = 3; // Potentiometer wiper (ADC1_CH3)
+pub const ENCODER_A_PIN: u8 = 4; // Rotary encoder channel A
+pub const ENCODER_B_PIN: u8 = 5; // Rotary encoder channel B
+pub const BUZZER_PIN: u8 = 10; // Passive piezo, other lead to GND
+
+pub const UART_BAUD: u32 = 115200;
+pub const I2C_FREQ: u32 = 100_000;
+pub const CMD_BUFFER_SIZE: usize = 128;
+
+// Potentiometer → warning threshold mapping
+pub const KNOB_ADC_MAX: u16 = 4095; // 12-bit reading at 11 dB attenuation
+pub const KNOB_MIN_DEG: f32 = 5.0;
+pub const KNOB_MAX_DEG: f32 = 85.0;
+pub const KNOB_READ_PERIOD_MS: u32 = 100;
+
+// Rotary encoder adjustment per detent (knob.target)
+pub const ENCODER_DEG_PER_STEP: f32 = 1.0;
+pub const ENCODER_MS_PER_STEP: u32 = 10;
+
+// posture.warn / posture.alert accept thresholds strictly between 0° and this
+pub const THRESHOLD_MAX_DEG: f32 = 90.0;
+
+/// The rule every threshold change goes through (CLI, knob, encoder):
+/// 0 < warn < alert < THRESHOLD_MAX_DEG
+pub fn thresholds_valid(warn: f32, alert: f32) -> bool {
+    warn > 0.0 && warn < alert && alert < THRESHOLD_MAX_DEG
+}
+
+// Neopixel strobe (neo.strobe). Capped at 3 Hz: faster flashing is a
+// photosensitive seizure risk (WCAG "three flashes" guideline).
+pub const NEO_STROBE_MAX_HZ: u8 = 3;
+pub const NEO_STROBE_COLOR: RGB8 = RGB8::new(255, 255, 255);
+
+// device.cal_check: max angle between the current gravity vector and the
+// one captured at calibration before recalibration is suggested
+pub const CAL_CHECK_MAX_DEG: f32 = 15.0;
+
+// Monitoring IMU sample period limits (posture.sample_period)
+pub const SAMPLE_PERIOD_MIN_MS: u32 = 10;
+pub const SAMPLE_PERIOD_MAX_MS: u32 = 1000;
+
+// Post-calibration check: max tilt (with the new offsets) to accept them.
+// cal.verify applies the same tolerance to the mean of CAL_VERIFY_SAMPLES reads.
+pub const CAL_VERIFY_TOLERANCE_DEG: f32 = 3.0;
+pub const CAL_VERIFY_SAMPLES: i32 = 20;
+pub const CAL_VERIFY_SPACING_MS: u32 = 5;
+pub const CAL_RETRIES_MAX: u8 = 10;
+
+// Calibration is aborted as soon as any gyro axis exceeds this rate: the
+// device is being moved and the averaged offsets would be wrong
+pub const CAL_STILL_GYRO_DPS: f32 = 5.0;
+pub const CAL_ABORT_FLASH_MS: u32 = 500;
+
+// cal.mount: resting vector averaged over this many reads
+pub const MOUNT_SAMPLES: u32 = 32;
+pub const MOUNT_SAMPLE_SPACING_MS: u32 = 5;
+
+// imu.dump: (first register, count) blocks read in one burst each. Config
+// (SMPLRT_DIV, CONFIG, GYRO_CONFIG, ACCEL_CONFIG), interrupt setup, USER_CTRL
+// and both power registers, WHO_AM_I.
+pub const IMU_DUMP_BLOCKS: [(u8, usize); 4] = [
+    (mpu6050::SMPLRT_DIV, 4),
+    (mpu6050::INT_PIN_CFG, 2),
+    (mpu6050::USER_CTRL, 3),
+    (mpu6050::WHO_AM_I_REG, 1),
+];
+
+// Guided demo: each step runs this long, colors cycled in step 1
+pub const DEMO_STEP_MS: u32 = 3000;
+pub const DEMO_STEPS: u8 = 5;
+pub const DEMO_STOP: u8 = u8::MAX;
+pub const DEMO_COLORS: [RGB8; 5] = [
+    RGB8::new(30, 0, 0),
+    RGB8::new(0, 30, 0),
+    RGB8::new(0, 0, 30),
+    RGB8::new(30, 30, 0),
+    RGB8::new(30, 0, 30),
+];
+
+// imu.capture RAM buffer (16 bytes per sample)
+pub const CAPTURE_MAX_SAMPLES: usize = 512;
+
+// imu.noise sample window
+pub const NOISE_DEFAULT_SAMPLES: u32 = 200;
+pub const NOISE_MAX_SAMPLES: u32 = 2000;
+pub const NOISE_SAMPLE_SPACING_MS: u32 = 2;
+
+// Auto-calibrate on boot (`--features auto-cal`): after the warm-up the
+// current orientation becomes zero and the device starts Monitoring
+pub const AUTO_CAL_ON_BOOT: bool = cfg!(feature = "auto-cal");
+pub const AUTO_CAL_WARMUP_MS: u32 = 3000;
+pub const AUTO_CAL_BLINK_MS: u32 = 250;
+
+// Over-temperature guard (MPU die temperature, temp.limit)
+pub const TEMP_CHECK_PERIOD_MS: u32 = 1000;
+pub const TEMP_HYSTERESIS_C: f32 = 2.0;
+pub const OVERTEMP_COLOR: RGB8 = RGB8::new(30, 0, 30); // Magenta
+
+// Gyro sensitivity at the default ±250 °/s range
+pub const GYRO_LSB_PER_DPS: f32 = 131.0;
+
+// Motion gate: alerts don't escalate while the device is being moved.
+// Accel deviation from 1 g in g (any range); gyro in raw counts at ±250 °/s.
+pub const MOTION_ACCEL_DEV_G: f32 = 0.15;
+pub const MOTION_GYRO_MAG: i32 = 3930; // 30 °/s
+
+// Stationary detection (posture.autocal): much tighter than the motion gate
+pub const STILL_ACCEL_DEV_G: f32 = 0.05;
+pub const STILL_GYRO_MAG: i32 = 393; // 3 °/s
+pub const STILL_TILT_DRIFT_DEG: f32 = 1.0; // accel angle change allowed while still
+
+// Gyro bias auto-zeroing: samples are averaged into GYRO_BIAS once the device
+// has been stationary (as above) for this long
+pub const GYRO_BIAS_STILL_MS: u32 = 2000;
+
+// Drop detection from the accel magnitude (in g, any range). Free fall: below
+// FREEFALL_G for FREEFALL_SAMPLES samples in a row (150 ms at the default
+// 50 ms period); impact: one sample above IMPACT_G. At ±2 g only a hit
+// across several axes can exceed IMPACT_G; ACCEL_RANGE G4 or more catches more.
+pub const FREEFALL_G: f32 = 0.3;
+pub const FREEFALL_SAMPLES: u32 = 3;
+pub const IMPACT_G: f32 = 2.5;
+pub const FREEFALL_COLOR: RGB8 = RGB8::new(0, 0, 40); // Blue
+pub const FREEFALL_FLASH_MS: u32 = 1000;
+pub const IMPACT_COLOR: RGB8 = RGB8::new(40, 40, 40); // White
+pub const IMPACT_FLASH_MS: u32 = 150;
+// After the flash an impact starts the white strobe (NEO_STROBE_MAX_HZ) for this long
+pub const IMPACT_STROBE_MS: u32 = 3000;
+
+// Shake gesture: SHAKE_SIGN_CHANGES reversals of the high-passed accel, each
+// swing over SHAKE_THRESHOLD_G, within SHAKE_WINDOW_MS. Acknowledged with
+// SHAKE_FLASHES white blinks of SHAKE_FLASH_PERIOD_MS each.
+pub const SHAKE_THRESHOLD_G: f32 = 1.0;
+pub const SHAKE_SIGN_CHANGES: u32 = 4;
+pub const SHAKE_WINDOW_MS: u32 = 1000;
+pub const SHAKE_FLASH_COLOR: RGB8 = RGB8::new(40, 40, 40); // White
+pub const SHAKE_FLASHES: u32 = 3;
+pub const SHAKE_FLASH_PERIOD_MS: u32 = 300;
+
+// Sedentary nudge: slow blue pulses on the Neopixel
+pub const SEDENTARY_PULSE_MS: u32 = 2000;
+pub const SEDENTARY_PULSES: u32 = 3;
+pub const SEDENTARY_BLUE_MAX: u32 = 40;
+
+// Posture score: alert level recorded every SCORE_SAMPLE_MS over the last
+// SCORE_WINDOW_SAMPLES (60 s), percentages refreshed every SCORE_UPDATE_MS
+pub const SCORE_WINDOW_SAMPLES: usize = 600;
+pub const SCORE_SAMPLE_MS: u32 = 100;
+pub const SCORE_UPDATE_MS: u32 = 1000;
+
+// Buzzer: LEDC square wave at 50% duty, switched on/off for the beep pattern
+// (Alert: 200 ms on every 400 ms, Warning: 100 ms on every 2 s)
+pub const BUZZER_FREQ_HZ: u32 = 2000;
+pub const BUZZER_TEST_MS: u32 = 500;
+pub const BUZZER_TEST_MIN_HZ: u32 = 100;
+pub const BUZZER_TEST_MAX_HZ: u32 = 10_000;
+
+// Shutdown: Neopixel fades from the alert color to off when a session ends
+pub const SHUTDOWN_FADE_MS: u32 = 800;
+
+// Crossfade between alert colors (Normal/Warning/Alert) while Monitoring
+pub const ALERT_FADE_MS: u32 = 300;
+
+// RTC watchdog (RWDT) timeout: the main loop feeds it every tick, so only a
+// hang (or sys.hang) lets it expire and reset the chip
+pub const WATCHDOG_TIMEOUT_MS: u64 = 3000;
+
+// sys.reset: wait this long after the confirmation so the UART TX FIFO
+// (128 bytes, ~11 ms at 115200 baud) drains before the chip resets
+pub const RESET_FLUSH_MS: u32 = 50;
+
+// Sleep state: light sleep between button presses. The timer wake keeps the
+// watchdog fed (it runs on during light sleep); no light sleep for a while
+// after the last received character, so the CLI stays usable.
+pub const LIGHT_SLEEP_TIMER_MS: u64 = 1000;
+pub const LIGHT_SLEEP_RX_IDLE_MS: u32 = 10_000;
+
+// cpu.wfi wake-up timer tick (matches the main loop tick)
+pub const WFI_WAKE_PERIOD_MS: u64 = 10;
+
+// Alert and state-change log lines: after the first, at most one per
+// message per this window; the next line logged counts the dropped repeats
+pub const LOG_RATE_LIMIT_MS: u32 = 1000;
+
+// LED blink frequencies
+pub const LED_BLINK_WARNING_HZ: u32 = 1;  // 1 Hz
+pub const LED_BLINK_ALERT_HZ: u32 = 5;    // 5 Hz
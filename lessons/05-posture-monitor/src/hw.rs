@@ -0,0 +1,121 @@
+//! Peripherals shared by the main loop and the CLI, and the state transitions
+//! that drive them
+
+use core::fmt::Write;
+use errcode::ErrorCode;
+use esp_hal::{
+    gpio::{Input, Output},
+    rtc_cntl::Rtc,
+};
+use esp_storage::FlashStorage;
+use heapless::String;
+use log::{error, info};
+use smart_leds::{SmartLedsWrite, RGB8};
+
+use crate::buzzer::Buzzer;
+use crate::config::*;
+use crate::state::*;
+
+/// Peripherals driven by state transitions and CLI commands
+pub struct Hw<'a, N> {
+    pub led: Output<'static>,
+    pub neopixel: N,
+    /// Last color shown, before brightness scaling (neo.bright repaints it)
+    pub neo_color: RGB8,
+    pub flash: FlashStorage<'static>,
+    /// Owns the RTC watchdog; long-running commands feed it too
+    pub rtc: Rtc<'static>,
+    pub buzzer: Buzzer<'a>,
+    /// Inputs, polled by the main loop and readable with gpio.read
+    pub button: Input<'static>,
+    pub encoder_a: Input<'static>,
+    pub encoder_b: Input<'static>,
+}
+
+impl<N> Hw<'_, N> {
+    /// The input on `pin`, or None if it isn't one of the input pins
+    pub fn input(&self, pin: u8) -> Option<&Input<'static>> {
+        match pin {
+            BUTTON_PIN => Some(&self.button),
+            ENCODER_A_PIN => Some(&self.encoder_a),
+            ENCODER_B_PIN => Some(&self.encoder_b),
+            _ => None,
+        }
+    }
+
+    /// Turn the LED on or off, whichever pin level that takes on this board
+    pub fn set_led(&mut self, on: bool) {
+        if on != LED_ACTIVE_LOW {
+            self.led.set_high();
+        } else {
+            self.led.set_low();
+        }
+    }
+
+    pub fn feed_watchdog(&mut self) {
+        self.rtc.rwdt.feed();
+    }
+}
+
+impl<N> Hw<'_, N>
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    /// Show a color on the Neopixel, scaled by NEO_BRIGHTNESS. Every
+    /// Neopixel write goes through here; black stays fully off.
+    pub fn set_neo(&mut self, color: RGB8) {
+        self.neo_color = color;
+        let brightness = unsafe { NEO_BRIGHTNESS } as u16;
+        let scale = |c: u8| (c as u16 * brightness / 255) as u8;
+        let scaled = RGB8::new(scale(color.r), scale(color.g), scale(color.b));
+        self.neopixel.write([scaled].into_iter()).ok();
+    }
+}
+
+/// Apply a state change with all of its side effects (Neopixel, LED,
+/// alert level, calibration restart). Button, CLI and the calibration
+/// completion path all go through here so they can't drift apart.
+pub fn transition_to<N>(new_state: DeviceState, hw: &mut Hw<'_, N>)
+where
+    N: SmartLedsWrite,
+    RGB8: Into<N::Color>,
+{
+    hw.set_led(false);
+    unsafe {
+        LED_STATE = false;
+        match new_state {
+            DeviceState::Sleep => {
+                NEO_STROBE_HZ = 0;
+                hw.set_neo(RGB8::new(0, 0, 0)); // Off
+            }
+            DeviceState::Monitoring => {
+                // Start from Normal; the next sample repaints if the device is tilted
+                ALERT_LEVEL = AlertLevel::Normal;
+                hw.set_neo(alert_color(AlertLevel::Normal));
+            }
+            DeviceState::Calibrating => {
+                CALIBRATION_SAMPLES = 0;
+                hw.set_neo(RGB8::new(30, 30, 0)); // Yellow
+            }
+        }
+        let old_state = DEVICE_STATE;
+        let mut limit = STATE_LOG_LIMIT[new_state as usize];
+        if let Some(suppressed) = limit.check(UPTIME_MS) {
+            info!("[STATE] {:?} → {:?}{}", old_state, new_state, suppressed);
+        }
+        STATE_LOG_LIMIT[new_state as usize] = limit;
+        DEVICE_STATE = new_state;
+    }
+}
+
+/// Report a fatal error on the log and UART; the main loop blinks it on the LED
+pub fn report_fault<W: Write>(code: ErrorCode, uart: &mut W) {
+    error!("[FAULT] {} (LED blinks {}x)", code.description(), code.blinks());
+    let mut buf: String<96> = String::new();
+    write!(buf, "FAULT: {} (LED blinks {}x)\r\n", code.description(), code.blinks()).ok();
+    let _ = uart.write_str(&buf);
+    unsafe {
+        FAULT = Some(code);
+    }
+}
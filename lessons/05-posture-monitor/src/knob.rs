@@ -0,0 +1,55 @@
+//! Potentiometer and rotary encoder → posture settings
+
+use log::info;
+
+use crate::config::*;
+use crate::state::*;
+
+/// Nudge the setting selected by `knob.target` by one encoder detent
+///
+/// A threshold step that would break `thresholds_valid` is ignored, so the
+/// encoder can't reach a pair the posture.* commands would reject.
+pub fn apply_encoder_step(step: i8) {
+    let (warn, alert) = unsafe { (TILT_WARNING_THRESHOLD, TILT_ALERT_THRESHOLD) };
+    let delta = step as f32 * ENCODER_DEG_PER_STEP;
+    match unsafe { KNOB_TARGET } {
+        KnobTarget::None => {}
+        KnobTarget::Warning => {
+            if thresholds_valid(warn + delta, alert) {
+                unsafe {
+                    TILT_WARNING_THRESHOLD = warn + delta;
+                }
+            }
+            info!("[KNOB] warn={:.0}°", unsafe { TILT_WARNING_THRESHOLD });
+        }
+        KnobTarget::Alert => {
+            if thresholds_valid(warn, alert + delta) {
+                unsafe {
+                    TILT_ALERT_THRESHOLD = alert + delta;
+                }
+            }
+            info!("[KNOB] alert={:.0}°", unsafe { TILT_ALERT_THRESHOLD });
+        }
+        KnobTarget::SamplePeriod => {
+            let period = unsafe { SAMPLE_PERIOD_MS } as i32 + step as i32 * ENCODER_MS_PER_STEP as i32;
+            let period = (period.max(0) as u32).clamp(SAMPLE_PERIOD_MIN_MS, SAMPLE_PERIOD_MAX_MS);
+            unsafe {
+                SAMPLE_PERIOD_MS = period;
+            }
+            info!("[KNOB] sample_period={}ms", period);
+        }
+    }
+}
+
+/// Map a raw potentiometer reading onto the warning threshold range,
+/// staying at least 1° below the alert threshold
+///
+/// None when no such value passes `thresholds_valid` (alert set to 1° or
+/// less from the CLI); the warning threshold is then left alone.
+pub fn knob_to_threshold(raw: u16) -> Option<f32> {
+    let fraction = raw.min(KNOB_ADC_MAX) as f32 / KNOB_ADC_MAX as f32;
+    let deg = KNOB_MIN_DEG + (KNOB_MAX_DEG - KNOB_MIN_DEG) * fraction;
+    let alert = unsafe { TILT_ALERT_THRESHOLD };
+    let deg = deg.min(alert - 1.0);
+    thresholds_valid(deg, alert).then_some(deg)
+}
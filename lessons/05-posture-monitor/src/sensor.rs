@@ -0,0 +1,89 @@
+//! Accelerometer helpers that depend on the calibration state: offsets,
+//! mount reference, range, and the MPU's own offset registers
+
+use esp_hal::i2c::master::{Error as I2cError, I2c};
+
+use crate::config::GYRO_LSB_PER_DPS;
+use crate::state::*;
+
+/// Raw accelerometer counts for 1 g at the configured range
+pub fn accel_1g() -> i32 {
+    mpu6050::accel_sensitivity(unsafe { ACCEL_RANGE }) as i32
+}
+
+/// Calibration offsets to subtract from raw accel reads
+///
+/// Zero with `device.cal_hw on`: the MPU already subtracted them.
+pub fn accel_offsets() -> [i32; 3] {
+    unsafe {
+        if CAL_HW {
+            [0; 3]
+        } else {
+            [CAL_OFFSET_X as i32, CAL_OFFSET_Y as i32, CAL_OFFSET_Z as i32]
+        }
+    }
+}
+
+/// Tilt of an offset-corrected accel vector: from vertical, or from the
+/// cal.mount reference when one is set
+pub fn corrected_tilt(v: [i32; 3]) -> f32 {
+    match unsafe { MOUNT_REF } {
+        Some([rx, ry, rz]) => crate::tilt::angle_between(v, [rx as i32, ry as i32, rz as i32]),
+        None => crate::tilt::tilt_angle(v[0], v[1], v[2]),
+    }
+}
+
+/// Angle between a raw accel sample and the orientation the calibration was
+/// taken in (`device.cal_check`, and the check after loading it at boot)
+pub fn cal_deviation(raw: [i16; 3]) -> f32 {
+    // Offsets hold the resting vector minus 1g on Z; add gravity back.
+    // With a mount reference, compare in the offset-corrected frame instead.
+    let [ox, oy, oz] = accel_offsets();
+    let [x, y, z] = raw.map(|v| v as i32);
+    let (reference, current) = match unsafe { MOUNT_REF } {
+        Some([rx, ry, rz]) => ([rx as i32, ry as i32, rz as i32], [x - ox, y - oy, z - oz]),
+        None => ([ox, oy, oz + accel_1g()], [x, y, z]),
+    };
+    crate::tilt::angle_between(current, reference)
+}
+
+/// Write the accel offset registers: factory trim, plus the calibration
+/// offsets when `with_cal` (see `mpu6050::write_hardware_offsets`)
+pub fn program_hw_offsets<Dm: esp_hal::DriverMode>(
+    i2c: &mut I2c<Dm>,
+    addr: u8,
+    with_cal: bool,
+) -> Result<(), mpu6050::Mpu6050Error<I2cError>> {
+    let trim = unsafe { ACCEL_TRIM };
+    let cal = if with_cal {
+        unsafe { [CAL_OFFSET_X, CAL_OFFSET_Y, CAL_OFFSET_Z] }
+    } else {
+        [0; 3]
+    };
+    // Raw counts per offset LSB: 8 at ±2 g down to 1 at ±16 g
+    let counts_per_lsb = (accel_1g() as f32 / mpu6050::ACCEL_OFFS_LSB_PER_G) as i16;
+    let mut regs = [0i16; 3];
+    for (reg, (t, c)) in regs.iter_mut().zip(trim.iter().zip(cal.iter())) {
+        *reg = t.saturating_sub(c / counts_per_lsb);
+    }
+    mpu6050::write_hardware_offsets(i2c, addr, regs)
+}
+
+/// Euclidean length of a raw 3-axis sample, in counts
+pub fn vector_magnitude(x: i16, y: i16, z: i16) -> i32 {
+    let (x, y, z) = (x as i64, y as i64, z as i64);
+    crate::tilt::isqrt((x * x + y * y + z * z) as u64) as i32
+}
+
+/// Rate of change of total tilt (°/s) from the gyro
+///
+/// Only rotation about the horizontal axis perpendicular to the lean
+/// direction (ax, ay) changes the tilt; rotation about Z doesn't.
+pub fn tilt_rate_dps(ax: i32, ay: i32, gx: i16, gy: i16) -> f32 {
+    let (ax, ay) = (ax as f32, ay as f32);
+    let lean = crate::tilt::isqrt((ax * ax + ay * ay) as u64) as f32;
+    if lean < 1.0 {
+        return 0.0;
+    }
+    (gx as f32 * ay - gy as f32 * ax) / lean / GYRO_LSB_PER_DPS
+}
@@ -0,0 +1,59 @@
+//! Settings saved to flash: calibration, mount reference and alert colors
+//!
+//! The record format is in `persist.rs`; these read and write the globals.
+
+use esp_storage::FlashStorage;
+use smart_leds::RGB8;
+
+use crate::persist::{self, Key};
+use crate::state::*;
+
+/// Save the current calibration offsets (`Key::Calibration`)
+pub fn save_calibration(flash: &mut FlashStorage<'static>) -> Result<(), persist::Error> {
+    let offsets = unsafe { [CAL_OFFSET_X, CAL_OFFSET_Y, CAL_OFFSET_Z] };
+    let payload = persist::encode_calibration(unsafe { ACCEL_RANGE } as u8, offsets);
+    persist::store(flash, Key::Calibration, &payload)
+}
+
+/// Saved calibration offsets; None if the record is blank, corrupt, or was
+/// taken at another accel range (raw counts don't carry over)
+pub fn load_calibration(flash: &mut FlashStorage<'static>) -> Option<[i16; 3]> {
+    let mut record = [0u8; persist::HEADER_LEN + persist::MAX_PAYLOAD];
+    let payload = persist::load(flash, Key::Calibration, &mut record)?;
+    let (range, offsets) = persist::decode_calibration(payload)?;
+    (range == unsafe { ACCEL_RANGE } as u8).then_some(offsets)
+}
+
+/// Save the mount reference (`Key::Mount`); with none set, an empty record
+/// so the next boot measures from vertical
+pub fn save_mount(flash: &mut FlashStorage<'static>) -> Result<(), persist::Error> {
+    match unsafe { MOUNT_REF } {
+        Some(reference) => {
+            let payload = persist::encode_calibration(unsafe { ACCEL_RANGE } as u8, reference);
+            persist::store(flash, Key::Mount, &payload)
+        }
+        None => persist::store(flash, Key::Mount, &[]),
+    }
+}
+
+/// Saved mount reference; None if cleared, blank, or taken at another accel range
+pub fn load_mount(flash: &mut FlashStorage<'static>) -> Option<[i16; 3]> {
+    let mut record = [0u8; persist::HEADER_LEN + persist::MAX_PAYLOAD];
+    let payload = persist::load(flash, Key::Mount, &mut record)?;
+    let (range, reference) = persist::decode_calibration(payload)?;
+    (range == unsafe { ACCEL_RANGE } as u8).then_some(reference)
+}
+
+/// Save the alert level colors (`Key::Colors`)
+pub fn save_colors(flash: &mut FlashStorage<'static>) -> Result<(), persist::Error> {
+    let colors = unsafe { ALERT_COLORS }.map(|c| [c.r, c.g, c.b]);
+    persist::store(flash, Key::Colors, &persist::encode_colors(colors))
+}
+
+/// Saved alert level colors; None if never set, blank or corrupt
+pub fn load_colors(flash: &mut FlashStorage<'static>) -> Option<[RGB8; 3]> {
+    let mut record = [0u8; persist::HEADER_LEN + persist::MAX_PAYLOAD];
+    let payload = persist::load(flash, Key::Colors, &mut record)?;
+    let colors = persist::decode_colors(payload)?;
+    Some(colors.map(|[r, g, b]| RGB8::new(r, g, b)))
+}
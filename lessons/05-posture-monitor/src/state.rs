@@ -0,0 +1,406 @@
+//! Device state and the GDB-visible globals
+//!
+//! Every setting and reading the main loop and the CLI share lives here as a
+//! `#[no_mangle] static mut`, so GDB can read or change it by name
+//! (`print TILT_ANGLE`, `set var SAMPLE_PERIOD_MS = 20`). The firmware is
+//! single-threaded: only the main loop and the command handlers it calls
+//! touch them.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use errcode::ErrorCode;
+use esp_hal::{timer::PeriodicTimer, Blocking};
+use smart_leds::RGB8;
+
+use crate::beep::BeepPattern;
+use crate::config::*;
+use crate::filter::{ComplementaryFilter, Ema3, GyroBias, Kalman1D, YawIntegrator};
+use crate::gesture::Gesture;
+use crate::macros::MacroStore;
+use crate::ratelimit::RateLimiter;
+use crate::{AccelRange, DlpfConfig};
+
+// Device states
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum DeviceState {
+    Sleep = 0,
+    Monitoring = 1,
+    Calibrating = 2,
+}
+
+// Alert levels (sub-states of Monitoring)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum AlertLevel {
+    Normal = 0,   // 0-30°
+    Warning = 1,  // 30-60°
+    Alert = 2,    // >60°
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Mode {
+    CLI = 0,
+    Streaming = 1,
+    Raw = 2, // 4-byte binary samples, see raw_frame()
+}
+
+// Setting the rotary encoder adjusts (knob.target)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum KnobTarget {
+    None = 0,
+    Warning = 1,      // TILT_WARNING_THRESHOLD
+    Alert = 2,        // TILT_ALERT_THRESHOLD
+    SamplePeriod = 3, // SAMPLE_PERIOD_MS
+}
+
+// Tilt estimator feeding TILT_ANGLE (posture.filter)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum TiltFilter {
+    Raw = 0,           // accelerometer angle only
+    Complementary = 1, // COMPLEMENTARY, alpha set with imu.alpha
+    Kalman = 2,        // KALMAN
+}
+
+#[no_mangle]
+pub static mut DEVICE_STATE: DeviceState = DeviceState::Sleep;
+
+#[no_mangle]
+pub static mut ALERT_LEVEL: AlertLevel = AlertLevel::Normal;
+
+#[no_mangle]
+pub static mut MODE: Mode = Mode::CLI;
+
+// Milliseconds since boot, read from the system timer at the top of each
+// loop iteration (wraps after ~49 days; compare with wrapping_sub)
+#[no_mangle]
+pub static mut UPTIME_MS: u32 = 0;
+
+// Fatal error blinked on the LED (see crates/errcode); cleared once the IMU answers again
+#[no_mangle]
+pub static mut FAULT: Option<ErrorCode> = None;
+
+// Boots so far including this one (flash record, see device.boots)
+#[no_mangle]
+pub static mut BOOT_COUNT: u32 = 0;
+
+#[no_mangle]
+pub static mut TILT_ANGLE: f32 = 0.0;
+// Accelerometer-only angle of the last sample (after imu.smooth), before
+// the tilt filter or any override
+#[no_mangle]
+pub static mut TILT_RAW: f32 = 0.0;
+
+// Posture thresholds (degrees)
+#[no_mangle]
+pub static mut TILT_WARNING_THRESHOLD: f32 = 30.0;
+#[no_mangle]
+pub static mut TILT_ALERT_THRESHOLD: f32 = 60.0;
+
+// Warning threshold follows the potentiometer when enabled (`posture.knob on`)
+#[no_mangle]
+pub static mut KNOB_ENABLED: bool = false;
+
+#[no_mangle]
+pub static mut KNOB_TARGET: KnobTarget = KnobTarget::Warning;
+
+// IMU sample period while Monitoring (ms). 50 ms is plenty for posture;
+// drop toward 10 ms for responsiveness testing.
+#[no_mangle]
+pub static mut SAMPLE_PERIOD_MS: u32 = 50;
+
+// When set, the main loop stops updating telemetry globals (state machine
+// is skipped) so GDB sees a stable snapshot; the CLI keeps running
+#[no_mangle]
+pub static mut TELEMETRY_FROZEN: bool = false;
+
+// term.echo: echo typed characters back (off for terminals with local echo)
+#[no_mangle]
+pub static mut TERM_ECHO: bool = true;
+
+// debug.profile: print each command's execution time after its reply
+#[no_mangle]
+pub static mut PROFILE_COMMANDS: bool = false;
+
+// Calibration re-runs allowed when the verify step fails (device.cal_retries)
+#[no_mangle]
+pub static mut CAL_RETRIES: u8 = 2;
+
+// White strobe frequency in Hz (0 = off)
+#[no_mangle]
+pub static mut NEO_STROBE_HZ: u8 = 0;
+// Start of a strobe triggered by an impact, which stops by itself after
+// IMPACT_STROBE_MS; None for neo.strobe, which runs until neo.off
+#[no_mangle]
+pub static mut NEO_STROBE_AUTO_MS: Option<u32> = None;
+
+// Global Neopixel brightness (neo.bright), 255 = colors as given
+#[no_mangle]
+pub static mut NEO_BRIGHTNESS: u8 = 255;
+
+// buzzer.mute: silence the alert beeps (buzzer.test still sounds)
+#[no_mangle]
+pub static mut BUZZER_MUTED: bool = false;
+
+// Beep pattern per alert level, indexed by `AlertLevel as usize`
+// (buzzer.pattern, or set a field from GDB); Normal stays silent
+#[no_mangle]
+pub static mut BUZZER_PATTERNS: [BeepPattern; 3] = [BeepPattern::SILENT, BeepPattern::WARNING, BeepPattern::ALERT];
+
+// device.light_sleep: light-sleep the chip while in Sleep (off for GDB sessions:
+// the debugger loses the core while it sleeps)
+#[no_mangle]
+pub static mut LIGHT_SLEEP: bool = true;
+#[no_mangle]
+pub static mut LIGHT_SLEEP_COUNT: u32 = 0;
+
+// MPU6050 I2C address (imu.addr): 0x68, or 0x69 with AD0 strapped high
+#[no_mangle]
+pub static mut IMU_ADDR: u8 = mpu6050::MPU_ADDR;
+
+// Accelerometer full-scale range, written to the sensor at boot. Calibration
+// offsets are raw counts, so they only hold for the range they were taken at.
+#[no_mangle]
+pub static mut ACCEL_RANGE: AccelRange = AccelRange::G2;
+
+// On-chip low-pass filter (imu.dlpf); wake_sensor resets it to the default,
+// so it is written again whenever the sensor is woken
+#[no_mangle]
+pub static mut DLPF: DlpfConfig = DlpfConfig::DEFAULT;
+
+// Current demo step (0 = not running, 1..=DEMO_STEPS, DEMO_STOP = finish)
+#[no_mangle]
+pub static mut DEMO_STEP: u8 = 0;
+
+// imu.capture burst: [t_us, ax, ay, az, gx, gy, gz] per sample, t_us from
+// the first sample. Filled before anything is printed, so the capture rate
+// is set by the I2C transaction alone.
+#[no_mangle]
+pub static mut CAPTURE_T_US: [u32; CAPTURE_MAX_SAMPLES] = [0; CAPTURE_MAX_SAMPLES];
+#[no_mangle]
+pub static mut CAPTURE_DATA: [[i16; 6]; CAPTURE_MAX_SAMPLES] = [[0; 6]; CAPTURE_MAX_SAMPLES];
+
+// imu.fifo on: the MPU buffers every accel+gyro sample in its 1 KB FIFO
+// until imu.fifo read drains it (the monitor itself keeps polling)
+#[no_mangle]
+pub static mut IMU_FIFO: bool = false;
+
+// Optional second MPU6050 on the same bus (imu2.*, joint.angle)
+#[no_mangle]
+pub static mut IMU2_ADDR: u8 = mpu6050::MPU_ADDR_ALT;
+
+#[no_mangle]
+pub static mut IMU_ACCEL_X: i16 = 0;
+#[no_mangle]
+pub static mut IMU_ACCEL_Y: i16 = 0;
+#[no_mangle]
+pub static mut IMU_ACCEL_Z: i16 = 0;
+
+#[no_mangle]
+pub static mut CAL_OFFSET_X: i16 = 0;
+#[no_mangle]
+pub static mut CAL_OFFSET_Y: i16 = 0;
+#[no_mangle]
+pub static mut CAL_OFFSET_Z: i16 = 0;
+
+// device.cal_hw: offsets live in the MPU's own registers instead of being
+// subtracted after each read. ACCEL_TRIM is the factory trim read at boot.
+#[no_mangle]
+pub static mut CAL_HW: bool = false;
+#[no_mangle]
+pub static mut ACCEL_TRIM: [i16; 3] = [0; 3];
+
+#[no_mangle]
+pub static mut CALIBRATION_SAMPLES: u16 = 0;
+
+// cal.mount: resting gravity vector (offset-corrected counts) that tilt is
+// measured from. None = tilt from vertical, i.e. from the +Z axis.
+#[no_mangle]
+pub static mut MOUNT_REF: Option<[i16; 3]> = None;
+
+#[no_mangle]
+pub static mut LED_STATE: bool = false;
+
+// Neopixel color per alert level, indexed by `AlertLevel as usize`
+// (default green/yellow/red; change with `posture.color`, saved to flash)
+#[no_mangle]
+pub static mut ALERT_COLORS: [RGB8; 3] = [
+    RGB8::new(0, 30, 0),
+    RGB8::new(30, 30, 0),
+    RGB8::new(30, 0, 0),
+];
+
+// MPU die temperature (°C), refreshed every TEMP_CHECK_PERIOD_MS while awake
+#[no_mangle]
+pub static mut TEMP_C: f32 = 0.0;
+
+// Over-temperature limit (°C) and whether exceeding it forces Sleep
+#[no_mangle]
+pub static mut TEMP_LIMIT_C: f32 = 60.0;
+#[no_mangle]
+pub static mut TEMP_SLEEP_ON_LIMIT: bool = false;
+
+#[no_mangle]
+pub static mut OVER_TEMP: bool = false;
+
+// Times each alert level was entered this session, indexed by `AlertLevel as usize`
+#[no_mangle]
+pub static mut ALERT_COUNTS: [u32; 3] = [0; 3];
+
+// Re-entering Warning/Alert within this window (posture.cooldown) still
+// changes the Neopixel and LED but skips the notification (0 = never suppress)
+#[no_mangle]
+pub static mut ALERT_COOLDOWN_MS: u32 = 10_000;
+
+// Log rate limiters (LOG_RATE_LIMIT_MS), one per message: `[ALERT]` lines
+// indexed by `AlertLevel as usize`, `[STATE]` lines by `DeviceState as usize`
+#[no_mangle]
+pub static mut ALERT_LOG_LIMIT: [RateLimiter; 3] = [RateLimiter::new(LOG_RATE_LIMIT_MS); 3];
+#[no_mangle]
+pub static mut STATE_LOG_LIMIT: [RateLimiter; 3] = [RateLimiter::new(LOG_RATE_LIMIT_MS); 3];
+
+// Tilt estimator (posture.filter). The complementary filter runs on every
+// sample whichever is selected, so switching to it and posture.compare
+// never start cold.
+#[no_mangle]
+pub static mut TILT_FILTER: TiltFilter = TiltFilter::Complementary;
+#[no_mangle]
+pub static mut COMPLEMENTARY: ComplementaryFilter = ComplementaryFilter::new(ComplementaryFilter::DEFAULT_ALPHA);
+#[no_mangle]
+pub static mut KALMAN: Kalman1D = Kalman1D::new(
+    Kalman1D::DEFAULT_Q_ANGLE,
+    Kalman1D::DEFAULT_Q_BIAS,
+    Kalman1D::DEFAULT_R_MEASURE,
+);
+
+// Accel smoothing before the tilt calculation (imu.smooth): weight of the
+// newest sample, 1.0 = off. Only the angle uses it; IMU_ACCEL_X/Y/Z stay raw.
+pub const ACCEL_SMOOTH_DEFAULT: f32 = 0.5;
+#[no_mangle]
+pub static mut ACCEL_EMA: Ema3 = Ema3::new(ACCEL_SMOOTH_DEFAULT);
+
+// Heading from the integrated Z gyro (imu.yaw, yaw.reset). Updated on every
+// Monitoring sample; drifts with gyro bias since nothing corrects it.
+#[no_mangle]
+pub static mut YAW: YawIntegrator = YawIntegrator::new();
+#[no_mangle]
+pub static mut YAW_ANGLE: f32 = 0.0;
+
+// Roll (side lean, about X) and pitch (forward lean, about Y) for imu.rpy,
+// updated on every Monitoring sample: accel angles fused with the X/Y gyro
+// by complementary filters that follow imu.alpha
+#[no_mangle]
+pub static mut ROLL: ComplementaryFilter = ComplementaryFilter::new(ComplementaryFilter::DEFAULT_ALPHA);
+#[no_mangle]
+pub static mut PITCH: ComplementaryFilter = ComplementaryFilter::new(ComplementaryFilter::DEFAULT_ALPHA);
+#[no_mangle]
+pub static mut ROLL_ANGLE: f32 = 0.0;
+#[no_mangle]
+pub static mut PITCH_ANGLE: f32 = 0.0;
+
+// Gyro zero-rate offset learned while stationary (imu.gyrobias,
+// gyrobias.reset); subtracted from every Monitoring gyro read
+#[no_mangle]
+pub static mut GYRO_BIAS: GyroBias = GyroBias::new();
+
+// posture.compare: print raw, complementary and (if enabled) Kalman angles
+// for every Monitoring sample
+#[no_mangle]
+pub static mut COMPARE: bool = false;
+
+// posture.autocal: calibrate automatically once the device has been still for
+// AUTOCAL_STILL_MS without a calibration this boot (off: just suggest it)
+#[no_mangle]
+pub static mut AUTOCAL: bool = false;
+#[no_mangle]
+pub static mut AUTOCAL_STILL_MS: u32 = AUTO_CAL_WARMUP_MS;
+// Offsets are valid: calibrated this boot or restored from flash
+#[no_mangle]
+pub static mut CALIBRATED: bool = false;
+
+// Nudge after this long without movement (posture.sedentary_timeout, 0 = off);
+// repeats every timeout while the device stays still
+#[no_mangle]
+pub static mut SEDENTARY_TIMEOUT_MIN: u32 = 30;
+
+// posture.score: percent of the window spent in Normal/Warning/Alert and the
+// seconds it covers (less than 60 until the window fills). None until the
+// first sample of a session.
+#[no_mangle]
+pub static mut POSTURE_SCORE: Option<[u8; 3]> = None;
+#[no_mangle]
+pub static mut POSTURE_SCORE_SPAN_S: u32 = 0;
+
+// Set while accel magnitude is away from 1 g or the gyro reports rotation
+#[no_mangle]
+pub static mut MOVING: bool = false;
+
+// Gesture event: set to Shake when one is detected and back to None once the
+// main loop has handled it (calibration cleared). Set it from GDB to fake one.
+#[no_mangle]
+pub static mut GESTURE: Gesture = Gesture::None;
+
+// Tilt injected from GDB (inject_tilt / clear_tilt_override). While set,
+// Monitoring uses TILT_OVERRIDE_DEG instead of the sensor-derived angle.
+#[no_mangle]
+pub static mut TILT_OVERRIDE: bool = false;
+#[no_mangle]
+pub static mut TILT_OVERRIDE_DEG: f32 = 0.0;
+
+// Streamed values: latest sample (false) or mean since the previous line (true)
+#[no_mangle]
+pub static mut STREAM_AVG: bool = false;
+
+// stream.csv: stream comma-separated rows instead of bracketed lines; the
+// header goes out once per streaming session
+#[no_mangle]
+pub static mut STREAM_CSV: bool = false;
+#[no_mangle]
+pub static mut CSV_HEADER_SENT: bool = false;
+
+// stream.crc: end every streamed text line with `*XX`, the CRC-8 of the
+// line before the `*`, so the host can drop lines corrupted on the wire
+#[no_mangle]
+pub static mut STREAM_CRC: bool = false;
+
+// Wake-up interrupts taken by the cpu.wfi timer
+#[no_mangle]
+pub static mut WFI_WAKEUPS: u32 = 0;
+
+// Timer that brings the core back out of `wfi` (shared with its ISR)
+pub static WAKE_TIMER: Mutex<RefCell<Option<PeriodicTimer<'static, Blocking>>>> = Mutex::new(RefCell::new(None));
+
+// Recorded command macros (macro.record / macro.run)
+pub static MACROS: Mutex<RefCell<MacroStore>> = Mutex::new(RefCell::new(MacroStore::new()));
+
+/// Configured Neopixel color for an alert level
+pub fn alert_color(level: AlertLevel) -> RGB8 {
+    unsafe { ALERT_COLORS[level as usize] }
+}
+
+// GDB-callable functions
+//
+//   (gdb) call inject_tilt(45.0)
+//   (gdb) call clear_tilt_override()
+
+/// Feed a simulated tilt to the Monitoring logic in place of the sensor angle
+/// (the IMU is still sampled when present; without one the alert logic runs
+/// on the injected angle alone)
+#[no_mangle]
+pub extern "C" fn inject_tilt(deg: f32) {
+    unsafe {
+        TILT_OVERRIDE_DEG = deg;
+        TILT_OVERRIDE = true;
+    }
+}
+
+/// Resume live tilt from the sensor
+#[no_mangle]
+pub extern "C" fn clear_tilt_override() {
+    unsafe {
+        TILT_OVERRIDE = false;
+    }
+}
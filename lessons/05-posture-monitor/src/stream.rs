@@ -0,0 +1,42 @@
+//! Streamed output: text lines with the optional CRC, and `stream.raw` frames
+
+use core::fmt::Write;
+use heapless::String;
+
+use crate::crc::crc8;
+use crate::state::*;
+
+/// Write one streamed text line, with its `*XX` CRC-8 when stream.crc is on
+pub fn write_stream_line<W: Write>(uart: &mut W, line: &str) {
+    let _ = uart.write_str(line);
+    if unsafe { STREAM_CRC } {
+        let mut buf: String<4> = String::new();
+        write!(buf, "*{:02X}", crc8(line.as_bytes())).ok();
+        let _ = uart.write_str(&buf);
+    }
+    let _ = uart.write_str("\r\n");
+}
+
+/// One `stream.raw` sample, little-endian:
+///
+/// | byte | content                                            |
+/// |------|----------------------------------------------------|
+/// | 0-1  | tilt, i16 in 0.01° units                           |
+/// | 2    | flags (see `raw_flags`)                            |
+/// | 3    | sequence number, wraps at 256 (detects drops)      |
+pub fn raw_frame(tilt_deg: f32, flags: u8, seq: u8) -> [u8; 4] {
+    let centideg = (tilt_deg * 100.0) as i16; // saturating cast
+    let [lo, hi] = centideg.to_le_bytes();
+    [lo, hi, flags, seq]
+}
+
+/// `stream.raw` flags byte: bits 0-1 alert level, bit 2 MOVING,
+/// bit 3 over-temperature, bit 4 tilt injected from GDB
+pub fn raw_flags() -> u8 {
+    unsafe {
+        ALERT_LEVEL as u8
+            | (MOVING as u8) << 2
+            | (OVER_TEMP as u8) << 3
+            | (TILT_OVERRIDE as u8) << 4
+    }
+}